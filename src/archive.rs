@@ -0,0 +1,250 @@
+// Imports a tar archive into the index, one entry at a time, so a released snapshot
+// can be brought under version control without unpacking it first.
+//
+// Regular files, executables and symlinks are staged; directory entries are implied by
+// their files. Long names in GNU (`L`) and pax (`x`) form are understood.
+
+use crate::constants::{BLOB, DIRECTORY_PATH, MODE_EXECUTABLE, MODE_FILE, MODE_SYMLINK};
+use crate::database;
+use crate::index::{self, IndexEntry};
+use std::io::{self, Read};
+use std::path::{Component, Path};
+
+const BLOCK_SIZE: usize = 512;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Reads a NUL-terminated string field
+fn field_str(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+// Reads a numeric field: octal text, or big-endian binary when the high bit is set
+fn field_number(field: &[u8]) -> io::Result<u64> {
+    if field.first().is_some_and(|&b| b & 0x80 != 0) {
+        return Ok(field[1..].iter().fold(0u64, |n, &b| (n << 8) | b as u64));
+    }
+    let text = field_str(field);
+    let text = text.trim_matches(|c: char| c == ' ' || c == '\0');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| invalid(format!("Invalid number in tar header: {:?}", text)))
+}
+
+// The header checksum is the byte sum with the checksum field itself counted as spaces
+fn verify_checksum(header: &[u8; BLOCK_SIZE]) -> io::Result<()> {
+    let expected = field_number(&header[148..156])?;
+    let actual: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+        .sum();
+    if actual != expected {
+        return Err(invalid("Tar header checksum mismatch".to_string()));
+    }
+    Ok(())
+}
+
+// Reads an entry's data and skips the padding up to the next block
+fn read_entry_data<R: Read>(reader: &mut R, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.by_ref().take(size).read_to_end(&mut data)?;
+    if (data.len() as u64) < size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Tar archive ends inside an entry"));
+    }
+    let padding = (BLOCK_SIZE - (size as usize % BLOCK_SIZE)) % BLOCK_SIZE;
+    io::copy(&mut reader.by_ref().take(padding as u64), &mut io::sink())?;
+    Ok(data)
+}
+
+// Picks the `path` and `linkpath` records out of a pax extended header
+fn parse_pax(data: &[u8]) -> (Option<String>, Option<String>) {
+    let (mut path, mut link) = (None, None);
+    let text = String::from_utf8_lossy(data);
+    // Each record is `<length> <key>=<value>\n`
+    for record in text.split_terminator('\n') {
+        if let Some((key, value)) = record.split_once(' ').and_then(|(_, rest)| rest.split_once('=')) {
+            match key {
+                "path" => path = Some(value.to_string()),
+                "linkpath" => link = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+    (path, link)
+}
+
+// Turns an archive member name into an index path, refusing anything outside the repository
+fn normalize_path(name: &str) -> io::Result<String> {
+    let mut parts = Vec::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            _ => return Err(invalid(format!("Refusing to import '{}': path leaves the repository", name))),
+        }
+    }
+    if parts.first().map(String::as_str) == Some(DIRECTORY_PATH) {
+        return Err(invalid(format!("Refusing to import '{}': path is inside {}", name, DIRECTORY_PATH)));
+    }
+    Ok(parts.join("/"))
+}
+
+// Stores every file in the archive as a blob and returns the index entries for them
+pub fn read_tar<R: Read>(mut reader: R) -> io::Result<Vec<IndexEntry>> {
+    let mut entries = Vec::new();
+    let mut long_name: Option<String> = None;
+    let mut long_link: Option<String> = None;
+
+    loop {
+        let mut header = [0u8; BLOCK_SIZE];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            // Archives that end without the trailing zero blocks are still accepted
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        verify_checksum(&header)?;
+
+        let size = field_number(&header[124..136])?;
+        let type_flag = header[156];
+        let data = read_entry_data(&mut reader, size)?;
+
+        let mut name = field_str(&header[0..100]);
+        if &header[257..262] == b"ustar" {
+            let prefix = field_str(&header[345..500]);
+            if !prefix.is_empty() {
+                name = format!("{}/{}", prefix, name);
+            }
+        }
+        let name = long_name.take().unwrap_or(name);
+        let link = long_link.take().unwrap_or_else(|| field_str(&header[157..257]));
+
+        match type_flag {
+            b'0' | b'\0' | b'7' => {
+                let mode = if field_number(&header[100..108])? & 0o111 != 0 { MODE_EXECUTABLE } else { MODE_FILE };
+                let blob_hash = database::store_data(&data, BLOB)?;
+                entries.push(IndexEntry { mode, blob_hash, path: normalize_path(&name)? });
+            }
+            b'2' => {
+                let blob_hash = database::store_data(link.as_bytes(), BLOB)?;
+                entries.push(IndexEntry { mode: MODE_SYMLINK, blob_hash, path: normalize_path(&name)? });
+            }
+            b'5' => {}
+            b'L' => long_name = Some(field_str(&data)),
+            b'K' => long_link = Some(field_str(&data)),
+            b'x' => (long_name, long_link) = parse_pax(&data),
+            b'g' => {}
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("Cannot import '{}': unsupported tar entry type '{}'", name, other as char),
+                ));
+            }
+        }
+    }
+
+    // A later member with the same name replaces an earlier one, as when extracting
+    entries.reverse();
+    let mut seen = std::collections::HashSet::new();
+    entries.retain(|entry| seen.insert(entry.path.clone()));
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+// Imports a tar archive into the index, returning the staged entries
+pub fn import_tar<R: Read>(reader: R) -> io::Result<Vec<IndexEntry>> {
+    // Blobs are stored under the index lock so gc cannot prune them before they are staged
+    let lock = index::lock_index()?;
+    let entries = read_tar(reader)?;
+    index::add_index_entries_locked(lock, &entries)?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+    use crate::tree;
+
+    // Builds a ustar header block for a member
+    fn header(name: &str, mode: u32, size: usize, type_flag: u8, link: &str) -> [u8; BLOCK_SIZE] {
+        let mut block = [0u8; BLOCK_SIZE];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        block[100..108].copy_from_slice(format!("{:07o}\0", mode).as_bytes());
+        block[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        block[156] = type_flag;
+        block[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[263..265].copy_from_slice(b"00");
+        block[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = block.iter().map(|&b| b as u32).sum();
+        block[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        block
+    }
+
+    fn member(archive: &mut Vec<u8>, name: &str, mode: u32, type_flag: u8, content: &[u8], link: &str) {
+        archive.extend_from_slice(&header(name, mode, content.len(), type_flag, link));
+        archive.extend_from_slice(content);
+        archive.resize(archive.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+    }
+
+    fn sample_archive() -> Vec<u8> {
+        let mut archive = Vec::new();
+        member(&mut archive, "project/", 0o755, b'5', b"", "");
+        member(&mut archive, "project/README", 0o644, b'0', b"hello\n", "");
+        member(&mut archive, "project/bin/run.sh", 0o755, b'0', b"#!/bin/sh\n", "");
+        member(&mut archive, "project/latest", 0o777, b'2', b"", "README");
+        let long_name = format!("project/{}.txt", "n".repeat(120));
+        member(&mut archive, "././@LongLink", 0o644, b'L', format!("{}\0", long_name).as_bytes(), "");
+        member(&mut archive, "truncated", 0o644, b'0', b"long", "");
+        archive.extend_from_slice(&[0u8; BLOCK_SIZE * 2]);
+        archive
+    }
+
+    #[test]
+    fn test_import_tar_stages_archive_contents() {
+        let _test_dir = TestDir::with_repo();
+
+        let entries = import_tar(&sample_archive()[..]).unwrap();
+        let summary: Vec<(String, u32)> = entries.iter().map(|entry| (entry.path.clone(), entry.mode)).collect();
+        assert_eq!(summary, vec![
+            ("project/README".to_string(), MODE_FILE),
+            ("project/bin/run.sh".to_string(), MODE_EXECUTABLE),
+            ("project/latest".to_string(), MODE_SYMLINK),
+            (format!("project/{}.txt", "n".repeat(120)), MODE_FILE),
+        ]);
+        assert_eq!(database::get_data(&entries[0].blob_hash).unwrap().2, b"hello\n");
+        assert_eq!(database::get_data(&entries[2].blob_hash).unwrap().2, b"README");
+
+        // The index and the tree built from it hold exactly the archive's files
+        let mut staged = index::load_index().unwrap();
+        staged.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(staged, entries);
+        let tree_hash = tree::create_tree(&staged).unwrap();
+        let mut flattened = tree::flatten_tree(&tree_hash).unwrap();
+        flattened.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(flattened, entries);
+    }
+
+    #[test]
+    fn test_import_tar_rejects_escaping_paths() {
+        let _test_dir = TestDir::with_repo();
+
+        let mut archive = Vec::new();
+        member(&mut archive, "../outside.txt", 0o644, b'0', b"nope", "");
+        assert!(import_tar(&archive[..]).is_err());
+        assert!(index::load_index().unwrap().is_empty());
+
+        let mut corrupted = sample_archive();
+        corrupted[0] = b'X';
+        assert!(read_tar(&corrupted[..]).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use clap::{Args, Parser, Subcommand};
+use rit::reflog;
 
 // Command line interface
 #[derive(Debug, Parser)]
@@ -11,7 +12,7 @@ pub struct RitArgs {
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     /// Initialize a new repository
-    Init,
+    Init(InitCommand),
 
     /// Remove the repository
     Remove,
@@ -28,14 +29,109 @@ pub enum Commands {
     /// Add the file to the staging area
     Add(AddCommand),
 
+    /// Remove files from the index and the working tree
+    Rm(RmCommand),
+
     /// List the contents of a tree object
     LsTree(LsTreeCommand),
 
+    /// List the files in the staging area
+    LsFiles(LsFilesCommand),
+
+    /// Store the staging area as a tree object and print its key
+    WriteTree,
+
+    /// Replace the staging area with the contents of a tree, leaving the working tree alone
+    ReadTree(ReadTreeCommand),
+
     /// Commit the changes in the staging area
     Commit(CommitCommand),
 
+    /// Show the commit history of the current branch
+    Log(LogCommand),
+
+    /// Show a commit's details and the changes it introduced
+    Show(ShowCommand),
+
     /// Show changes in directory
-    Status
+    Status(StatusCommand),
+
+    /// Remove objects that are no longer reachable
+    Gc(GcCommand),
+
+    /// Move the current branch to a commit, or reset index entries to their version in one
+    Reset(ResetCommand),
+
+    /// Check the object database for dangling objects
+    Fsck(FsckCommand),
+
+    /// Show the paths that differ between two commits or trees
+    DiffTree(DiffTreeCommand),
+
+    /// Show changes between the index and the working tree, or HEAD and the index
+    Diff(DiffCommand),
+
+    /// Show which paths are ignored and why
+    CheckIgnore(CheckIgnoreCommand),
+
+    /// List or create branches, or set the branch a branch tracks
+    Branch(BranchCommand),
+
+    /// Show or edit the log of where refs have pointed
+    Reflog(ReflogCommand),
+
+    /// Stage every file in a tar archive, optionally committing them
+    ImportArchive(ImportArchiveCommand),
+
+    /// Show which commit last changed each line of a file
+    Blame(BlameCommand),
+
+    /// Restore files, or the whole tree, from a commit into the working tree
+    Checkout(CheckoutCommand),
+
+    /// Get and set repository options
+    Config(ConfigCommand),
+
+    /// Switch to a branch, updating the working tree to its commit
+    Switch(SwitchCommand),
+
+    /// Merge another branch into the current one, or abort a merge in progress
+    Merge(MergeCommand),
+
+    /// Move every branch and tag into .rit/packed-refs
+    PackRefs(PackRefsCommand),
+
+    /// Create a tag, or list tags when no name is given
+    Tag(TagCommand),
+
+    /// Check that an annotated tag is well formed and its target exists
+    VerifyTag(VerifyTagCommand),
+
+    /// Write the branches, tags and their objects out as a git repository
+    ExportGit(ExportGitCommand)
+}
+
+#[derive(Debug, Args)]
+pub struct InitCommand {
+    /// The branch HEAD points to (defaults to $RIT_DEFAULT_BRANCH, then master)
+    #[clap(short = 'b', long)]
+    pub initial_branch: Option<String>,
+
+    /// Re-create missing parts of an existing repository
+    #[clap(long)]
+    pub reinit: bool,
+
+    /// The hash to address objects with: sha256 (the default) or blake3
+    #[clap(long, value_name = "FORMAT", conflicts_with = "reinit")]
+    pub object_format: Option<String>,
+
+    /// How to compress new objects: zstd (the default), lz4 or none
+    #[clap(long, value_name = "CODEC", conflicts_with = "reinit")]
+    pub compression: Option<String>,
+
+    /// The zstd level to compress new objects at, from 1 to 22 (defaults to 3)
+    #[clap(long, value_name = "LEVEL", conflicts_with = "reinit")]
+    pub compression_level: Option<i32>
 }
 
 #[derive(Debug, Args)]
@@ -46,6 +142,18 @@ pub struct HashObjectCommand {
 
 #[derive(Debug, Args)]
 pub struct CatFileCommand {
+    /// Show the object's contents parsed and labeled by type
+    #[clap(short = 'p', conflicts_with_all = ["show_type", "show_size"])]
+    pub pretty: bool,
+
+    /// Print only the object's type
+    #[clap(short = 't', conflicts_with = "show_size")]
+    pub show_type: bool,
+
+    /// Print only the object's size in bytes
+    #[clap(short = 's')]
+    pub show_size: bool,
+
     /// The key of the file
     pub key: String
 }
@@ -58,14 +166,53 @@ pub struct BlobCommand {
 
 #[derive(Debug, Args)]
 pub struct AddCommand {
-    /// The file to store
+    /// Add the file even if the ignore rules match it
+    #[clap(short, long)]
+    pub force: bool,
+
+    /// The file to stage, or a directory to stage every file beneath (`.` for the whole tree)
+    pub file: String
+}
+
+#[derive(Debug, Args)]
+pub struct RmCommand {
+    /// Only remove the file from the index, keeping the working copy
+    #[clap(long)]
+    pub cached: bool,
+
+    /// The tracked file to remove
     pub file: String
 }
 
 #[derive(Debug, Args)]
 pub struct LsTreeCommand {
     /// The key of the tree object
-    pub key: String
+    pub key: String,
+
+    /// Print only the names of the entries
+    #[clap(long)]
+    pub name_only: bool,
+
+    /// End each entry with NUL instead of a newline
+    #[clap(short = 'z')]
+    pub nul_terminated: bool
+}
+
+#[derive(Debug, Args)]
+pub struct LsFilesCommand {
+    /// Also show each entry's mode and blob hash
+    #[clap(short, long)]
+    pub stage: bool,
+
+    /// End each entry with NUL instead of a newline
+    #[clap(short = 'z')]
+    pub nul_terminated: bool
+}
+
+#[derive(Debug, Args)]
+pub struct ReadTreeCommand {
+    /// The tree, or a commit whose tree to read
+    pub tree: String
 }
 
 #[derive(Debug, Args)]
@@ -74,7 +221,387 @@ pub struct CommitCommand {
     #[clap(short, long, required = true)]
     pub message: String,
 
-    /// The committer name
-    #[clap(short, long, required = true)]
-    pub committer: String
-}
\ No newline at end of file
+    /// The committer, as `Name <email>` (defaults to user.name and user.email from the config)
+    #[clap(short, long)]
+    pub committer: Option<String>,
+
+    /// The author of the change, as `Name <email>`, when not the committer
+    #[clap(long)]
+    pub author: Option<String>,
+
+    /// Record the commit even if it changes nothing
+    #[clap(long)]
+    pub allow_empty: bool,
+
+    /// Show what would be committed without writing the commit
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Do not print the new commit hash
+    #[clap(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Also show the diff of what is being committed
+    #[clap(short, long)]
+    pub verbose: bool,
+
+    /// Record this UTC offset (+HHMM) instead of the local timezone
+    #[clap(long, value_name = "OFFSET", allow_hyphen_values = true)]
+    pub committer_tz: Option<String>,
+
+    /// Accept a committer whose email does not look like an address
+    #[clap(long)]
+    pub allow_invalid_identity: bool
+}
+
+#[derive(Debug, Args)]
+pub struct ShowCommand {
+    /// The commit to show
+    #[clap(default_value = "HEAD")]
+    pub commit: String,
+
+    /// List the changed paths with their status instead of printing patches
+    #[clap(long)]
+    pub name_status: bool
+}
+
+#[derive(Debug, Args)]
+pub struct LogCommand {
+    /// Show the patch each commit introduced
+    #[clap(short = 'p', long)]
+    pub patch: bool,
+
+    /// Show the oldest commits first
+    #[clap(long)]
+    pub reverse: bool,
+
+    /// Never show a commit before its parents
+    #[clap(long)]
+    pub topo_order: bool,
+
+    /// Show dates as `iso` local time (the default) or as `raw` seconds with their offset
+    #[clap(long, value_name = "FORMAT", require_equals = true, value_parser = ["raw", "iso"])]
+    pub date: Option<String>,
+
+    /// Show only merge commits, those with two or more parents
+    #[clap(long, conflicts_with = "no_merges")]
+    pub merges: bool,
+
+    /// Leave out merge commits
+    #[clap(long)]
+    pub no_merges: bool
+}
+
+#[derive(Debug, Args)]
+pub struct StatusCommand {
+    /// Show how many commits the current branch is ahead/behind the given branch
+    #[clap(long)]
+    pub compare: Option<String>,
+
+    /// Machine-readable output; only v2 is supported
+    #[clap(long, value_name = "VERSION", require_equals = true, value_parser = ["v2"])]
+    pub porcelain: Option<String>,
+
+    /// One `XY path` line per changed file, with git's two-letter status codes
+    #[clap(short, long, conflicts_with_all = ["porcelain", "nul_terminated", "compare"])]
+    pub short: bool,
+
+    /// Include the branch header in short or porcelain output
+    #[clap(short, long)]
+    pub branch: bool,
+
+    /// End porcelain entries with NUL instead of a newline; implies --porcelain=v2
+    #[clap(short = 'z')]
+    pub nul_terminated: bool,
+
+    /// Exit with status 1 when there are staged, unstaged or untracked changes
+    #[clap(long)]
+    pub exit_code: bool,
+
+    /// Whether to look for untracked files; `no` (as in `-uno`) skips the working-tree scan
+    #[clap(short = 'u', long, value_name = "MODE", default_value = "normal", value_parser = ["no", "normal", "all"])]
+    pub untracked_files: String,
+
+    /// Limit the status to these paths
+    pub pathspecs: Vec<String>
+}
+
+#[derive(Debug, Args)]
+pub struct GcCommand {
+    /// Print object counts and bytes reclaimed (default when run in a terminal)
+    #[clap(long, conflicts_with = "no_stat")]
+    pub stat: bool,
+
+    /// Do not print statistics
+    #[clap(long)]
+    pub no_stat: bool,
+
+    /// Only run when the number of objects exceeds gc.auto (default 6700)
+    #[clap(long)]
+    pub auto: bool,
+
+    /// List the objects that would be removed without removing them
+    #[clap(short = 'n', long, conflicts_with_all = ["auto", "prune_reflog"])]
+    pub dry_run: bool,
+
+    /// Expire old reflog entries before pruning
+    #[clap(long)]
+    pub prune_reflog: bool,
+
+    /// Age in days after which reflog entries expire
+    #[clap(long, value_name = "DAYS", default_value_t = reflog::DEFAULT_EXPIRE_DAYS, requires = "prune_reflog")]
+    pub expire: u64,
+
+    /// Age in days after which reflog entries for unreachable commits expire
+    #[clap(long, value_name = "DAYS", default_value_t = reflog::DEFAULT_EXPIRE_UNREACHABLE_DAYS, requires = "prune_reflog")]
+    pub expire_unreachable: u64
+}
+
+#[derive(Debug, Args)]
+pub struct ResetCommand {
+    /// Move the branch to the commit and make the index match it, keeping the working tree (the default)
+    #[clap(long, conflicts_with = "hard")]
+    pub mixed: bool,
+
+    /// Move the branch to the commit and make the index and working tree match it
+    #[clap(long)]
+    pub hard: bool,
+
+    /// An optional commit followed by the paths to reset
+    pub args: Vec<String>,
+
+    /// Paths to reset, given after `--`
+    #[clap(last = true)]
+    pub paths: Vec<String>
+}
+
+#[derive(Debug, Args)]
+pub struct FsckCommand {
+    /// Write dangling objects into .rit/lost-found
+    #[clap(long)]
+    pub lost_found: bool
+}
+
+#[derive(Debug, Args)]
+pub struct DiffTreeCommand {
+    /// The commit or tree to compare from
+    pub old: String,
+
+    /// The commit or tree to compare to
+    pub new: String,
+
+    /// Also pair similar files as renames, above the given similarity percent
+    #[clap(short = 'M', long = "find-renames", num_args = 0..=1, require_equals = true, default_missing_value = "50",
+           value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub find_renames: Option<u8>
+}
+
+#[derive(Debug, Args)]
+pub struct CheckIgnoreCommand {
+    /// Also show the pattern, file and line that matched
+    #[clap(short, long)]
+    pub verbose: bool,
+
+    /// The paths to check
+    #[clap(required = true)]
+    pub paths: Vec<String>
+}
+
+#[derive(Debug, Args)]
+pub struct BranchCommand {
+    /// Track the given branch (defaults to tracking from the current branch)
+    #[clap(short = 'u', long, value_name = "UPSTREAM")]
+    pub set_upstream_to: Option<String>,
+
+    /// The branch to create, or to set the upstream of
+    pub name: Option<String>
+}
+
+#[derive(Debug, Args)]
+pub struct DiffCommand {
+    /// Compare the index with HEAD instead of the working tree with the index
+    #[clap(long, alias = "staged")]
+    pub cached: bool,
+
+    /// One machine-readable line per changed path
+    #[clap(long)]
+    pub raw: bool,
+
+    /// Report whitespace errors in added lines and exit non-zero if there are any
+    #[clap(long, conflicts_with = "raw")]
+    pub check: bool,
+
+    /// Print patches, writing binary changes out in full so they can be applied
+    #[clap(long, conflicts_with_all = ["raw", "check"])]
+    pub binary: bool
+}
+
+#[derive(Debug, Args)]
+pub struct ReflogCommand {
+    #[clap(subcommand)]
+    pub action: Option<ReflogAction>
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ReflogAction {
+    /// Show the entries of a ref's log, newest first
+    Show {
+        /// The ref whose log to show
+        #[clap(default_value = "HEAD")]
+        reference: String
+    },
+
+    /// Remove every entry from the logs of the given refs
+    Expire {
+        /// Expire the logs of every ref
+        #[clap(long, required_unless_present = "refs")]
+        all: bool,
+
+        /// The refs whose logs to clear
+        #[clap(conflicts_with = "all")]
+        refs: Vec<String>
+    },
+
+    /// Remove single entries, given as <ref>@{<n>}
+    Delete {
+        /// The entries to delete
+        #[clap(required = true)]
+        entries: Vec<String>
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArchiveCommand {
+    /// The tar archive to import
+    pub archive: String,
+
+    /// Commit the imported files with this message
+    #[clap(short, long)]
+    pub message: Option<String>,
+
+    /// The committer, as `Name <email>` (defaults to user.name and user.email from the config)
+    #[clap(short, long, requires = "message")]
+    pub committer: Option<String>
+}
+
+#[derive(Debug, Args)]
+pub struct BlameCommand {
+    /// Machine-readable output in git's porcelain format
+    #[clap(long)]
+    pub porcelain: bool,
+
+    /// An optional revision (HEAD by default) followed by the file to blame
+    #[clap(required = true, num_args = 1..=2, value_names = ["REV", "FILE"])]
+    pub args: Vec<String>
+}
+
+#[derive(Debug, Args)]
+pub struct CheckoutCommand {
+    /// The commit to restore files from, or to check out entirely (detaching HEAD) when no paths are given; a branch name switches to it
+    #[clap(required_unless_present = "new_branch", conflicts_with = "new_branch")]
+    pub commit: Option<String>,
+
+    /// Create a branch at the current commit and switch to it, like `switch -c`
+    #[clap(short = 'b', value_name = "BRANCH", conflicts_with_all = ["index", "paths"])]
+    pub new_branch: Option<String>,
+
+    /// Also update the index to match the restored files
+    #[clap(long)]
+    pub index: bool,
+
+    /// Overwrite files even if they have uncommitted changes
+    #[clap(short, long)]
+    pub force: bool,
+
+    /// Files or directories to restore, given after `--`
+    #[clap(last = true)]
+    pub paths: Vec<String>
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigCommand {
+    /// List every option with its value
+    #[clap(short, long, conflicts_with_all = ["key", "unset"])]
+    pub list: bool,
+
+    /// Remove the option
+    #[clap(long, requires = "key", conflicts_with = "value")]
+    pub unset: bool,
+
+    /// The option, as section.name or section.subsection.name
+    #[clap(required_unless_present = "list")]
+    pub key: Option<String>,
+
+    /// The value to set; without one the current value is printed
+    pub value: Option<String>
+}
+
+#[derive(Debug, Args)]
+pub struct SwitchCommand {
+    /// Create the branch at the current commit before switching to it
+    #[clap(short, long)]
+    pub create: bool,
+
+    /// Switch even if tracked files have uncommitted changes, discarding them
+    #[clap(short, long)]
+    pub force: bool,
+
+    /// The branch to switch to
+    pub branch: String
+}
+
+#[derive(Debug, Args)]
+pub struct MergeCommand {
+    /// The branch or commit to merge
+    #[clap(required_unless_present = "abort", conflicts_with = "abort")]
+    pub branch: Option<String>,
+
+    /// The committer of the merge commit, as `Name <email>` (defaults to user.name and user.email from the config)
+    #[clap(short, long, conflicts_with = "abort")]
+    pub committer: Option<String>,
+
+    /// Abandon the merge in progress and restore the pre-merge state
+    #[clap(long)]
+    pub abort: bool
+}
+
+#[derive(Debug, Args)]
+pub struct TagCommand {
+    /// The tag to create; without it, every tag is listed
+    pub name: Option<String>,
+
+    /// The commit to tag
+    #[clap(default_value = "HEAD", requires = "name")]
+    pub commit: String,
+
+    /// Create an annotated tag object with this message
+    #[clap(short, long, requires = "name")]
+    pub message: Option<String>,
+
+    /// The tagger of an annotated tag, as `Name <email>` (defaults to user.name and user.email from the config)
+    #[clap(long, requires = "message")]
+    pub tagger: Option<String>,
+
+    /// Replace an existing tag of the same name
+    #[clap(short, long, requires = "name")]
+    pub force: bool
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyTagCommand {
+    /// The tag name or tag object key to verify
+    pub name: String
+}
+
+#[derive(Debug, Args)]
+pub struct ExportGitCommand {
+    /// The git directory to create, e.g. `../copy/.git` or a bare `../copy.git`
+    pub path: String
+}
+
+#[derive(Debug, Args)]
+pub struct PackRefsCommand {
+    /// Keep the loose ref files after packing them
+    #[clap(long)]
+    pub no_prune: bool
+}
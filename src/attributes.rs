@@ -0,0 +1,150 @@
+// Path attributes in the style of .gitattributes, read from `.ritattributes` at the
+// repository root. Each line is `<pattern> <attr>[=<value>]...`; `-attr` unsets an
+// attribute, and for any attribute the last matching line wins.
+//
+// Understood attributes:
+// - `binary` (shorthand for `-diff -merge -text`): never diff or line-merge the path
+// - `diff=<driver>`: names a diff driver; `diff.<driver>.binary = true` in the config
+//   makes the driver treat its files as binary
+
+use crate::config::Config;
+use crate::constants::RITATTRIBUTES_FILE;
+use crate::ignore::glob_match;
+use std::fs;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Set,
+    Unset,
+    Value(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AttributeRule {
+    glob: String,
+    anchored: bool,
+    attributes: Vec<(String, AttributeValue)>,
+}
+
+impl AttributeRule {
+    // Parses one line; blank lines, comments and lines without attributes yield nothing
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let mut fields = line.split_whitespace();
+        let pattern = fields.next()?;
+
+        let mut attributes = Vec::new();
+        for field in fields {
+            if field == "binary" {
+                for name in ["diff", "merge", "text"] {
+                    attributes.push((name.to_string(), AttributeValue::Unset));
+                }
+                attributes.push(("binary".to_string(), AttributeValue::Set));
+            } else if let Some(name) = field.strip_prefix('-') {
+                attributes.push((name.to_string(), AttributeValue::Unset));
+            } else if let Some((name, value)) = field.split_once('=') {
+                attributes.push((name.to_string(), AttributeValue::Value(value.to_string())));
+            } else {
+                attributes.push((field.to_string(), AttributeValue::Set));
+            }
+        }
+        if attributes.is_empty() {
+            return None;
+        }
+
+        // As in ignore files, a slash ties the pattern to the repository root
+        let anchored = pattern.contains('/');
+        let glob = pattern.strip_prefix('/').unwrap_or(pattern).to_string();
+        Some(AttributeRule { glob, anchored, attributes })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        if self.anchored {
+            glob_match(&self.glob, path)
+        } else {
+            glob_match(&self.glob, path.rsplit('/').next().unwrap_or(path))
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Attributes {
+    rules: Vec<AttributeRule>,
+}
+
+impl Attributes {
+    // Reads `.ritattributes`; a missing file means no attributes
+    pub fn load() -> io::Result<Self> {
+        match fs::read_to_string(RITATTRIBUTES_FILE) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        Attributes { rules: contents.lines().filter_map(AttributeRule::parse).collect() }
+    }
+
+    // The value of one attribute for a path, from the last line that mentions it
+    pub fn get(&self, path: &str, name: &str) -> Option<&AttributeValue> {
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| rule.matches(path))
+            .find_map(|rule| rule.attributes.iter().rev().find(|(attr, _)| attr == name).map(|(_, value)| value))
+    }
+
+    // The diff driver named for a path with `diff=<driver>`
+    pub fn diff_driver(&self, path: &str) -> Option<&str> {
+        match self.get(path, "diff") {
+            Some(AttributeValue::Value(driver)) => Some(driver),
+            _ => None,
+        }
+    }
+
+    // Whether a path must be treated as binary whatever its content looks like
+    pub fn is_binary(&self, path: &str, config: &Config) -> io::Result<bool> {
+        match self.get(path, "diff") {
+            Some(AttributeValue::Unset) => Ok(true),
+            Some(AttributeValue::Value(driver)) => Ok(config.get_bool(&format!("diff.{}.binary", driver))?.unwrap_or(false)),
+            _ => Ok(false),
+        }
+    }
+}
+
+// Whether the repository's attributes mark a path as binary
+pub fn is_binary_path(path: &str) -> io::Result<bool> {
+    Attributes::load()?.is_binary(path, &Config::load()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_match_attributes() {
+        let attributes = Attributes::parse("# assets\n*.png binary\ndocs/*.md diff=markdown\n*.lock -diff\n/vendor/*.png diff\n");
+
+        assert_eq!(attributes.get("img/logo.png", "binary"), Some(&AttributeValue::Set));
+        assert_eq!(attributes.get("img/logo.png", "diff"), Some(&AttributeValue::Unset));
+        assert_eq!(attributes.diff_driver("docs/guide.md"), Some("markdown"));
+        assert_eq!(attributes.diff_driver("guide.md"), None);
+        assert_eq!(attributes.get("Cargo.lock", "diff"), Some(&AttributeValue::Unset));
+        // A later line overrides an earlier one for the paths it matches
+        assert_eq!(attributes.get("vendor/icon.png", "diff"), Some(&AttributeValue::Set));
+        assert_eq!(attributes.get("vendor/icon.png", "merge"), Some(&AttributeValue::Unset));
+        assert_eq!(attributes.get("src/main.rs", "diff"), None);
+
+        let mut config = Config::default();
+        assert!(attributes.is_binary("img/logo.png", &config).unwrap());
+        assert!(!attributes.is_binary("vendor/icon.png", &config).unwrap());
+        assert!(!attributes.is_binary("docs/guide.md", &config).unwrap());
+        config.set("diff.markdown.binary", "true").unwrap();
+        assert!(attributes.is_binary("docs/guide.md", &config).unwrap());
+    }
+}
@@ -0,0 +1,66 @@
+// Base85 in git's alphabet, as used by binary patches. Every 4 bytes become 5 characters,
+// most significant first; a short final group is padded with zero bytes, so callers must
+// record the real length themselves.
+
+use std::io;
+
+const ALPHABET: &[u8; 85] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(4) * 5);
+    for group in data.chunks(4) {
+        let mut bytes = [0u8; 4];
+        bytes[..group.len()].copy_from_slice(group);
+        let mut value = u32::from_be_bytes(bytes);
+        let mut chars = [0u8; 5];
+        for slot in chars.iter_mut().rev() {
+            *slot = ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        out.extend(chars.iter().map(|&c| c as char));
+    }
+    out
+}
+
+// Decodes `len` bytes from base85 text, which must hold exactly the groups they need
+pub fn decode(text: &str, len: usize) -> io::Result<Vec<u8>> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid base85: {}", reason));
+    if text.len() != len.div_ceil(4) * 5 {
+        return Err(invalid("length does not match"));
+    }
+
+    let mut out = Vec::with_capacity(len + 3);
+    for group in text.as_bytes().chunks(5) {
+        let mut value: u64 = 0;
+        for &c in group {
+            let digit = ALPHABET.iter().position(|&a| a == c).ok_or_else(|| invalid("unexpected character"))?;
+            value = value * 85 + digit as u64;
+        }
+        let value = u32::try_from(value).map_err(|_| invalid("group out of range"))?;
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    if out[len..].iter().any(|&b| b != 0) {
+        return Err(invalid("non-zero padding"));
+    }
+    out.truncate(len);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base85_round_trip() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(&[0, 0, 0, 0]), "00000");
+        assert_eq!(encode(&[0xFF, 0xFF, 0xFF, 0xFF]), "|NsC0");
+        for len in 0..20 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37 + 11) as u8).collect();
+            assert_eq!(decode(&encode(&data), data.len()).unwrap(), data);
+        }
+        assert!(decode("0000", 3).is_err());
+        assert!(decode("0000\"", 3).is_err());
+        assert!(decode("|NsC1", 4).is_err());
+    }
+}
@@ -0,0 +1,319 @@
+// Attributes each line of a file to the commit that last changed it, walking
+// first parents back from a revision and diffing each version of the file against
+// the one before it.
+
+use crate::commit::{read_commit, Commit};
+use crate::date;
+use crate::diff::{blob_text, diff_lines, LineOp};
+use crate::refs;
+use crate::tree::flatten_tree;
+use std::collections::HashMap;
+use std::io;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlameLine {
+    // The commit that introduced the line
+    pub commit: String,
+    // The line's number in that commit's version of the file
+    pub orig_line: usize,
+    // The line's number in the blamed version
+    pub final_line: usize,
+    pub content: String,
+}
+
+// Finds the blob stored at a path in a commit
+fn blob_at(commit_hash: &str, path: &str) -> io::Result<Option<String>> {
+    let tree = read_commit(commit_hash)?.tree;
+    Ok(flatten_tree(&tree)?.into_iter().find(|entry| entry.path == path).map(|entry| entry.blob_hash))
+}
+
+fn text_of(blob_hash: &str, path: &str) -> io::Result<String> {
+    blob_text(blob_hash)?.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Cannot blame binary file '{}'", path)))
+}
+
+// For each line of `new`, the index of the same line in `old` if it was carried over unchanged
+fn line_origins(old: &str, new: &str) -> Vec<Option<usize>> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let mut origins = Vec::with_capacity(new_lines.len());
+    let mut old_index = 0;
+    for op in diff_lines(&old_lines, &new_lines) {
+        match op {
+            LineOp::Equal(_) => {
+                origins.push(Some(old_index));
+                old_index += 1;
+            }
+            LineOp::Delete(_) => old_index += 1,
+            LineOp::Insert(_) => origins.push(None),
+        }
+    }
+    origins
+}
+
+// Diffs between versions of a file, kept for the length of a blame run so each pair of
+// blobs is only diffed once
+#[derive(Default)]
+pub struct BlameCache {
+    origins: HashMap<(String, String), Rc<Vec<Option<usize>>>>,
+    texts: HashMap<String, Rc<String>>,
+    // How many diffs were actually computed
+    pub diffs: usize,
+}
+
+impl BlameCache {
+    fn text(&mut self, blob_hash: &str, path: &str) -> io::Result<Rc<String>> {
+        if let Some(text) = self.texts.get(blob_hash) {
+            return Ok(Rc::clone(text));
+        }
+        let text = Rc::new(text_of(blob_hash, path)?);
+        self.texts.insert(blob_hash.to_string(), Rc::clone(&text));
+        Ok(text)
+    }
+
+    fn line_origins(&mut self, old_blob: &str, new_blob: &str, path: &str) -> io::Result<Rc<Vec<Option<usize>>>> {
+        let key = (old_blob.to_string(), new_blob.to_string());
+        if let Some(origins) = self.origins.get(&key) {
+            return Ok(Rc::clone(origins));
+        }
+        let old = self.text(old_blob, path)?;
+        let new = self.text(new_blob, path)?;
+        let origins = Rc::new(line_origins(&old, &new));
+        self.diffs += 1;
+        self.origins.insert(key, Rc::clone(&origins));
+        Ok(origins)
+    }
+}
+
+// Blames every line of `path` as of the revision `rev`
+pub fn blame(rev: &str, path: &str) -> io::Result<Vec<BlameLine>> {
+    blame_with_cache(rev, path, &mut BlameCache::default())
+}
+
+// Blames with a cache that can be shared across several blames of the same file
+pub fn blame_with_cache(rev: &str, path: &str, cache: &mut BlameCache) -> io::Result<Vec<BlameLine>> {
+    let mut commit_hash = refs::resolve_commit(rev)?;
+    let mut blob = blob_at(&commit_hash, path)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No such path '{}' in {}", path, rev)))?;
+    let final_text = cache.text(&blob, path)?;
+    let final_lines: Vec<&str> = final_text.lines().collect();
+
+    let mut origins: Vec<Option<(String, usize)>> = vec![None; final_lines.len()];
+    // Lines not yet attributed: (index in the final file, index in the version being examined)
+    let mut pending: Vec<(usize, usize)> = (0..final_lines.len()).map(|i| (i, i)).collect();
+
+    while !pending.is_empty() {
+        let parent = read_commit(&commit_hash)?.parent;
+        let parent_blob = match &parent {
+            Some(parent) => blob_at(parent, path)?,
+            None => None,
+        };
+
+        let (parent, parent_blob) = match (parent, parent_blob) {
+            (Some(parent), Some(parent_blob)) => (parent, parent_blob),
+            // The file starts here, so this commit introduced everything still pending
+            _ => {
+                for (final_index, index) in pending.drain(..) {
+                    origins[final_index] = Some((commit_hash.clone(), index + 1));
+                }
+                break;
+            }
+        };
+
+        if parent_blob != blob {
+            let carried = cache.line_origins(&parent_blob, &blob, path)?;
+            let mut still_pending = Vec::new();
+            for (final_index, index) in pending {
+                match carried[index] {
+                    Some(parent_index) => still_pending.push((final_index, parent_index)),
+                    None => origins[final_index] = Some((commit_hash.clone(), index + 1)),
+                }
+            }
+            pending = still_pending;
+        }
+
+        commit_hash = parent;
+        blob = parent_blob;
+    }
+
+    Ok(final_lines
+        .iter()
+        .zip(origins)
+        .enumerate()
+        .map(|(index, (content, origin))| {
+            let (commit, orig_line) = origin.expect("every line is attributed");
+            BlameLine { commit, orig_line, final_line: index + 1, content: content.to_string() }
+        })
+        .collect())
+}
+
+// Splits `Name <email>` into the name and the bracketed email
+fn split_identity(identity: &str) -> (&str, &str) {
+    match identity.find('<') {
+        Some(start) => (identity[..start].trim_end(), &identity[start..]),
+        None => (identity, "<>"),
+    }
+}
+
+fn commit_header(commit: &Commit, path: &str) -> String {
+    let tz = date::format_offset(commit.timezone);
+    let mut header = String::new();
+    for (role, identity) in [("author", &commit.author), ("committer", &commit.committer)] {
+        let (name, email) = split_identity(identity);
+        header.push_str(&format!("{} {}\n", role, name));
+        header.push_str(&format!("{}-mail {}\n", role, email));
+        header.push_str(&format!("{}-time {}\n", role, commit.timestamp));
+        header.push_str(&format!("{}-tz {}\n", role, tz));
+    }
+    header.push_str(&format!("summary {}\n", commit.message.lines().next().unwrap_or("")));
+    match &commit.parent {
+        Some(parent) => header.push_str(&format!("previous {} {}\n", parent, path)),
+        None => header.push_str("boundary\n"),
+    }
+    header.push_str(&format!("filename {}\n", path));
+    header
+}
+
+// Renders blame in git's porcelain format: a `<commit> <orig> <final> [<count>]` line per
+// source line, the commit's details the first time it appears, then the tab-prefixed line
+pub fn porcelain(lines: &[BlameLine], path: &str) -> io::Result<String> {
+    let mut out = String::new();
+    let mut commits: HashMap<&str, Commit> = HashMap::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        // A group is a run of lines from one commit whose original lines are consecutive
+        let starts_group = index == 0
+            || lines[index - 1].commit != line.commit
+            || lines[index - 1].orig_line + 1 != line.orig_line;
+        out.push_str(&format!("{} {} {}", line.commit, line.orig_line, line.final_line));
+        if starts_group {
+            let size = lines[index..]
+                .iter()
+                .zip(line.orig_line..)
+                .take_while(|(next, orig)| next.commit == line.commit && next.orig_line == *orig)
+                .count();
+            out.push_str(&format!(" {}", size));
+        }
+        out.push('\n');
+
+        if !commits.contains_key(line.commit.as_str()) {
+            let commit = read_commit(&line.commit)?;
+            out.push_str(&commit_header(&commit, path));
+            commits.insert(&line.commit, commit);
+        }
+        out.push_str(&format!("\t{}\n", line.content));
+    }
+
+    Ok(out)
+}
+
+// Renders blame for people: `<short hash> (<name> <date> <line>) <content>`
+pub fn human(lines: &[BlameLine]) -> io::Result<String> {
+    let mut commits: HashMap<&str, Commit> = HashMap::new();
+    for line in lines {
+        if !commits.contains_key(line.commit.as_str()) {
+            commits.insert(&line.commit, read_commit(&line.commit)?);
+        }
+    }
+
+    let width = lines.len().to_string().len();
+    let mut out = String::new();
+    for line in lines {
+        let commit = &commits[line.commit.as_str()];
+        let (name, _) = split_identity(&commit.author);
+        out.push_str(&format!(
+            "{} ({} {} {:>width$}) {}\n",
+            &line.commit[..8.min(line.commit.len())],
+            name,
+            date::format_iso(commit.timestamp, commit.timezone),
+            line.final_line,
+            line.content,
+            width = width
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::commit_with_timezone;
+    use crate::test_utils::{stage_files, TestDir};
+
+    fn commit_file(content: &str, message: &str, committer: &str) -> String {
+        stage_files(&[("file.txt", content)]);
+        commit_with_timezone(message, committer, 60).unwrap()
+    }
+
+    #[test]
+    fn test_blame_attributes_lines() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_file("one\ntwo\nthree\n", "first", "Ann <ann@example.com>");
+        let second = commit_file("one\n2\nthree\nfour\n", "second", "Bob <bob@example.com>");
+        let third = commit_file("zero\none\n2\nthree\nfour\n", "third", "Ann <ann@example.com>");
+
+        let lines = blame("HEAD", "file.txt").unwrap();
+        let summary: Vec<(&str, usize, usize)> = lines.iter().map(|l| (l.commit.as_str(), l.orig_line, l.final_line)).collect();
+        assert_eq!(summary, vec![
+            (third.as_str(), 1, 1),
+            (first.as_str(), 1, 2),
+            (second.as_str(), 2, 3),
+            (first.as_str(), 3, 4),
+            (second.as_str(), 4, 5),
+        ]);
+
+        // Blaming an older revision only sees the history up to it
+        let older = blame("HEAD~1", "file.txt").unwrap();
+        assert_eq!(older[0].commit, first);
+        assert!(blame("HEAD", "missing.txt").is_err());
+    }
+
+    #[test]
+    fn test_blame_diffs_each_version_pair_once() {
+        let _test_dir = TestDir::with_repo();
+        let mut content = String::new();
+        for version in 0..20 {
+            content.push_str(&format!("line {}\n", version));
+            commit_file(&content, &format!("version {}", version), "Ann <ann@example.com>");
+        }
+
+        // One diff per change in the file's history, however many lines there are
+        let mut cache = BlameCache::default();
+        let lines = blame_with_cache("HEAD", "file.txt", &mut cache).unwrap();
+        assert_eq!(lines.len(), 20);
+        assert_eq!(cache.diffs, 19);
+
+        // Blaming older revisions again reuses every diff already computed
+        for back in 1..10 {
+            blame_with_cache(&format!("HEAD~{}", back), "file.txt", &mut cache).unwrap();
+        }
+        assert_eq!(cache.diffs, 19);
+        assert_eq!(blame("HEAD", "file.txt").unwrap(), lines);
+    }
+
+    #[test]
+    fn test_porcelain_headers() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_file("one\ntwo\n", "first", "Ann <ann@example.com>");
+        let second = commit_file("one\ntwo\nthree\n", "add three", "Bob <bob@example.com>");
+
+        let lines = blame("HEAD", "file.txt").unwrap();
+        let out = porcelain(&lines, "file.txt").unwrap();
+        let out_lines: Vec<&str> = out.lines().collect();
+
+        // The first line opens a group of two lines from the first commit, with its details
+        assert_eq!(out_lines[0], format!("{} 1 1 2", first));
+        assert_eq!(out_lines[1], "author Ann");
+        assert_eq!(out_lines[2], "author-mail <ann@example.com>");
+        assert!(out_lines[3].starts_with("author-time "));
+        assert_eq!(out_lines[4], "author-tz +0100");
+        assert!(out.contains("summary first\nboundary\nfilename file.txt\n\tone\n"));
+
+        // The second line continues the group, so only the hashes and the line follow
+        assert!(out.contains(&format!("\tone\n{} 2 2\n\ttwo\n", first)));
+
+        // The third line comes from the second commit, which records its parent
+        assert!(out.contains(&format!("{} 3 3 1\nauthor Bob\n", second)));
+        assert!(out.contains(&format!("summary add three\nprevious {} file.txt\nfilename file.txt\n\tthree\n", first)));
+    }
+}
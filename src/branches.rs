@@ -1,13 +1,16 @@
-use std::{fs, io::{self, ErrorKind, Write}};
+use std::{fs, io::{self, ErrorKind, Write}, path::Path};
 use crate::{database::get_data, tree::{read_tree, TreeEntry}};
-use crate::constants::{head_file_path, refs_dir_path, heads_dir_path};
+use crate::constants::{head_file_path, heads_dir_path, HEADS_DIR, REFS_DIR};
+use crate::packed_refs;
 use crate::commit::commit_tree_hash_from_data;
+use crate::refs::validate_ref_name;
+use crate::config::Config;
 
-pub fn init_branches() -> io::Result<()> {
+pub fn init_branches(default_branch: &str) -> io::Result<()> {
     // Make HEAD file
     let head_file = head_file_path();
     let mut file = std::fs::File::create(&head_file)?;
-    file.write_all(b"ref: refs/heads/master\n")?;
+    file.write_all(format!("ref: refs/heads/{}\n", default_branch).as_bytes())?;
     
     // Make refs/heads directory
     let heads_dir = heads_dir_path();
@@ -17,14 +20,47 @@ pub fn init_branches() -> io::Result<()> {
 }
 
 pub fn create_banch(branch_name: &str, commit_hash: &str) -> io::Result<()> {
+    validate_ref_name(branch_name)?;
     let branch_file = heads_dir_path().join(branch_name);
+    // A name like feature/login lives in a directory under refs/heads
+    if let Some(parent) = branch_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
     let mut file = std::fs::File::create(&branch_file)?;
     file.write_all(commit_hash.as_bytes())?;
     Ok(())
 }
 
+// Points HEAD at an existing branch
+pub fn set_head_to_branch(branch_name: &str) -> io::Result<()> {
+    validate_ref_name(branch_name)?;
+    if get_commit_hash(branch_name)?.is_none() {
+        return Err(io::Error::new(ErrorKind::NotFound, format!("Branch '{}' does not exist", branch_name)));
+    }
+    fs::write(head_file_path(), format!("ref: refs/heads/{}\n", branch_name))
+}
+
+// Points HEAD straight at a commit, leaving every branch where it is
+pub fn detach_head(commit_hash: &str) -> io::Result<()> {
+    fs::write(head_file_path(), format!("{}\n", commit_hash))
+}
+
+// Returns the commit HEAD names when it is detached from any branch
+pub fn get_detached_head() -> Option<String> {
+    let head = fs::read_to_string(head_file_path()).ok()?;
+    let head = head.trim();
+    if head.is_empty() || head.starts_with("ref:") {
+        return None;
+    }
+    Some(head.to_string())
+}
+
+// Moves the current branch to a commit, or a detached HEAD when no branch is checked out
 pub fn update_current_branch(commit_hash: &str) -> io::Result<()> {
-    let branch_name = get_current_branch_name().expect("HEAD file is not set to a branch");
+    let branch_name = match get_current_branch_name() {
+        Some(branch_name) => branch_name,
+        None => return detach_head(commit_hash),
+    };
     let branch_file = heads_dir_path().join(branch_name);
     let mut file = std::fs::File::create(&branch_file)?;
     file.write_all(commit_hash.as_bytes())?;
@@ -39,36 +75,96 @@ pub fn get_current_branch_name() -> Option<String> {
     if head_parts.len() < 2 {
         return None;
     }
-    let branch_ref = head_parts[1];
-    let branch_parts: Vec<&str> = branch_ref.split('/').collect();
-    if branch_parts.len() < 3 {
+    // Everything after refs/heads/, so feature/login keeps its slash
+    let branch_name = head_parts[1].strip_prefix(&format!("{}/{}/", REFS_DIR, HEADS_DIR))?;
+    if branch_name.is_empty() {
         return None;
     }
-    Some(branch_parts[2].to_string())
+    Some(branch_name.to_string())
 }
 
+// Returns the commit HEAD is at, through the current branch or a detached HEAD
 pub fn get_current_branch_commit_hash() -> io::Result<Option<String>> {
     if let Some(branch_name) = get_current_branch_name() {
         get_commit_hash(&branch_name)
     } else {
-        Ok(None)
+        Ok(get_detached_head())
     }
 }
 
-pub fn get_current_tree_from_commit_hash()-> Vec<TreeEntry>{
-    let commit_hash = get_current_branch_commit_hash().unwrap().unwrap();
-    let commit_hash_str: &str = &commit_hash;
-    let (_,_,data) = get_data(commit_hash_str).unwrap();
-    let tree = commit_tree_hash_from_data(data);
-    let tree_entry = read_tree(&tree).unwrap();
-    tree_entry
+// Returns the root tree entries of the current commit, or nothing when there are no commits yet
+pub fn get_current_tree_from_commit_hash() -> io::Result<Vec<TreeEntry>> {
+    let commit_hash = match get_current_branch_commit_hash()? {
+        Some(commit_hash) => commit_hash,
+        None => return Ok(Vec::new()),
+    };
+    let (_, _, data) = get_data(&commit_hash)?;
+    let tree = commit_tree_hash_from_data(data)?;
+    read_tree(&tree)
+}
+
+// Lists the names of every branch under refs/heads
+pub fn list_branches() -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    let heads_dir = heads_dir_path();
+    if !heads_dir.exists() {
+        return Ok(names);
+    }
+
+    names.extend(ref_names_under(&heads_dir)?);
+
+    // Branches that only exist in packed-refs
+    names.extend(packed_refs::packed_names(HEADS_DIR)?);
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+// Lists the ref files under a directory by their paths relative to it, descending into
+// the directories that names with slashes create, and skipping leftover lock files
+pub(crate) fn ref_names_under(dir: &Path) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() {
+            names.extend(ref_names_under(&entry.path())?.into_iter().map(|nested| format!("{}/{}", name, nested)));
+        } else if !name.ends_with(".lock") {
+            names.push(name);
+        }
+    }
+    Ok(names)
 }
 
 pub fn get_commit_hash(branch_name: &str) -> io::Result<Option<String>> {
     let branch_file = heads_dir_path().join(branch_name);
     match fs::read_to_string(&branch_file) {
         Ok(commit_hash) => Ok(Some(commit_hash.trim().to_string())),
-        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None), // Return None if the branch has no commits
+        // Fall back to packed-refs, and None if the branch has no commits
+        Err(ref e) if e.kind() == ErrorKind::NotFound => packed_refs::packed_ref(&format!("{}/{}/{}", REFS_DIR, HEADS_DIR, branch_name)),
         Err(e) => Err(e), // Propagate other errors
     }
-}
\ No newline at end of file
+}
+
+// Records `upstream` as the branch `branch_name` tracks, as git's branch.<name>.remote/.merge
+pub fn set_upstream(branch_name: &str, upstream: &str) -> io::Result<()> {
+    validate_ref_name(branch_name)?;
+    if get_commit_hash(upstream)?.is_none() {
+        return Err(io::Error::new(ErrorKind::NotFound, format!("Upstream branch '{}' does not exist", upstream)));
+    }
+
+    let mut config = Config::load()?;
+    // "." marks the upstream as a branch of this repository
+    config.set(&format!("branch.{}.remote", branch_name), ".")?;
+    config.set(&format!("branch.{}.merge", branch_name), &format!("refs/heads/{}", upstream))?;
+    config.save()
+}
+
+// Returns the local branch the given branch tracks, if one is configured
+pub fn get_upstream(branch_name: &str) -> io::Result<Option<String>> {
+    let merge = match Config::load()?.get(&format!("branch.{}.merge", branch_name)) {
+        Some(merge) => merge.to_string(),
+        None => return Ok(None),
+    };
+    Ok(Some(merge.strip_prefix("refs/heads/").unwrap_or(&merge).to_string()))
+}
@@ -0,0 +1,468 @@
+// Restores files from a commit into the working tree.
+
+use crate::branches;
+use crate::commit::read_commit;
+use crate::constants::{MODE_EXECUTABLE, MODE_SYMLINK};
+use crate::database;
+use crate::index::{self, add_index_entries, file_changes, load_index, matches_any_pathspec, matches_pathspec, IndexEntry};
+use crate::reflog::{self, ReflogEntry};
+use crate::refs::{resolve_commit, resolve_ref};
+use crate::tree::flatten_tree;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Writes one tree entry's blob to its path, creating parent directories and keeping
+// the executable bit and symlinks where the platform supports them
+pub fn write_entry(entry: &IndexEntry) -> io::Result<()> {
+    let (_, _, data) = database::get_data(&entry.blob_hash)?;
+    let path = Path::new(&entry.path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    if entry.mode == MODE_SYMLINK {
+        use std::os::unix::ffi::OsStrExt;
+        if path.symlink_metadata().is_ok() {
+            fs::remove_file(path)?;
+        }
+        return std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(&data), path);
+    }
+
+    fs::write(path, &data)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if entry.mode == MODE_EXECUTABLE { 0o755 } else { 0o644 };
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(())
+}
+
+// Moves the working tree from one set of tracked entries to another: writes every entry
+// whose working file differs and removes the files only the old set tracked
+pub fn switch_entries(old: &[IndexEntry], new: &[IndexEntry]) -> io::Result<()> {
+    let new_paths: HashSet<&str> = new.iter().map(|entry| entry.path.as_str()).collect();
+    for entry in old {
+        if !new_paths.contains(entry.path.as_str()) {
+            remove_entry(&entry.path)?;
+        }
+    }
+
+    for entry in new {
+        if !working_file_matches(entry)? {
+            write_entry(entry)?;
+        }
+    }
+    Ok(())
+}
+
+// Paths the new entries would write over where a file the old entries do not track sits
+// with different content. Those files exist nowhere else, so replacing them loses work.
+pub fn untracked_overwrites(old: &[IndexEntry], new: &[IndexEntry]) -> io::Result<Vec<String>> {
+    let old_paths: HashSet<&str> = old.iter().map(|entry| entry.path.as_str()).collect();
+    let mut paths = Vec::new();
+    for entry in new {
+        if old_paths.contains(entry.path.as_str()) || Path::new(&entry.path).symlink_metadata().is_err() {
+            continue;
+        }
+        if !working_file_matches(entry)? {
+            paths.push(entry.path.clone());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// Fails, naming the files, if moving from the old entries to the new ones would overwrite
+// untracked files
+pub fn refuse_untracked_overwrites(old: &[IndexEntry], new: &[IndexEntry], command: &str) -> io::Result<()> {
+    let paths = untracked_overwrites(old, new)?;
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let paths: Vec<String> = paths.iter().map(|path| format!("\t{}", path)).collect();
+    Err(io::Error::other(format!(
+        "The following untracked working tree files would be overwritten by {}:\n{}\nMove or remove them before you {}",
+        command,
+        paths.join("\n"),
+        command
+    )))
+}
+
+// Whether the file at the entry's path is a regular file already holding the entry's content
+fn working_file_matches(entry: &IndexEntry) -> io::Result<bool> {
+    let path = Path::new(&entry.path);
+    match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_file() && entry.mode != MODE_SYMLINK => Ok(database::hash_working_file(path)? == entry.blob_hash),
+        _ => Ok(false),
+    }
+}
+
+// Deletes a tracked file and any directories it leaves empty
+fn remove_entry(path: &str) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    let mut dir = Path::new(path).parent();
+    while let Some(parent) = dir.filter(|p| !p.as_os_str().is_empty()) {
+        if fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+
+// Whether restoring the entry would lose work: the working file differs from both the
+// index and the version being restored. An untracked file counts as differing from the index.
+fn would_clobber(entry: &IndexEntry, index: &[IndexEntry]) -> io::Result<bool> {
+    let path = Path::new(&entry.path);
+    if !path.is_file() {
+        return Ok(false);
+    }
+    let working_hash = database::hash_working_file(path)?;
+    let staged = index.iter().find(|staged| staged.path == entry.path).map(|staged| staged.blob_hash.as_str());
+    Ok(working_hash != entry.blob_hash && Some(working_hash.as_str()) != staged)
+}
+
+// Restores every file matching the pathspecs from the commit into the working tree, and
+// into the index too when `update_index` is set. Returns the restored paths.
+// Unless `force` is set, nothing is restored if that would overwrite uncommitted changes.
+pub fn checkout_paths(rev: &str, pathspecs: &[String], update_index: bool, force: bool) -> io::Result<Vec<String>> {
+    if pathspecs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "No paths given to checkout"));
+    }
+
+    let entries: Vec<IndexEntry> = flatten_tree(&read_commit(&resolve_commit(rev)?)?.tree)?
+        .into_iter()
+        .filter(|entry| matches_any_pathspec(&entry.path, pathspecs))
+        .collect();
+
+    // Like git, a pathspec that names nothing is an error rather than a silent no-op
+    for pathspec in pathspecs {
+        if !entries.iter().any(|entry| matches_pathspec(&entry.path, pathspec)) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("pathspec '{}' did not match any file(s) in {}", pathspec, rev),
+            ));
+        }
+    }
+
+    if !force {
+        let index = load_index()?;
+        let mut clobbered = Vec::new();
+        for entry in &entries {
+            if would_clobber(entry, &index)? {
+                clobbered.push(format!("\t{}", entry.path));
+            }
+        }
+        if !clobbered.is_empty() {
+            return Err(io::Error::other(format!(
+                "Your local changes to the following files would be overwritten by checkout:\n{}\nCommit or stage them, or use --force to discard them",
+                clobbered.join("\n")
+            )));
+        }
+    }
+
+    for entry in &entries {
+        write_entry(entry)?;
+    }
+    if update_index {
+        add_index_entries(&entries)?;
+    }
+
+    Ok(entries.into_iter().map(|entry| entry.path).collect())
+}
+
+// Tracked paths with uncommitted changes: staged against HEAD, or modified or deleted in
+// the working tree. Untracked files do not count. The paths come back sorted.
+pub fn uncommitted_changes() -> io::Result<Vec<String>> {
+    let head: HashMap<String, String> = match resolve_ref("HEAD") {
+        Ok(commit_hash) => flatten_tree(&read_commit(&commit_hash)?.tree)?
+            .into_iter()
+            .map(|entry| (entry.path, entry.blob_hash))
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+        Err(e) => return Err(e),
+    };
+    let index = load_index()?;
+
+    let mut paths: HashSet<String> = HashSet::new();
+    for entry in &index {
+        if head.get(&entry.path) != Some(&entry.blob_hash) {
+            paths.insert(entry.path.clone());
+        }
+    }
+    for path in head.keys() {
+        if !index.iter().any(|entry| &entry.path == path) {
+            paths.insert(path.clone());
+        }
+    }
+    for (path, change) in file_changes(Path::new(""))? {
+        if change == "modified" || change == "deleted" {
+            paths.insert(path);
+        }
+    }
+
+    let mut paths: Vec<String> = paths.into_iter().collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn refuse_uncommitted_changes(command: &str) -> io::Result<()> {
+    let changed = uncommitted_changes()?;
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let paths: Vec<String> = changed.iter().map(|path| format!("\t{}", path)).collect();
+    Err(io::Error::other(format!(
+        "Your local changes to the following files would be overwritten by {}:\n{}\nCommit them, or use --force to discard them",
+        command,
+        paths.join("\n")
+    )))
+}
+
+// Makes the working tree and the index match a commit's tree, removing tracked files the
+// commit does not have, and detaches HEAD at the commit so later commits leave every
+// branch where it is. Unless `force` is set, nothing is touched while tracked files have
+// uncommitted changes. Returns the commit.
+pub fn checkout_commit(rev: &str, force: bool) -> io::Result<String> {
+    let current = branches::get_current_branch_commit_hash()?;
+    let commit_hash = resolve_commit(rev)?;
+    let commit = read_commit(&commit_hash)?;
+    let entries = flatten_tree(&commit.tree)?;
+
+    let lock = index::lock_index()?;
+    let index = load_index()?;
+    if !force {
+        refuse_uncommitted_changes("checkout")?;
+        refuse_untracked_overwrites(&index, &entries, "checkout")?;
+    }
+
+    switch_entries(&index, &entries)?;
+    index::save_index_locked(lock, &entries)?;
+
+    let from = branches::get_current_branch_name().unwrap_or_else(|| "HEAD".to_string());
+    branches::detach_head(&commit_hash)?;
+    record_checkout(current, &commit_hash, commit.committer, &from, &commit_hash)?;
+    Ok(commit_hash)
+}
+
+// Logs a move of HEAD under the identity of its latest entry, so it can be found with `HEAD@{1}`
+fn record_checkout(old: Option<String>, new: &str, fallback_identity: String, from: &str, to: &str) -> io::Result<()> {
+    let identity = reflog::read_reflog("HEAD")?.pop().map(|entry| entry.committer).unwrap_or(fallback_identity);
+    let entry = ReflogEntry {
+        old: old.unwrap_or_else(|| new.to_string()),
+        new: new.to_string(),
+        committer: identity,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        message: format!("checkout: moving from {} to {}", from, to),
+    };
+    reflog::append("HEAD", &entry)
+}
+
+// Points HEAD at a branch and makes the working tree and index match its tip, creating
+// the branch at the current commit first when `create` is set. Switching to a branch at
+// the current commit keeps local changes; otherwise they must be committed first unless
+// `force` is set. Returns the branch's commit.
+pub fn switch_branch(name: &str, create: bool, force: bool) -> io::Result<String> {
+    let current = branches::get_current_branch_commit_hash()?;
+    if create {
+        let head = current.clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Cannot create a branch before the first commit"))?;
+        if branches::get_commit_hash(name)?.is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("A branch named '{}' already exists", name)));
+        }
+        branches::create_banch(name, &head)?;
+    }
+    let commit_hash = branches::get_commit_hash(name)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Branch '{}' does not exist", name)))?;
+    let commit = read_commit(&commit_hash)?;
+
+    let lock = index::lock_index()?;
+    if current.as_deref() != Some(commit_hash.as_str()) {
+        let index = load_index()?;
+        let entries = flatten_tree(&commit.tree)?;
+        if !force {
+            refuse_uncommitted_changes("switch")?;
+            refuse_untracked_overwrites(&index, &entries, "switch")?;
+        }
+        switch_entries(&index, &entries)?;
+        index::save_index_locked(lock, &entries)?;
+    } else {
+        drop(lock);
+    }
+
+    let from = branches::get_current_branch_name().unwrap_or_else(|| "HEAD".to_string());
+    branches::set_head_to_branch(name)?;
+
+    record_checkout(current, &commit_hash, commit.committer, &from, name)?;
+
+    Ok(commit_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{commit_files, TestDir};
+
+    fn index_hash(path: &str) -> Option<String> {
+        load_index().unwrap().into_iter().find(|e| e.path == path).map(|e| e.blob_hash)
+    }
+
+    #[test]
+    fn test_checkout_restores_several_paths() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_files(&[("a.txt", "a v1"), ("b.txt", "b v1"), ("docs/one.md", "one v1"), ("docs/two.md", "two v1")], "first");
+        commit_files(&[("a.txt", "a v2"), ("b.txt", "b v2"), ("docs/one.md", "one v2"), ("docs/two.md", "two v2")], "second");
+        let a_staged = index_hash("a.txt");
+
+        let restored = checkout_paths(&first, &["a.txt".to_string(), "docs".to_string()], false, false).unwrap();
+        assert_eq!(restored, vec!["a.txt", "docs/one.md", "docs/two.md"]);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v1");
+        assert_eq!(fs::read_to_string("docs/one.md").unwrap(), "one v1");
+        assert_eq!(fs::read_to_string("docs/two.md").unwrap(), "two v1");
+        assert_eq!(fs::read_to_string("b.txt").unwrap(), "b v2");
+        // Without --index the staged version is untouched
+        assert_eq!(index_hash("a.txt"), a_staged);
+
+        checkout_paths(&first, &["b.txt".to_string()], true, false).unwrap();
+        assert_eq!(fs::read_to_string("b.txt").unwrap(), "b v1");
+        assert_eq!(index_hash("b.txt"), Some(database::blob_key(b"b v1").unwrap()));
+
+        assert!(checkout_paths(&first, &["missing.txt".to_string()], false, false).is_err());
+    }
+
+    #[test]
+    fn test_checkout_refuses_to_overwrite_local_changes() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_files(&[("a.txt", "a v1"), ("b.txt", "b v1")], "first");
+        commit_files(&[("a.txt", "a v2"), ("b.txt", "b v2")], "second");
+
+        // A file matching the index is restored without fuss
+        checkout_paths(&first, &["a.txt".to_string()], false, false).unwrap();
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v1");
+
+        // An unstaged edit would be lost, so nothing is touched
+        fs::write("b.txt", "b edited").unwrap();
+        let error = checkout_paths(&first, &["a.txt".to_string(), "b.txt".to_string()], false, false).unwrap_err();
+        assert!(error.to_string().contains("\tb.txt"), "{}", error);
+        assert!(!error.to_string().contains("a.txt"));
+        assert_eq!(fs::read_to_string("b.txt").unwrap(), "b edited");
+
+        // --force discards the edit
+        checkout_paths(&first, &["b.txt".to_string()], false, true).unwrap();
+        assert_eq!(fs::read_to_string("b.txt").unwrap(), "b v1");
+    }
+
+    #[test]
+    fn test_checkout_commit_restores_whole_tree() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_files(&[("a.txt", "a v1"), ("docs/one.md", "one v1")], "first");
+        let second = commit_files(&[("a.txt", "a v2"), ("docs/two.md", "two v2")], "second");
+
+        assert_eq!(checkout_commit(&first, false).unwrap(), first);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v1");
+        assert_eq!(fs::read_to_string("docs/one.md").unwrap(), "one v1");
+        assert!(!Path::new("docs/two.md").exists());
+        let mut paths: Vec<String> = load_index().unwrap().into_iter().map(|e| e.path).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["a.txt", "docs/one.md"]);
+
+        // Back at the tip, a local edit blocks the checkout until --force is given
+        checkout_commit(&second, true).unwrap();
+        fs::write("a.txt", "edited").unwrap();
+        let error = checkout_commit(&first, false).unwrap_err();
+        assert!(error.to_string().contains("\ta.txt"), "{}", error);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "edited");
+        checkout_commit(&first, true).unwrap();
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v1");
+    }
+
+    #[test]
+    fn test_checkout_commit_detaches_head() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_files(&[("a.txt", "a v1")], "first");
+        let second = commit_files(&[("a.txt", "a v2")], "second");
+
+        checkout_commit(&first, false).unwrap();
+        assert_eq!(branches::get_current_branch_name(), None);
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), Some(first.clone()));
+        assert_eq!(reflog::read_reflog("HEAD").unwrap().pop().unwrap().message, format!("checkout: moving from master to {}", first));
+
+        // Committing moves only the detached HEAD
+        let detached = commit_files(&[("a.txt", "a detached")], "detached");
+        assert_eq!(read_commit(&detached).unwrap().parent, Some(first));
+        assert_eq!(branches::get_commit_hash("master").unwrap(), Some(second.clone()));
+        assert_eq!(branches::get_detached_head(), Some(detached));
+
+        assert_eq!(switch_branch("master", false, false).unwrap(), second);
+        assert_eq!(branches::get_current_branch_name().as_deref(), Some("master"));
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v2");
+    }
+
+    #[test]
+    fn test_switch_refuses_to_overwrite_untracked_files() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_files(&[("a.txt", "a v1")], "first");
+        switch_branch("feature", true, false).unwrap();
+        let feature = commit_files(&[("notes.txt", "branch notes")], "notes");
+        switch_branch("master", false, false).unwrap();
+        fs::write("notes.txt", "my own notes").unwrap();
+
+        let error = switch_branch("feature", false, false).unwrap_err();
+        assert!(error.to_string().contains("untracked working tree files would be overwritten by switch:\n\tnotes.txt"), "{}", error);
+        assert_eq!(branches::get_current_branch_name().as_deref(), Some("master"));
+        assert!(checkout_commit(&feature, false).is_err());
+        assert!(crate::merge::start_merge_with("feature", "Tester <tester@example.com>").is_err());
+        assert_eq!(fs::read_to_string("notes.txt").unwrap(), "my own notes");
+        assert_eq!(branches::get_commit_hash("master").unwrap(), Some(first));
+
+        // A file that already holds the branch's content is not in the way
+        fs::write("notes.txt", "branch notes").unwrap();
+        assert_eq!(untracked_overwrites(&load_index().unwrap(), &flatten_tree(&read_commit(&feature).unwrap().tree).unwrap()).unwrap(), Vec::<String>::new());
+
+        // --force replaces the file
+        fs::write("notes.txt", "my own notes").unwrap();
+        switch_branch("feature", false, true).unwrap();
+        assert_eq!(fs::read_to_string("notes.txt").unwrap(), "branch notes");
+    }
+
+    #[test]
+    fn test_switch_branch_moves_head_and_tree() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_files(&[("a.txt", "a v1")], "first");
+        switch_branch("topic", true, false).unwrap();
+        assert_eq!(branches::get_current_branch_name().as_deref(), Some("topic"));
+        let topic = commit_files(&[("a.txt", "a topic"), ("b.txt", "b topic")], "on topic");
+
+        assert_eq!(switch_branch("master", false, false).unwrap(), first);
+        assert_eq!(branches::get_current_branch_name().as_deref(), Some("master"));
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v1");
+        assert!(!Path::new("b.txt").exists());
+        assert_eq!(reflog::read_reflog("HEAD").unwrap().pop().unwrap().message, "checkout: moving from topic to master");
+
+        // A dirty tree blocks switching to a different commit
+        fs::write("a.txt", "edited").unwrap();
+        let error = switch_branch("topic", false, false).unwrap_err();
+        assert!(error.to_string().contains("\ta.txt"), "{}", error);
+        assert_eq!(branches::get_current_branch_name().as_deref(), Some("master"));
+
+        // ...but not creating a branch at the current commit, which keeps the edit
+        switch_branch("fix", true, false).unwrap();
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "edited");
+
+        assert_eq!(switch_branch("topic", false, true).unwrap(), topic);
+        assert_eq!(fs::read_to_string("b.txt").unwrap(), "b topic");
+        assert!(switch_branch("missing", false, false).is_err());
+        assert!(switch_branch("topic", true, false).is_err());
+    }
+}
@@ -1,4 +1,6 @@
-use core::time;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
 use std::io;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
@@ -6,52 +8,86 @@ use crate::staging;
 use crate::branches;
 use crate::tree;
 use crate::database;
-use crate::constants::COMMIT;
+use crate::diff::{compare_entries, PathChange};
+use crate::graph;
+use crate::reflog;
+use crate::date;
+use crate::index;
+use crate::merge;
+use crate::hooks::{run_hook, HookOutcome};
+use crate::config::Config;
+use crate::constants::{commit_editmsg_path, COMMIT, HEAD_FILE};
 
 #[derive(Debug)]
-struct Commit {
-    tree: String,
-    parent: Option<String>,
-    committer: String,
-    message: String,
-    timestamp: u64,
+pub struct Commit {
+    pub tree: String,
+    // The first parent, which is the previous tip of the branch the commit was made on
+    pub parent: Option<String>,
+    // Further parents of a merge commit, in the order they were merged
+    pub merge_parents: Vec<String>,
+    // Who wrote the change; the committer for commits written before it was recorded
+    pub author: String,
+    pub committer: String,
+    pub message: String,
+    pub timestamp: u64,
+    // The committer's UTC offset in minutes; 0 for commits written before it was recorded
+    pub timezone: i32,
 }
 
 impl Commit {
-    fn new(tree: String, parent: Option<String>, committer: String, message: String) -> Self {
+    fn new(tree: String, parent: Option<String>, committer: String, message: String, timezone: i32) -> Self {
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
         Commit {
             tree,
             parent,
+            merge_parents: Vec::new(),
+            author: committer.clone(),
             committer,
             message,
             timestamp,
+            timezone,
         }
     }
 
+    // Every parent, first parent first
+    pub fn parents(&self) -> Vec<String> {
+        self.parent.iter().chain(&self.merge_parents).cloned().collect()
+    }
+
+    // Whether the commit joins two or more lines of history
+    pub fn is_merge(&self) -> bool {
+        self.parents().len() >= 2
+    }
+
     fn serialize(&self) -> Vec<u8> {
-        let parent_str = if let Some(ref parent) = self.parent {
-            format!("parent {}\n", parent)
-        } else {
-            String::new()
-        };
+        let parent_str: String = self.parents().iter().map(|parent| format!("parent {}\n", parent)).collect();
+        let stamp = format!("{} {}", self.timestamp, date::format_offset(self.timezone));
 
         format!(
-            "tree {}\n{}committer {} {}\n\n{}",
+            "tree {}\n{}author {} {}\ncommitter {} {}\n\n{}",
             self.tree,
             parent_str,
+            self.author,
+            stamp,
             self.committer,
-            self.timestamp,
+            stamp,
             self.message
         ).into_bytes()
     }
 
-    fn deserialize(data: &[u8]) -> io::Result<Self> {
+    pub fn deserialize(data: &[u8]) -> io::Result<Self> {
+        let malformed = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Malformed commit: {}", reason));
+        if data.is_empty() {
+            return Err(malformed("the object is empty"));
+        }
         let data_str = String::from_utf8_lossy(data);
         let mut tree = String::new();
         let mut parent = None;
+        let mut merge_parents = Vec::new();
+        let mut author = None;
         let mut committer = String::new();
         let mut timestamp = 0;
+        let mut timezone = 0;
         let mut message = String::new();
         let mut in_message = false;
 
@@ -67,65 +103,268 @@ impl Commit {
                 continue;
             }
 
-            let mut parts = line.splitn(2, ' ');
-            let key = parts.next().unwrap();
-            let value = parts.next().unwrap_or("");
+            let (key, value) = line.split_once(' ').ok_or_else(|| malformed(&format!("header line '{}' has no value", line)))?;
 
             match key {
                 "tree" => tree = value.to_string(),
-                "parent" => parent = Some(value.to_string()),
-                "committer" => {
-                    let parts: Vec<&str> = value.split_whitespace().collect();
-                    if parts.len() > 2 {
-                        committer = parts[..parts.len() - 1].join(" ");
-                        timestamp = parts[parts.len() - 1].parse().unwrap_or_default();
-                    } else {
-                        committer = value.to_string(); // Fallback to the raw value
-                        timestamp = 0; // Default timestamp
-                    }
-                },
+                "parent" if parent.is_none() => parent = Some(value.to_string()),
+                "parent" => merge_parents.push(value.to_string()),
+                "author" => author = Some(parse_identity(value)?.0),
+                "committer" => (committer, timestamp, timezone) = parse_identity(value)?,
                 _ => {}
             }
         }
 
+        if tree.is_empty() {
+            return Err(malformed("missing tree line"));
+        }
+        if committer.is_empty() {
+            return Err(malformed("missing committer line"));
+        }
+
         Ok(Commit {
             tree,
             parent,
+            merge_parents,
+            author: author.unwrap_or_else(|| committer.clone()),
             committer,
             message: message.trim_end().to_string(),
             timestamp,
+            timezone,
         })
     }
 }
 
+// Parses a `Name <email> <unix> [<tz>]` line into the identity, its timestamp and its
+// UTC offset in minutes (0 when absent)
+pub(crate) fn parse_identity(value: &str) -> io::Result<(String, u64, i32)> {
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, format!("Malformed committer line: {}", value));
+
+    // Locate the email span first so names containing digits or spaces are kept intact
+    let email_start = value.find('<').ok_or_else(malformed)?;
+    let email_end = value[email_start..].find('>').map(|i| email_start + i).ok_or_else(malformed)?;
+    if value[..email_start].trim().is_empty() {
+        return Err(malformed());
+    }
+
+    let mut rest = value[email_end + 1..].split_whitespace();
+    let timestamp = rest.next().and_then(|t| t.parse::<u64>().ok()).ok_or_else(malformed)?;
+    // An optional timezone offset such as +0100 may follow the timestamp
+    let timezone = match rest.next() {
+        Some(tz) => date::parse_offset(tz).ok_or_else(malformed)?,
+        None => 0,
+    };
+    if rest.next().is_some() {
+        return Err(malformed());
+    }
+
+    Ok((value[..=email_end].to_string(), timestamp, timezone))
+}
+
+// Checks a committer is `Name <email>`. What a commit needs to be read back (a name, an
+// email in angle brackets, nothing after it, no line breaks) is always required; a plausible
+// email address (non-empty parts around one `@`, no spaces) can be waived with `allow_invalid`.
+pub fn validate_identity(identity: &str, allow_invalid: bool) -> io::Result<()> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid identity '{}': {}", identity, reason));
+
+    if identity.contains(['\n', '\r', '\0']) {
+        return Err(invalid("it contains a line break"));
+    }
+    let (name, rest) = identity.split_once('<').ok_or_else(|| invalid("expected `Name <email>`"))?;
+    let (email, trailing) = rest.split_once('>').ok_or_else(|| invalid("expected `Name <email>`"))?;
+    if name.trim().is_empty() {
+        return Err(invalid("the name is empty"));
+    }
+    if !trailing.is_empty() {
+        return Err(invalid("nothing may follow the email"));
+    }
+    if allow_invalid {
+        return Ok(());
+    }
+
+    if name.contains('>') || email.contains('<') {
+        return Err(invalid("stray angle bracket"));
+    }
+    let plausible = match email.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && !domain.is_empty() && !domain.contains('@'),
+        None => false,
+    };
+    if !plausible || email.chars().any(char::is_whitespace) {
+        return Err(invalid("the email does not look like an address (use --allow-invalid-identity to commit anyway)"));
+    }
+    Ok(())
+}
+
+// The identity `user.name` and `user.email` in the config describe, if both are set
+pub fn configured_identity() -> io::Result<Option<String>> {
+    let config = Config::load()?;
+    match (config.get("user.name"), config.get("user.email")) {
+        (Some(name), Some(email)) => Ok(Some(format!("{} <{}>", name, email))),
+        _ => Ok(None),
+    }
+}
+
+// The committer to record: the one given, or else the configured user
+pub fn resolve_committer(committer: &str) -> io::Result<String> {
+    if !committer.trim().is_empty() {
+        return Ok(committer.to_string());
+    }
+    configured_identity()?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No committer given; pass --committer, or set one with `rit config user.name \"Your Name\"` and `rit config user.email you@example.com`",
+        )
+    })
+}
+
+// Settings for a commit beyond its message and committer
+#[derive(Debug, Default, Clone)]
+pub struct CommitOptions {
+    // The UTC offset to record, in minutes; the local offset when unset
+    pub timezone: Option<i32>,
+    // Accept a committer whose email does not look like an address
+    pub allow_invalid_identity: bool,
+    // Record the commit even if its tree is the same as its parent's
+    pub allow_empty: bool,
+    // Who wrote the change, when that is not the committer
+    pub author: Option<String>,
+}
+
 pub fn commit(message: &str, commiter:&str) -> io::Result<String> {
+    commit_with_options(message, commiter, &CommitOptions::default())
+}
+
+// Commits recording the given UTC offset, in minutes, instead of the local one
+pub fn commit_with_timezone(message: &str, commiter: &str, timezone: i32) -> io::Result<String> {
+    commit_with_options(message, commiter, &CommitOptions { timezone: Some(timezone), ..CommitOptions::default() })
+}
+
+// An empty committer means the user configured with `rit config`
+pub fn commit_with_options(message: &str, commiter: &str, options: &CommitOptions) -> io::Result<String> {
+    let commiter = &resolve_committer(commiter)?;
+    // A malformed committer would make the commit unreadable
+    validate_identity(commiter, options.allow_invalid_identity)?;
+    let author = options.author.as_deref().unwrap_or(commiter);
+    validate_identity(author, options.allow_invalid_identity)?;
+    let timezone = match options.timezone {
+        Some(timezone) => timezone,
+        None => date::local_offset()?,
+    };
+
+    // A merge in progress is concluded by this commit, which takes the merged commit as a
+    // second parent; one that left conflicts cannot be concluded yet
+    let merge_state = merge::read_state()?;
+    if let Some(state) = &merge_state {
+        if !state.conflicts().is_empty() {
+            return Err(io::Error::other("A merge with conflicts is in progress; concluding it with a commit is not supported yet, run `rit merge --abort` to abandon it"));
+        }
+    }
+    let merge_parents: Vec<String> = merge_state.iter().map(|state| state.merge_head.clone()).collect();
+
+    // Let the pre-commit hook veto the commit
+    if let HookOutcome::Abort(_) = run_hook("pre-commit", &[], None)? {
+        return Err(io::Error::other("pre-commit hook failed; commit aborted"));
+    }
+
+    // Let the commit-msg hook check or rewrite the message
+    let message_path = commit_editmsg_path();
+    fs::write(&message_path, message)?;
+    if let HookOutcome::Abort(_) = run_hook("commit-msg", &[&message_path.to_string_lossy()], None)? {
+        return Err(io::Error::other("commit-msg hook failed; commit aborted"));
+    }
+    let message = fs::read_to_string(&message_path)?;
+    let message = message.trim_end();
+
+    // Hold the index lock until the branch points at the new objects, so gc leaves them alone
+    let _lock = index::lock_index()?;
+
     // Get index
     let entries = staging::get_staged_entries()?;
     
     // Get the latest commit hash if there is one
     let latest_commit_hash: Option<String> = branches::get_current_branch_commit_hash()?;
 
-    // Detect if there are no changes to commit, return a message
-
     // Create a new tree
     let tree_hash = tree::create_tree(&entries)?;
 
+    // Refuse a commit that changes nothing: the same tree as the parent, or an empty first
+    // commit. A merge commit records the join even when the tree is unchanged.
+    let parent_tree = match &latest_commit_hash {
+        Some(parent) => Some(read_commit(parent)?.tree),
+        None => None,
+    };
+    let unchanged = match parent_tree {
+        Some(parent_tree) => parent_tree == tree_hash,
+        None => entries.is_empty(),
+    };
+    if unchanged && !options.allow_empty && merge_parents.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "nothing to commit, working tree clean"));
+    }
+
     // Create the commit object and store it in the database
-    let commit_hash = create_commit_object(&tree_hash, message, commiter, latest_commit_hash)?;
+    let commit_hash = create_commit_object(&tree_hash, message, author, commiter, latest_commit_hash.clone(), merge_parents.clone(), timezone)?;
 
     // Update the branch to point to the new commit
     branches::update_current_branch(&commit_hash)?;
 
+    // Record the move in the reflogs; a detached HEAD has only its own
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let summary = message.lines().next().unwrap_or("");
+    let action = if merge_parents.is_empty() { "commit" } else { "commit (merge)" };
+    let reflog_message = format!("{}: {}", action, summary);
+    if let Some(branch) = branches::get_current_branch_name() {
+        reflog::record_branch_update(&branch, latest_commit_hash.as_deref(), &commit_hash, commiter, timestamp, &reflog_message)?;
+    } else {
+        let entry = reflog::ReflogEntry {
+            old: latest_commit_hash.clone().unwrap_or_else(|| database::ZERO_HASH.to_string()),
+            new: commit_hash.clone(),
+            committer: commiter.to_string(),
+            timestamp,
+            message: reflog_message,
+        };
+        reflog::append(HEAD_FILE, &entry)?;
+    }
+
+    if merge_state.is_some() {
+        merge::clear_state()?;
+    }
+
     Ok(commit_hash)
 }
 
-fn create_commit_object(tree_hash: &str, message: &str, commiter: &str, parent_commit_hash: Option<String>) -> io::Result<String> {
-    let commit = Commit::new(
+// What a commit would record, computed without writing anything
+#[derive(Debug)]
+pub struct CommitPreview {
+    pub tree: String,
+    pub parent: Option<String>,
+    pub changes: Vec<PathChange>,
+}
+
+// Runs the commit pipeline without storing objects or moving the branch
+pub fn commit_dry_run() -> io::Result<CommitPreview> {
+    let entries = staging::get_staged_entries()?;
+    let parent = branches::get_current_branch_commit_hash()?;
+    let tree = tree::hash_tree(&entries)?;
+
+    let parent_entries = match &parent {
+        Some(parent) => tree::flatten_tree(&read_commit(parent)?.tree)?,
+        None => Vec::new(),
+    };
+    let changes = compare_entries(parent_entries, entries);
+
+    Ok(CommitPreview { tree, parent, changes })
+}
+
+fn create_commit_object(tree_hash: &str, message: &str, author: &str, commiter: &str, parent_commit_hash: Option<String>, merge_parents: Vec<String>, timezone: i32) -> io::Result<String> {
+    let mut commit = Commit::new(
         tree_hash.to_string(),
         parent_commit_hash.map(|s| s.to_string()),
         commiter.to_string(),
-        message.to_string()
+        message.to_string(),
+        timezone
         );
+    commit.author = author.to_string();
+    commit.merge_parents = merge_parents;
 
     let commit_data = commit.serialize();
 
@@ -133,25 +372,127 @@ fn create_commit_object(tree_hash: &str, message: &str, commiter: &str, parent_c
     Ok(commit_hash)
 }
 
-pub fn data_to_commit(data: Vec<u8>) -> Commit{
-    let commit = Commit::deserialize(&data).unwrap();
-    commit
+// Reads and deserializes the commit object stored under the given hash
+pub fn read_commit(commit_hash: &str) -> io::Result<Commit> {
+    let (object_type, _, data) = database::get_data(commit_hash)?;
+    if object_type != COMMIT {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Object {} is not a commit", commit_hash)));
+    }
+    Commit::deserialize(&data)
+}
+
+pub fn data_to_commit(data: Vec<u8>) -> io::Result<Commit> {
+    Commit::deserialize(&data)
 }
 
-pub fn commit_tree_hash_from_data(data: Vec<u8>) -> String{
-    let commit = Commit::deserialize(&data).unwrap();
-    commit.tree
+// The tree a stored commit points at; malformed commit data is an InvalidData error
+pub fn commit_tree_hash_from_data(data: Vec<u8>) -> io::Result<String> {
+    Ok(Commit::deserialize(&data)?.tree)
+}
+
+// Walks the history of the current branch from newest to oldest, following every parent.
+// Commits with equal timestamps come in the order they were reached, so first parents lead.
+pub fn log() -> io::Result<Vec<(String, Commit)>> {
+    let mut history = Vec::new();
+    let mut reached: HashMap<String, Commit> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let mut order = 0usize;
+    if let Some(head) = branches::get_current_branch_commit_hash()? {
+        let commit = read_commit(&head)?;
+        queue.push((commit.timestamp, Reverse(order), head.clone()));
+        reached.insert(head, commit);
+    }
+
+    let mut shown = HashSet::new();
+    while let Some((_, _, hash)) = queue.pop() {
+        if !shown.insert(hash.clone()) {
+            continue;
+        }
+        let commit = reached.remove(&hash).expect("queued commits have been read");
+        for parent in commit.parents() {
+            if !shown.contains(&parent) && !reached.contains_key(&parent) {
+                order += 1;
+                let parent_commit = read_commit(&parent)?;
+                queue.push((parent_commit.timestamp, Reverse(order), parent.clone()));
+                reached.insert(parent, parent_commit);
+            }
+        }
+        history.push((hash, commit));
+    }
+
+    Ok(history)
+}
+
+// Walks the history of the current branch, optionally oldest first or in topological
+// order (parents before children)
+pub fn log_with(reverse: bool, topo_order: bool) -> io::Result<Vec<(String, Commit)>> {
+    let mut history = if topo_order {
+        match branches::get_current_branch_commit_hash()? {
+            Some(head) => graph::topo_order(&head)?
+                .into_iter()
+                .map(|hash| read_commit(&hash).map(|commit| (hash, commit)))
+                .collect::<io::Result<Vec<_>>>()?,
+            None => Vec::new(),
+        }
+    } else {
+        log()?
+    };
+
+    // Plain log is newest first and topological order is oldest first; --reverse flips either
+    if reverse {
+        history.reverse();
+    }
+
+    Ok(history)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::constants::hooks_dir_path;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::TestDir;
     use std::time::{SystemTime, UNIX_EPOCH};
 
+    #[cfg(unix)]
+    fn install_hook(name: &str, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        let hook_path = hooks_dir_path().join(name);
+        fs::write(&hook_path, script).unwrap();
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failing_pre_commit_hook_aborts_commit() {
+        let _test_dir = TestDir::with_repo();
+        install_hook("pre-commit", "#!/bin/sh\nexit 1\n");
+
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+
+        assert!(commit("message", "Tester <tester@example.com>").is_err());
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commit_msg_hook_rewrites_message() {
+        let _test_dir = TestDir::with_repo();
+        install_hook("commit-msg", "#!/bin/sh\necho \"rewritten\" > \"$1\"\n");
+
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+
+        let commit_hash = commit("original", "Tester <tester@example.com>").unwrap();
+        assert_eq!(read_commit(&commit_hash).unwrap().message, "rewritten");
+    }
+
     #[test]
     fn test_commit_serialization() {
         let tree = "tree_hash".to_string();
-        let parent = Some("parent_hash".to_string());
+        let parent_hash = "parent_hash".to_string();
+        let parent = Some(parent_hash.clone());
         let committer = "Committer Name <committer@example.com>".to_string();
         let message = "Initial commit".to_string();
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
@@ -159,16 +500,21 @@ mod tests {
         let commit = Commit {
             tree: tree.clone(),
             parent: parent.clone(),
+            merge_parents: Vec::new(),
+            author: committer.clone(),
             committer: committer.clone(),
             message: message.clone(),
             timestamp,
+            timezone: 0,
         };
 
         let serialized = commit.serialize();
         let expected_serialized = format!(
-            "tree {}\nparent {}\ncommitter {} {}\n\n{}",
+            "tree {}\nparent {}\nauthor {} {} +0000\ncommitter {} {} +0000\n\n{}",
             tree,
-            parent.unwrap(),
+            parent_hash,
+            committer,
+            timestamp,
             committer,
             timestamp,
             message
@@ -199,6 +545,8 @@ mod tests {
         assert_eq!(deserialized.tree, tree);
         assert_eq!(deserialized.parent, Some(parent));
         assert_eq!(deserialized.committer, committer);
+        // Objects written before authors were recorded credit the committer
+        assert_eq!(deserialized.author, committer);
         assert_eq!(deserialized.message, message);
         assert_eq!(deserialized.timestamp, timestamp);
     }
@@ -210,14 +558,299 @@ mod tests {
         let committer = "Committer Name <committer@example.com>".to_string();
         let message = "Initial commit".to_string();
 
-        let commit = Commit::new(tree.clone(), parent.clone(), committer.clone(), message.clone());
+        let commit = Commit::new(tree.clone(), parent.clone(), committer.clone(), message.clone(), -330);
         let serialized = commit.serialize();
         let deserialized = Commit::deserialize(&serialized).unwrap();
 
         assert_eq!(deserialized.tree, tree);
         assert_eq!(deserialized.parent, parent);
         assert_eq!(deserialized.timestamp, commit.timestamp);
+        assert_eq!(deserialized.timezone, -330);
         assert_eq!(deserialized.committer, committer);
         assert_eq!(deserialized.message, message);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_identity_normal() {
+        let (identity, timestamp, timezone) = parse_identity("Jane Doe <jane@example.com> 1700000000 +0100").unwrap();
+        assert_eq!(identity, "Jane Doe <jane@example.com>");
+        assert_eq!(timestamp, 1700000000);
+        assert_eq!(timezone, 60);
+    }
+
+    #[test]
+    fn test_parse_identity_numeric_name() {
+        let (identity, timestamp, timezone) = parse_identity("Agent 007 2 <bond@example.com> 42").unwrap();
+        assert_eq!(identity, "Agent 007 2 <bond@example.com>");
+        assert_eq!(timestamp, 42);
+        assert_eq!(timezone, 0);
+    }
+
+    #[test]
+    fn test_deserialize_malformed_committer_is_error() {
+        let missing_email = b"tree t\ncommitter Jane 1700000000\n\nmessage";
+        assert!(Commit::deserialize(missing_email).is_err());
+
+        let missing_timestamp = b"tree t\ncommitter Jane <jane@example.com>\n\nmessage";
+        assert!(Commit::deserialize(missing_timestamp).is_err());
+
+        let bad_timestamp = b"tree t\ncommitter Jane <jane@example.com> soon\n\nmessage";
+        assert!(Commit::deserialize(bad_timestamp).is_err());
+
+        let bad_timezone = b"tree t\ncommitter Jane <jane@example.com> 42 +2";
+        assert!(Commit::deserialize(bad_timezone).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_garbage_is_error() {
+        let garbage: [&[u8]; 6] = [
+            b"",
+            b"\n\nmessage only",
+            b"\xff\xfe\x00\x01garbage",
+            b"parent p\ncommitter Jane <jane@example.com> 42\n\nno tree",
+            b"tree t\n\nno committer",
+            b"tree t\ncommitter\n\nempty committer",
+        ];
+        for data in garbage {
+            assert!(Commit::deserialize(data).is_err(), "{:?}", String::from_utf8_lossy(data));
+        }
+        // A commit with no message is still well formed
+        assert!(Commit::deserialize(b"tree t\ncommitter Jane <jane@example.com> 42").is_ok());
+
+        // Every truncation either parses or fails, without panicking
+        let truncated = b"tree t\ncommitter Jane <jane@example.com> 42\n\nmessage";
+        for len in 0..truncated.len() {
+            let _ = Commit::deserialize(&truncated[..len]);
+        }
+    }
+
+    #[test]
+    fn test_malformed_head_commit_is_error() {
+        let _test_dir = TestDir::with_repo();
+        let bad = database::store_data(b"no tree here", COMMIT).unwrap();
+        branches::update_current_branch(&bad).unwrap();
+
+        let error = branches::get_current_tree_from_commit_hash().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(index::status_report(&[]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_validate_identity() {
+        for valid in ["Jane Doe <jane@example.com>", "Agent 007 <bond@mi6.gov.uk>", "J <j@localhost>"] {
+            assert!(validate_identity(valid, false).is_ok(), "{}", valid);
+        }
+        for invalid in [
+            "Jane Doe",
+            "<jane@example.com>",
+            "   <jane@example.com>",
+            "Jane <jane@example.com",
+            "Jane <jane@example.com> 1700000000",
+            "Jane\nDoe <jane@example.com>",
+            "Jane <jane>",
+            "Jane <@example.com>",
+            "Jane <jane@>",
+            "Jane <jane doe@example.com>",
+            "Jane <a@b@c>",
+            "Jane> <jane@example.com>",
+        ] {
+            assert!(validate_identity(invalid, false).is_err(), "{}", invalid);
+        }
+
+        // The escape hatch waives the email check but not what parsing needs
+        assert!(validate_identity("Jane <jane>", true).is_ok());
+        assert!(validate_identity("Jane <>", true).is_ok());
+        assert!(validate_identity("Jane Doe", true).is_err());
+        assert!(validate_identity("Jane <jane> 42", true).is_err());
+    }
+
+    #[test]
+    fn test_commit_rejects_invalid_identity() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+
+        let error = commit("message", "Tester").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(commit("message", "Tester <tester>").is_err());
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), None);
+
+        let options = CommitOptions { allow_invalid_identity: true, ..CommitOptions::default() };
+        let commit_hash = commit_with_options("message", "Tester <tester>", &options).unwrap();
+        assert_eq!(read_commit(&commit_hash).unwrap().committer, "Tester <tester>");
+    }
+
+    #[test]
+    fn test_commit_falls_back_to_configured_user() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+
+        crate::config::set_value("user.name", "Configured").unwrap();
+        let error = commit("message", "").unwrap_err();
+        assert!(error.to_string().contains("rit config user.name"), "{}", error);
+
+        crate::config::set_value("user.email", "configured@example.com").unwrap();
+        let commit_hash = commit("message", "").unwrap();
+        assert_eq!(read_commit(&commit_hash).unwrap().committer, "Configured <configured@example.com>");
+
+        // A committer that is given still wins
+        fs::write("file.txt", "changed").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let commit_hash = commit("message", "Tester <tester@example.com>").unwrap();
+        assert_eq!(read_commit(&commit_hash).unwrap().committer, "Tester <tester@example.com>");
+    }
+
+    #[test]
+    fn test_commit_refuses_empty_commits() {
+        let _test_dir = TestDir::with_repo();
+        let allow_empty = CommitOptions { allow_empty: true, ..CommitOptions::default() };
+
+        // An empty index makes an empty first commit
+        let error = commit("message", "Tester <tester@example.com>").unwrap_err();
+        assert_eq!(error.to_string(), "nothing to commit, working tree clean");
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), None);
+        let root = commit_with_options("root", "Tester <tester@example.com>", &allow_empty).unwrap();
+
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+        assert_eq!(read_commit(&first).unwrap().parent, Some(root));
+
+        // Re-staging the same content leaves the tree unchanged
+        add_file_to_staging("file.txt").unwrap();
+        assert!(commit("again", "Tester <tester@example.com>").is_err());
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), Some(first.clone()));
+        let again = commit_with_options("again", "Tester <tester@example.com>", &allow_empty).unwrap();
+        assert_eq!(read_commit(&again).unwrap().tree, read_commit(&first).unwrap().tree);
+    }
+
+    #[test]
+    fn test_commit_records_forced_timezone() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+
+        let commit_hash = commit_with_timezone("message", "Tester <tester@example.com>", -570).unwrap();
+        let (_, _, data) = database::get_data(&commit_hash).unwrap();
+        let text = String::from_utf8(data.clone()).unwrap();
+        let committer_line = text.lines().find(|line| line.starts_with("committer ")).unwrap();
+        assert!(committer_line.ends_with(" -0930"), "{}", committer_line);
+
+        let commit = Commit::deserialize(&data).unwrap();
+        assert_eq!(commit.timezone, -570);
+        assert_eq!(commit.serialize(), data);
+    }
+
+    #[test]
+    fn test_commit_records_author() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+
+        let options = CommitOptions { author: Some("Author <author@example.com>".to_string()), ..CommitOptions::default() };
+        let commit_hash = commit_with_options("message", "Tester <tester@example.com>", &options).unwrap();
+        let (_, _, data) = database::get_data(&commit_hash).unwrap();
+        let commit = Commit::deserialize(&data).unwrap();
+        assert_eq!(commit.author, "Author <author@example.com>");
+        assert_eq!(commit.committer, "Tester <tester@example.com>");
+        assert_eq!(commit.serialize(), data);
+
+        fs::write("file.txt", "changed").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let bad_author = CommitOptions { author: Some("Nobody".to_string()), ..CommitOptions::default() };
+        assert!(commit_with_options("message", "Tester <tester@example.com>", &bad_author).is_err());
+    }
+
+    #[test]
+    fn test_log_reverse_and_topo_order() {
+        let _test_dir = TestDir::with_repo();
+        let mut hashes = Vec::new();
+        for content in ["one", "two", "three"] {
+            fs::write("file.txt", content).unwrap();
+            add_file_to_staging("file.txt").unwrap();
+            hashes.push(commit(content, "Tester <tester@example.com>").unwrap());
+        }
+
+        let newest_first: Vec<String> = log().unwrap().into_iter().map(|(hash, _)| hash).collect();
+        let reversed: Vec<String> = log_with(true, false).unwrap().into_iter().map(|(hash, _)| hash).collect();
+        assert_eq!(reversed, hashes);
+        assert_eq!(newest_first, hashes.iter().rev().cloned().collect::<Vec<_>>());
+
+        // Every parent must be listed before its child
+        let topo = log_with(false, true).unwrap();
+        for (position, (_, commit)) in topo.iter().enumerate() {
+            if let Some(parent) = &commit.parent {
+                let parent_position = topo.iter().position(|(hash, _)| hash == parent).unwrap();
+                assert!(parent_position < position);
+            }
+        }
+    }
+
+    #[test]
+    fn test_log_merge_filters() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "base").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let base = commit("base", "Tester <tester@example.com>").unwrap();
+        fs::write("file.txt", "ours").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let ours = commit("ours", "Tester <tester@example.com>").unwrap();
+
+        // A side commit off the base, joined back in by a merge commit with both parents
+        let base_commit = read_commit(&base).unwrap();
+        let side = Commit::new(base_commit.tree.clone(), Some(base.clone()), "Tester <tester@example.com>".to_string(), "side".to_string(), 0);
+        let side = database::store_data(&side.serialize(), COMMIT).unwrap();
+        let mut merge = Commit::new(read_commit(&ours).unwrap().tree, Some(ours.clone()), "Tester <tester@example.com>".to_string(), "merge side".to_string(), 0);
+        merge.merge_parents.push(side.clone());
+        let merge_hash = database::store_data(&merge.serialize(), COMMIT).unwrap();
+        branches::update_current_branch(&merge_hash).unwrap();
+
+        let stored = read_commit(&merge_hash).unwrap();
+        assert_eq!(stored.parents(), vec![ours.clone(), side.clone()]);
+        assert!(stored.is_merge());
+
+        // The log reaches both lines of history, each commit once
+        let history = log().unwrap();
+        let mut hashes: Vec<&str> = history.iter().map(|(hash, _)| hash.as_str()).collect();
+        assert_eq!(hashes[0], merge_hash);
+        assert_eq!(hashes.last(), Some(&base.as_str()));
+        hashes.sort();
+        let mut expected = vec![base.as_str(), ours.as_str(), side.as_str(), merge_hash.as_str()];
+        expected.sort();
+        assert_eq!(hashes, expected);
+
+        let merges: Vec<&str> = history.iter().filter(|(_, commit)| commit.is_merge()).map(|(hash, _)| hash.as_str()).collect();
+        assert_eq!(merges, vec![merge_hash.as_str()]);
+        let no_merges: Vec<&str> = history.iter().filter(|(_, commit)| !commit.is_merge()).map(|(hash, _)| hash.as_str()).collect();
+        assert_eq!(no_merges.len(), 3);
+        assert!(!no_merges.contains(&merge_hash.as_str()));
+    }
+
+    #[test]
+    fn test_commit_dry_run_writes_nothing() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "one").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+
+        fs::write("file.txt", "two").unwrap();
+        fs::write("new.txt", "new").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        add_file_to_staging("new.txt").unwrap();
+        let objects_before = database::list_objects().unwrap();
+
+        let preview = commit_dry_run().unwrap();
+        assert_eq!(preview.parent, Some(first.clone()));
+        let changed: Vec<(&str, &str)> = preview.changes.iter().map(|c| (c.path.as_str(), c.status.label())).collect();
+        assert_eq!(changed, vec![("file.txt", "modified"), ("new.txt", "new file")]);
+
+        // No commit or tree object was written and the branch did not move
+        assert_eq!(database::list_objects().unwrap(), objects_before);
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), Some(first));
+
+        // The previewed tree is exactly what a real commit records
+        let second = commit("second", "Tester <tester@example.com>").unwrap();
+        assert_eq!(read_commit(&second).unwrap().tree, preview.tree);
+    }
+}
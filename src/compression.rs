@@ -1,14 +1,366 @@
+// Object compression. Each repository picks a codec at init, recorded as
+// `compression.codec` in its config; objects say which codec wrote them, so changing it
+// later never breaks reading older objects:
+//
+//   zstd  a bare zstd frame, recognised by its magic number (every object before codecs existed)
+//   none  a 0x00 byte, then the data
+//   lz4   a 0x04 byte, the data's length as a little-endian u64, then one LZ4 block
+
+use crate::config::Config;
 use std::io::{self, Cursor};
 use zstd::stream::{encode_all as zstd_compress, decode_all as zstd_decompress};
 
-// Compress data using zstd with compression level 3
+pub const CODEC_KEY: &str = "compression.codec";
+// The zstd level new objects are written at; zstd records what it needs in each frame, so
+// objects written at any level read back the same way
+pub const LEVEL_KEY: &str = "compression.level";
+pub const DEFAULT_LEVEL: i32 = 3;
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+const NONE_TAG: u8 = 0x00;
+const LZ4_TAG: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompressionCodec {
+    #[default]
+    Zstd,
+    Lz4,
+    None,
+}
+
+impl CompressionCodec {
+    pub fn name(self) -> &'static str {
+        match self {
+            CompressionCodec::Zstd => "zstd",
+            CompressionCodec::Lz4 => "lz4",
+            CompressionCodec::None => "none",
+        }
+    }
+
+    pub fn from_name(name: &str) -> io::Result<Self> {
+        match name.to_lowercase().as_str() {
+            "zstd" => Ok(CompressionCodec::Zstd),
+            "lz4" => Ok(CompressionCodec::Lz4),
+            "none" => Ok(CompressionCodec::None),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown compression codec '{}': expected zstd, lz4 or none", name))),
+        }
+    }
+
+    // The codec new objects in the current repository are written with
+    pub fn current() -> io::Result<Self> {
+        match Config::load()?.get(CODEC_KEY) {
+            Some(name) => CompressionCodec::from_name(name),
+            None => Ok(CompressionCodec::Zstd),
+        }
+    }
+}
+
+// Checks a zstd compression level is in the 1-22 range zstd accepts
+pub fn validate_level(level: i64) -> io::Result<i32> {
+    if (1..=22).contains(&level) {
+        Ok(level as i32)
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid compression level {}: expected 1 to 22", level)))
+    }
+}
+
+// The zstd level configured for the current repository
+pub fn current_level() -> io::Result<i32> {
+    match Config::load()?.get_int(LEVEL_KEY)? {
+        Some(level) => validate_level(i64::try_from(level).unwrap_or(i64::MAX)),
+        None => Ok(DEFAULT_LEVEL),
+    }
+}
+
+// Compress data with the repository's codec and level
 pub fn compress_data(data: &[u8]) -> io::Result<Vec<u8>> {
-    let compressed_data = zstd_compress(Cursor::new(data), 3)?;
-    Ok(compressed_data)
+    compress_with(data, CompressionCodec::current()?, current_level()?)
 }
 
-// Decompress data using zstd
+// Compress data with the given codec; the level only matters to zstd
+pub fn compress_with(data: &[u8], codec: CompressionCodec, level: i32) -> io::Result<Vec<u8>> {
+    match codec {
+        CompressionCodec::Zstd => zstd_compress(Cursor::new(data), level),
+        CompressionCodec::Lz4 => {
+            let mut out = vec![LZ4_TAG];
+            out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            out.extend(lz4_compress(data));
+            Ok(out)
+        }
+        CompressionCodec::None => {
+            let mut out = vec![NONE_TAG];
+            out.extend_from_slice(data);
+            Ok(out)
+        }
+    }
+}
+
+// Decompress data written by any codec, whichever the repository uses now
 pub fn uncompress_data(data: &[u8]) -> io::Result<Vec<u8>> {
-    let decompressed_data = zstd_decompress(Cursor::new(data))?;
-    Ok(decompressed_data)
-}
\ No newline at end of file
+    if data.starts_with(&ZSTD_MAGIC) {
+        return zstd_decompress(Cursor::new(data));
+    }
+    match data.first() {
+        Some(&NONE_TAG) => Ok(data[1..].to_vec()),
+        Some(&LZ4_TAG) => {
+            let len = data.get(1..9).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Truncated lz4 object"))?;
+            let len = usize::try_from(u64::from_le_bytes(len.try_into().expect("slice of 8 bytes")))
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid lz4 object: length is too large"))?;
+            lz4_decompress(&data[9..], len)
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Unknown compression format")),
+    }
+}
+
+// LZ4 block format: sequences of a token (literal count in the high nibble, match length
+// minus 4 in the low one, 15 meaning more length bytes follow), the literals, and a
+// 2-byte little-endian offset back to the match. The last sequence is literals only.
+const LZ4_MIN_MATCH: usize = 4;
+// The last match must start this far from the end, and the last 5 bytes are always literals
+const LZ4_MF_LIMIT: usize = 12;
+const LZ4_LAST_LITERALS: usize = 5;
+const LZ4_HASH_BITS: u32 = 12;
+
+fn lz4_write_length(out: &mut Vec<u8>, mut extra: usize) {
+    while extra >= 255 {
+        out.push(255);
+        extra -= 255;
+    }
+    out.push(extra as u8);
+}
+
+fn lz4_write_sequence(out: &mut Vec<u8>, literals: &[u8], found: Option<(usize, usize)>) {
+    let match_code = found.map_or(0, |(_, len)| len - LZ4_MIN_MATCH);
+    out.push(((literals.len().min(15) as u8) << 4) | match_code.min(15) as u8);
+    if literals.len() >= 15 {
+        lz4_write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = found {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_code >= 15 {
+            lz4_write_length(out, match_code - 15);
+        }
+    }
+}
+
+// Greedy compression with a hash table of the last position each 4-byte sequence was seen
+fn lz4_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + 16);
+    let mut table = vec![usize::MAX; 1 << LZ4_HASH_BITS];
+    let mut anchor = 0;
+    let mut i = 0;
+    while i + LZ4_MF_LIMIT <= data.len() {
+        let sequence = u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let slot = (sequence.wrapping_mul(2654435761) >> (32 - LZ4_HASH_BITS)) as usize;
+        let candidate = table[slot];
+        table[slot] = i;
+
+        if candidate == usize::MAX || i - candidate > u16::MAX as usize || data[candidate..candidate + 4] != data[i..i + 4] {
+            i += 1;
+            continue;
+        }
+        let mut len = LZ4_MIN_MATCH;
+        while i + len < data.len() - LZ4_LAST_LITERALS && data[candidate + len] == data[i + len] {
+            len += 1;
+        }
+        lz4_write_sequence(&mut out, &data[anchor..i], Some((i - candidate, len)));
+        i += len;
+        anchor = i;
+    }
+    lz4_write_sequence(&mut out, &data[anchor..], None);
+    out
+}
+
+fn lz4_decompress(block: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid lz4 block: {}", reason));
+    // The recorded length comes from the object, so only trust it as far as the block
+    // could possibly expand: each byte yields at most 255 more through a length byte
+    let mut out = Vec::with_capacity(len.min(block.len().saturating_mul(255)));
+    let mut position = 0;
+    let read_length = |position: &mut usize, mut length: usize| -> io::Result<usize> {
+        loop {
+            let byte = *block.get(*position).ok_or_else(|| invalid("truncated length"))?;
+            *position += 1;
+            length += byte as usize;
+            if byte != 255 {
+                return Ok(length);
+            }
+        }
+    };
+
+    loop {
+        let token = *block.get(position).ok_or_else(|| invalid("truncated"))?;
+        position += 1;
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 15 {
+            literal_len = read_length(&mut position, literal_len)?;
+        }
+        if out.len() + literal_len > len {
+            return Err(invalid("longer than recorded"));
+        }
+        out.extend_from_slice(block.get(position..position + literal_len).ok_or_else(|| invalid("truncated literals"))?);
+        position += literal_len;
+        if position == block.len() {
+            break;
+        }
+
+        let offset = block.get(position..position + 2).ok_or_else(|| invalid("truncated offset"))?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        position += 2;
+        if offset == 0 || offset > out.len() {
+            return Err(invalid("offset out of range"));
+        }
+        let mut match_len = (token & 0x0F) as usize;
+        if match_len == 15 {
+            match_len = read_length(&mut position, match_len)?;
+        }
+        if out.len() + match_len + LZ4_MIN_MATCH > len {
+            return Err(invalid("longer than recorded"));
+        }
+        // Copy byte by byte, since a match may overlap the bytes it produces
+        let start = out.len() - offset;
+        for k in 0..match_len + LZ4_MIN_MATCH {
+            out.push(out[start + k]);
+        }
+    }
+
+    if out.len() != len {
+        return Err(invalid("length does not match"));
+    }
+    Ok(out)
+}
+
+// Wraps data in a zlib stream of stored (uncompressed) deflate blocks. Every zlib reader
+// accepts these, which is all exporting loose objects to git needs.
+pub fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // CMF/FLG: deflate with a 32K window, no dictionary, checksum bits making it divisible by 31
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(if blocks.peek().is_none() { 0x01 } else { 0x00 });
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// Reads back a zlib stream made of stored blocks, as written by zlib_stored; streams
+// using compressed blocks are rejected
+pub fn zlib_unstore(stream: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid zlib stream: {}", reason));
+    if stream.len() < 6 || stream[0] & 0x0F != 8 || !u16::from_be_bytes([stream[0], stream[1]]).is_multiple_of(31) {
+        return Err(invalid("bad header"));
+    }
+
+    let mut data = Vec::new();
+    let mut position = 2;
+    loop {
+        let header = *stream.get(position).ok_or_else(|| invalid("truncated"))?;
+        if header & 0x06 != 0 {
+            return Err(invalid("only stored blocks are supported"));
+        }
+        let lengths = stream.get(position + 1..position + 5).ok_or_else(|| invalid("truncated"))?;
+        let len = u16::from_le_bytes([lengths[0], lengths[1]]);
+        if !len != u16::from_le_bytes([lengths[2], lengths[3]]) {
+            return Err(invalid("block length check failed"));
+        }
+        position += 5;
+        data.extend_from_slice(stream.get(position..position + len as usize).ok_or_else(|| invalid("truncated"))?);
+        position += len as usize;
+        if header & 0x01 != 0 {
+            break;
+        }
+    }
+
+    let checksum = stream.get(position..position + 4).ok_or_else(|| invalid("missing checksum"))?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&data) {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zlib_stored_round_trip() {
+        // The empty stream matches what zlib itself writes at level 0
+        assert_eq!(zlib_stored(b""), vec![0x78, 0x01, 0x01, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        for size in [0, 5, 65535, 65536, 200_000] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            assert_eq!(zlib_unstore(&zlib_stored(&data)).unwrap(), data);
+        }
+        assert!(zlib_unstore(&[0x78, 0x01, 0x01, 0x01, 0x00, 0xFF, 0xFF]).is_err());
+    }
+
+    #[test]
+    fn test_codecs_round_trip() {
+        let mut inputs: Vec<Vec<u8>> = vec![Vec::new(), b"a".to_vec(), b"hello hello hello hello hello hello\n".to_vec(), vec![7; 100_000]];
+        inputs.push((0..70_000).map(|i| (i % 251) as u8).collect());
+        // Pseudo-random bytes that hardly compress
+        let mut state = 12345u32;
+        inputs.push((0..5000).map(|_| { state = state.wrapping_mul(1103515245).wrapping_add(12345); (state >> 16) as u8 }).collect());
+
+        for codec in [CompressionCodec::Zstd, CompressionCodec::Lz4, CompressionCodec::None] {
+            for input in &inputs {
+                let compressed = compress_with(input, codec, DEFAULT_LEVEL).unwrap();
+                assert_eq!(&uncompress_data(&compressed).unwrap(), input, "{:?} with {} bytes", codec, input.len());
+            }
+        }
+
+        // Repetitive data actually shrinks under lz4
+        assert!(compress_with(&vec![7; 100_000], CompressionCodec::Lz4, DEFAULT_LEVEL).unwrap().len() < 1000);
+        assert!(uncompress_data(&[0x05, 1, 2]).is_err());
+        assert!(uncompress_data(&[LZ4_TAG, 3, 0, 0, 0, 0, 0, 0, 0, 0x30, b'a', b'b']).is_err());
+    }
+
+    #[test]
+    fn test_lz4_rejects_bad_recorded_length() {
+        let mut compressed = compress_with(b"hello hello hello hello", CompressionCodec::Lz4, DEFAULT_LEVEL).unwrap();
+
+        // A huge recorded length is corruption, not an allocation to attempt
+        let mut huge = compressed.clone();
+        huge[1..9].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+        assert_eq!(uncompress_data(&huge).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // So is any length the block does not decode to exactly
+        for len in [0u64, 5, 22, 24] {
+            compressed[1..9].copy_from_slice(&len.to_le_bytes());
+            assert_eq!(uncompress_data(&compressed).unwrap_err().kind(), io::ErrorKind::InvalidData, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_zstd_levels_round_trip() {
+        let data: Vec<u8> = (0..50_000).map(|i| ((i * 7) % 97) as u8).collect();
+        let mut sizes = Vec::new();
+        for level in [1, 3, 10, 19, 22] {
+            let compressed = compress_with(&data, CompressionCodec::Zstd, level).unwrap();
+            assert_eq!(uncompress_data(&compressed).unwrap(), data, "level {}", level);
+            sizes.push(compressed.len());
+        }
+        // Higher levels never do worse than the fastest one on this input
+        assert!(sizes.iter().all(|&size| size <= sizes[0]), "{:?}", sizes);
+
+        assert_eq!(validate_level(19).unwrap(), 19);
+        assert!(validate_level(0).is_err());
+        assert!(validate_level(23).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+// Repository configuration stored in `.rit/config`, in git's INI-like format:
+//
+//   [gc]
+//   	auto = 6700
+//   [branch "main"]
+//   	remote = origin
+//
+// Keys are addressed as `section.name` or `section.subsection.name`. Section and
+// key names are case-insensitive; subsection names are not.
+
+use crate::constants::config_file_path;
+use crate::lock::LockFile;
+use std::fs;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Section {
+    name: String,
+    subsection: Option<String>,
+    entries: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    sections: Vec<Section>,
+}
+
+// Splits `section[.subsection].name` into its parts
+fn split_key(key: &str) -> io::Result<(String, Option<String>, String)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid config key '{}': expected section.name", key));
+
+    let (section, name) = key.split_once('.').ok_or_else(invalid)?;
+    let (subsection, name) = match name.rsplit_once('.') {
+        Some((subsection, name)) => (Some(subsection.to_string()), name),
+        None => (None, name),
+    };
+    if section.is_empty() || name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(invalid());
+    }
+
+    Ok((section.to_lowercase(), subsection, name.to_lowercase()))
+}
+
+impl Config {
+    // Reads `.rit/config`; a missing file is an empty configuration
+    pub fn load() -> io::Result<Self> {
+        match fs::read_to_string(config_file_path()) {
+            Ok(contents) => Config::parse(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse(contents: &str) -> io::Result<Self> {
+        let mut config = Config::default();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let malformed = || io::Error::new(io::ErrorKind::InvalidData, format!("Malformed config line {}: {}", index + 1, line));
+
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header.strip_suffix(']').ok_or_else(malformed)?;
+                let (name, subsection) = match header.split_once(' ') {
+                    Some((name, rest)) => {
+                        let rest = rest.trim();
+                        let subsection = rest.strip_prefix('"').and_then(|r| r.strip_suffix('"')).ok_or_else(malformed)?;
+                        (name, Some(subsection.to_string()))
+                    }
+                    None => (header, None),
+                };
+                config.sections.push(Section { name: name.to_lowercase(), subsection, entries: Vec::new() });
+                continue;
+            }
+
+            let section = config.sections.last_mut().ok_or_else(malformed)?;
+            // A bare key is a boolean set to true, as in git
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => (line, "true"),
+            };
+            section.entries.push((name.to_lowercase(), value.to_string()));
+        }
+
+        Ok(config)
+    }
+
+    fn serialize(&self) -> String {
+        let mut out = String::new();
+        for section in &self.sections {
+            match &section.subsection {
+                Some(subsection) => out.push_str(&format!("[{} \"{}\"]\n", section.name, subsection)),
+                None => out.push_str(&format!("[{}]\n", section.name)),
+            }
+            for (name, value) in &section.entries {
+                out.push_str(&format!("\t{} = {}\n", name, value));
+            }
+        }
+        out
+    }
+
+    // Writes the configuration back to `.rit/config`
+    pub fn save(&self) -> io::Result<()> {
+        LockFile::acquire(&config_file_path())?.commit(self.serialize().as_bytes())
+    }
+
+    // Returns the last value set for the key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let (name, subsection, key) = split_key(key).ok()?;
+        self.sections
+            .iter()
+            .filter(|section| section.name == name && section.subsection == subsection)
+            .flat_map(|section| section.entries.iter())
+            .filter(|(entry_name, _)| *entry_name == key)
+            .map(|(_, value)| value.as_str())
+            .next_back()
+    }
+
+    // Every value as `(section[.subsection].name, value)`, in file order, as `config --list` shows them
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        for section in &self.sections {
+            let prefix = match &section.subsection {
+                Some(subsection) => format!("{}.{}", section.name, subsection),
+                None => section.name.clone(),
+            };
+            for (name, value) in &section.entries {
+                entries.push((format!("{}.{}", prefix, name), value.clone()));
+            }
+        }
+        entries
+    }
+
+    // Returns the value as an integer, accepting git's k/m/g suffixes
+    pub fn get_int(&self, key: &str) -> io::Result<Option<u64>> {
+        let value = match self.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let (digits, multiplier) = match value.to_ascii_lowercase().chars().last() {
+            Some('k') => (&value[..value.len() - 1], 1024),
+            Some('m') => (&value[..value.len() - 1], 1024 * 1024),
+            Some('g') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+            _ => (value, 1),
+        };
+        digits
+            .parse::<u64>()
+            .map(|number| Some(number * multiplier))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid integer for '{}': {}", key, value)))
+    }
+
+    // Returns the value as a boolean, accepting git's true/false, yes/no, on/off and 1/0
+    pub fn get_bool(&self, key: &str) -> io::Result<Option<bool>> {
+        let value = match self.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(Some(true)),
+            "false" | "no" | "off" | "0" | "" => Ok(Some(false)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid boolean for '{}': {}", key, value))),
+        }
+    }
+
+    // Sets the key, replacing its last value or adding it to its section
+    pub fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
+        let (name, subsection, key) = split_key(key)?;
+
+        let existing = self
+            .sections
+            .iter_mut()
+            .filter(|section| section.name == name && section.subsection == subsection)
+            .flat_map(|section| section.entries.iter_mut())
+            .rfind(|(entry_name, _)| *entry_name == key);
+        if let Some(entry) = existing {
+            entry.1 = value.to_string();
+            return Ok(());
+        }
+
+        match self.sections.iter_mut().rev().find(|section| section.name == name && section.subsection == subsection) {
+            Some(section) => section.entries.push((key, value.to_string())),
+            None => self.sections.push(Section { name, subsection, entries: vec![(key, value.to_string())] }),
+        }
+        Ok(())
+    }
+
+    // Removes every value of the key, returning whether anything was removed
+    pub fn unset(&mut self, key: &str) -> io::Result<bool> {
+        let (name, subsection, key) = split_key(key)?;
+        let mut removed = false;
+        for section in self.sections.iter_mut().filter(|section| section.name == name && section.subsection == subsection) {
+            let before = section.entries.len();
+            section.entries.retain(|(entry_name, _)| *entry_name != key);
+            removed |= section.entries.len() != before;
+        }
+        self.sections.retain(|section| !section.entries.is_empty());
+        Ok(removed)
+    }
+}
+
+// Reads a single value from the repository configuration
+pub fn get_value(key: &str) -> io::Result<Option<String>> {
+    Ok(Config::load()?.get(key).map(str::to_string))
+}
+
+// Sets a single value in the repository configuration
+pub fn set_value(key: &str, value: &str) -> io::Result<()> {
+    let mut config = Config::load()?;
+    config.set(key, value)?;
+    config.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn test_parse_and_get() {
+        let config = Config::parse("# comment\n[gc]\n\tauto = 100\n[Branch \"Main\"]\n\tRemote = origin\n\trebase\n").unwrap();
+        assert_eq!(config.get("gc.auto"), Some("100"));
+        assert_eq!(config.get("GC.AUTO"), Some("100"));
+        assert_eq!(config.get("branch.Main.remote"), Some("origin"));
+        assert_eq!(config.get("branch.main.remote"), None);
+        assert_eq!(config.get("branch.Main.rebase"), Some("true"));
+        assert_eq!(config.get_int("gc.auto").unwrap(), Some(100));
+        assert_eq!(config.get_bool("branch.Main.rebase").unwrap(), Some(true));
+        assert_eq!(config.get_bool("gc.missing").unwrap(), None);
+        assert!(config.get_bool("gc.auto").is_err());
+        assert!(Config::parse("auto = 1\n").is_err());
+        assert_eq!(
+            config.entries(),
+            vec![
+                ("gc.auto".to_string(), "100".to_string()),
+                ("branch.Main.remote".to_string(), "origin".to_string()),
+                ("branch.Main.rebase".to_string(), "true".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_unset_round_trip() {
+        let _test_dir = TestDir::with_repo();
+        set_value("user.name", "Tester").unwrap();
+        set_value("user.email", "tester@example.com").unwrap();
+        set_value("user.name", "Renamed").unwrap();
+        set_value("gc.auto", "2k").unwrap();
+
+        let mut config = Config::load().unwrap();
+        assert_eq!(config.get("user.name"), Some("Renamed"));
+        assert_eq!(config.get("user.email"), Some("tester@example.com"));
+        assert_eq!(config.get_int("gc.auto").unwrap(), Some(2048));
+
+        assert!(config.unset("user.email").unwrap());
+        assert!(!config.unset("user.email").unwrap());
+        config.save().unwrap();
+        assert_eq!(get_value("user.email").unwrap(), None);
+        assert!(set_value("nodot", "value").is_err());
+    }
+}
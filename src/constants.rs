@@ -11,6 +11,35 @@ pub const SOURCE_PATH: &str = "src";
 pub const HEAD_FILE: &str = "HEAD";
 pub const REFS_DIR: &str = "refs";
 pub const HEADS_DIR: &str = "heads";
+pub const TAGS_DIR: &str = "tags";
+pub const HOOKS_DIR: &str = "hooks";
+pub const DEFAULT_BRANCH: &str = "master";
+// Environment variable overriding the branch HEAD points to after `rit init` (git's init.defaultBranch)
+pub const DEFAULT_BRANCH_ENV: &str = "RIT_DEFAULT_BRANCH";
+pub const COMMIT_EDITMSG_FILE: &str = "COMMIT_EDITMSG";
+pub const LOST_FOUND_DIR: &str = "lost-found";
+pub const LOGS_DIR: &str = "logs";
+pub const INFO_DIR: &str = "info";
+pub const CONFIG_FILE: &str = "config";
+pub const EXCLUDE_FILE: &str = "exclude";
+pub const DESCRIPTION_FILE: &str = "description";
+// Merge state, present while a merge is stopped waiting for its conflicts to be resolved
+pub const MERGE_HEAD_FILE: &str = "MERGE_HEAD";
+pub const MERGE_MSG_FILE: &str = "MERGE_MSG";
+pub const ORIG_HEAD_FILE: &str = "ORIG_HEAD";
+// Branches and tags consolidated into one file by `pack-refs`
+pub const PACKED_REFS_FILE: &str = "packed-refs";
+// Highest on-disk format this binary understands, recorded as core.repositoryformatversion.
+// Format 0 repositories use the default object format and codec; format 1 ones may not,
+// and list what they need under `extensions`.
+pub const REPOSITORY_FORMAT_VERSION: u64 = 1;
+pub const BASE_REPOSITORY_FORMAT_VERSION: u64 = 0;
+// Placeholder written by `rit init`, the same text git uses
+pub const DEFAULT_DESCRIPTION: &str = "Unnamed repository; edit this file 'description' to name the repository.\n";
+// Ignore file read from the repository root
+pub const RITIGNORE_FILE: &str = ".ritignore";
+// Attributes file read from the repository root
+pub const RITATTRIBUTES_FILE: &str = ".ritattributes";
 
 pub fn directory_path() -> PathBuf {
     Path::new(DIRECTORY_PATH).to_path_buf()
@@ -40,8 +69,62 @@ pub fn heads_dir_path() -> PathBuf {
     refs_dir_path().join(HEADS_DIR)
 }
 
+pub fn tags_dir_path() -> PathBuf {
+    refs_dir_path().join(TAGS_DIR)
+}
+
+pub fn hooks_dir_path() -> PathBuf {
+    directory_path().join(HOOKS_DIR)
+}
+
+pub fn commit_editmsg_path() -> PathBuf {
+    directory_path().join(COMMIT_EDITMSG_FILE)
+}
+
+pub fn lost_found_dir_path() -> PathBuf {
+    directory_path().join(LOST_FOUND_DIR)
+}
+
+pub fn logs_dir_path() -> PathBuf {
+    directory_path().join(LOGS_DIR)
+}
+
+pub fn config_file_path() -> PathBuf {
+    directory_path().join(CONFIG_FILE)
+}
+
+pub fn info_exclude_path() -> PathBuf {
+    directory_path().join(INFO_DIR).join(EXCLUDE_FILE)
+}
+
+pub fn description_path() -> PathBuf {
+    directory_path().join(DESCRIPTION_FILE)
+}
+
+pub fn merge_head_path() -> PathBuf {
+    directory_path().join(MERGE_HEAD_FILE)
+}
+
+pub fn merge_msg_path() -> PathBuf {
+    directory_path().join(MERGE_MSG_FILE)
+}
+
+pub fn orig_head_path() -> PathBuf {
+    directory_path().join(ORIG_HEAD_FILE)
+}
+
+pub fn packed_refs_path() -> PathBuf {
+    directory_path().join(PACKED_REFS_FILE)
+}
+
 
 // Object database types
+// Modes recorded for files in the index and in trees, as in git
+pub const MODE_FILE: u32 = 0o100644;
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+pub const MODE_SYMLINK: u32 = 0o120000;
+
 pub const BLOB: &str = "blob";
 pub const TREE: &str = "tree";
 pub const COMMIT: &str = "commit";
+pub const TAG: &str = "tag";
@@ -1,7 +1,6 @@
-use json::object;
-
-use crate::constants::{DIRECTORY_PATH, OBJECTS_DIR, BLOB};
+use crate::constants::{DIRECTORY_PATH, OBJECTS_DIR, BLOB, COMMIT, TAG, TREE};
 // use crate::utility::{create_directory, open_file};
+use crate::config::Config;
 use crate::compression::{compress_data, uncompress_data};
 use crate::hash::{hash_data};
 
@@ -9,16 +8,15 @@ use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::fs::File;
-use std::io::Read;
 use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-pub fn store_data(data: &[u8], object_type: &str) -> io::Result<String> {
-    // Create metadata for the object
-    let metadata = format!("{} {}\0", object_type, data.len());
+// Stands in for a missing object, e.g. the old value of a new ref or the blob of an added path
+pub const ZERO_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
 
-    // Concatenate the metadata and data
-    let mut object = metadata.into_bytes();
-    object.extend_from_slice(data);
+pub fn store_data(data: &[u8], object_type: &str) -> io::Result<String> {
+    // Prefix the data with its header
+    let object = create_data(data, object_type)?;
 
     //hash the data to obtain the key
     let key = hash_data(&object)?;
@@ -39,37 +37,86 @@ pub fn store_data(data: &[u8], object_type: &str) -> io::Result<String> {
     // Compress the data before writing it to the file
     let object = compress_data(&object)?;
 
-    // Write the data to the file in the object database
-    let mut file = File::create(object_path)?; // Create the file
-    file.write_all(&object)?; // Write the data to the file
-    file.flush()?; // Ensure all data is written to disk
+    // Write to a temporary file and rename it into place, so a crash never leaves a
+    // partial object under its key
+    let temp_path = temp_object_path();
+    let fsync = Config::load()?.get_bool("core.fsyncObjectFiles")?.unwrap_or(true);
+    let written = write_temp_object(&temp_path, &object, fsync)
+        .and_then(|()| fs::rename(&temp_path, &object_path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    written?;
 
     // Return the key
     Ok(key)
 }
 
-pub fn store_file(file_path: &str) -> io::Result<String> {
-    // Open the file in read-only mode and read its contents into a buffer
-    let mut buffer = Vec::new();
-    let mut file = File::open(file_path)?;
-    file.read_to_end(&mut buffer)?;
+// A unique path for an object being written. It sits at the top of the objects directory,
+// where list_objects never looks, so a leftover from a crash is not mistaken for an object.
+fn temp_object_path() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    get_object_database_path().join(format!("tmp_obj_{}_{}", std::process::id(), count))
+}
 
-    // Store the data in the object database
-    store_data(&buffer, BLOB)
+// Writes the compressed object, syncing it to disk unless core.fsyncObjectFiles is off
+fn write_temp_object(path: &Path, object: &[u8], fsync: bool) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(object)?;
+    file.flush()?;
+    if fsync {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+// Computes the key an object would be stored under without writing it
+// Every key is the hash of the header plus the data, exactly as store_data writes it
+pub fn hash_object(data: &[u8], object_type: &str) -> io::Result<String> {
+    hash_data(&create_data(data, object_type)?)
 }
 
-pub fn store_temporary(file_path: &str) -> io::Result<Vec<u8>>{
-    // Open the file in read-only mode and read its contents into a buffer
-    let mut buffer = Vec::new();
-    let mut file = File::open(file_path)?;
-    file.read_to_end(&mut buffer)?;
+// Keys of the empty tree and empty blob, i.e. the hashes of "tree 0\0" and "blob 0\0"
+// under the repository's object format
+pub fn empty_tree_hash() -> io::Result<String> {
+    hash_object(b"", TREE)
+}
 
-    create_data(&buffer, BLOB)
+pub fn empty_blob_hash() -> io::Result<String> {
+    hash_object(b"", BLOB)
+}
+
+// The key of file content; hash-object, add and status all go through this
+pub fn blob_key(bytes: &[u8]) -> io::Result<String> {
+    hash_object(bytes, BLOB)
+}
+
+// Reads what a working file stores as its blob: the file's bytes, or for a symbolic link
+// the path it points to
+pub fn read_working_file(file_path: &Path) -> io::Result<Vec<u8>> {
+    #[cfg(unix)]
+    if file_path.symlink_metadata()?.file_type().is_symlink() {
+        use std::os::unix::ffi::OsStrExt;
+        return Ok(fs::read_link(file_path)?.as_os_str().as_bytes().to_vec());
+    }
+    fs::read(file_path)
+}
+
+// Computes the key a working file's content would be stored under, without storing it
+pub fn hash_working_file(file_path: &Path) -> io::Result<String> {
+    let buffer = read_working_file(file_path)?;
+    blob_key(&buffer)
+}
+
+// Stores a working file as a blob; a symbolic link stores the path it points to
+pub fn store_file(file_path: &str) -> io::Result<String> {
+    store_data(&read_working_file(Path::new(file_path))?, BLOB)
 }
 
 pub fn create_data(data: &[u8], object_type: &str) -> io::Result<Vec<u8>> {
     // Create metadata for the object
-    let metadata = format!("{} {} \0\n", object_type, data.len());
+    let metadata = format!("{} {}\0", object_type, data.len());
 
     // Concatenate the metadata and data
     let mut object = metadata.into_bytes();
@@ -80,40 +127,65 @@ pub fn create_data(data: &[u8], object_type: &str) -> io::Result<Vec<u8>> {
 }
 
 pub fn get_data(key: &str) -> io::Result<(String, usize, Vec<u8>)> {
-    let file_path = get_object_path(key);
-    if file_path.exists() {
-        // Open the file and read its contents into a buffer
-        let mut buffer = Vec::new();
-        let mut file = File::open(file_path)?;
-        file.read_to_end(&mut buffer)?;
-        let data = uncompress_data(&buffer)?;
-        let (object_type, object_size, object_data) = parse_metadata_and_data(&data)?;
-        Ok((object_type.to_string(), object_size, object_data.to_vec()))
-    } else {
-        Err(io::Error::new(io::ErrorKind::NotFound, "Object not found"))
+    if !get_object_path(key).exists() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "Object not found"));
     }
+    // Re-hashing every read costs CPU, so it is only done when core.verifyObjectsOnRead is set
+    let verify = Config::load()?.get_bool("core.verifyObjectsOnRead")?.unwrap_or(false);
+    read_object(key, verify)
+}
+
+// Reads an object and checks that its content still hashes to its key
+pub fn read_verified(key: &str) -> io::Result<(String, usize, Vec<u8>)> {
+    read_object(key, true)
 }
 
+fn read_object(key: &str, verify: bool) -> io::Result<(String, usize, Vec<u8>)> {
+    let buffer = fs::read(get_object_path(key))?;
+    // Name the key in decode errors so a damaged store can be diagnosed
+    let corrupt = |detail: String| io::Error::new(io::ErrorKind::InvalidData, format!("Corrupt object {}: {}", key, detail));
+
+    let data = uncompress_data(&buffer).map_err(|e| corrupt(e.to_string()))?;
+    if verify {
+        let actual = hash_data(&data)?;
+        if actual != key {
+            return Err(corrupt(format!("content hashes to {}", actual)));
+        }
+    }
+    let (object_type, object_size, object_data) = parse_metadata_and_data(&data).map_err(|e| corrupt(e.to_string()))?;
+    Ok((object_type.to_string(), object_size, object_data.to_vec()))
+}
+
+// Headers are `<type> <size>\0`; the longest type and a 20-digit size fit well inside this
+const MAX_HEADER_LEN: usize = 32;
+
 fn parse_metadata_and_data(data: &[u8]) -> io::Result<(&str, usize, &[u8])> {
-    // Find the position of the first space character in the data
-    let first_space = data
+    // Only look for the terminator in the header region, so payload bytes are never mistaken for it
+    let header_region = &data[..data.len().min(MAX_HEADER_LEN)];
+    let null_char = header_region
         .iter()
-        .position(|&x| x == b' ')
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid metadata: no space"))?;
+        .position(|&x| x == b'\0')
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid metadata: no null terminator in header"))?;
 
-    // Find the position of the null character in the data
-    let null_char = data
+    // The space separating type and size must come before the terminator
+    let first_space = header_region[..null_char]
         .iter()
-        .position(|&x| x == b'\0')
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid metadata: no null terminator"))?;
+        .position(|&x| x == b' ')
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid metadata: no space"))?;
 
     // Extract the object type from the data
     let object_type = std::str::from_utf8(&data[..first_space])
         .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid object type"))?;
+    if ![BLOB, TREE, COMMIT, TAG].contains(&object_type) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unknown object type {:?}", object_type)));
+    }
 
     // Extract the object size from the data
     let object_size_str = std::str::from_utf8(&data[first_space + 1..null_char])
         .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid object size"))?;
+    if object_size_str.is_empty() || !object_size_str.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Object size is not a valid number"));
+    }
     let object_size: usize = object_size_str
         .parse()
         .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Object size is not a valid number"))?;
@@ -132,9 +204,84 @@ pub fn delete_data(key: &str) -> io::Result<()> {
     Ok(())
 }
 
+// Lists the keys of every object in the object database
+pub fn list_objects() -> io::Result<Vec<String>> {
+    let mut keys = Vec::new();
+    let objects_dir = get_object_database_path();
+    if !objects_dir.exists() {
+        return Ok(keys);
+    }
+
+    for sub_dir in fs::read_dir(objects_dir)? {
+        let sub_dir = sub_dir?;
+        if !sub_dir.file_type()?.is_dir() {
+            continue;
+        }
+        let prefix = sub_dir.file_name().to_string_lossy().to_string();
+        for object in fs::read_dir(sub_dir.path())? {
+            let object = object?;
+            keys.push(format!("{}{}", prefix, object.file_name().to_string_lossy()));
+        }
+    }
+
+    keys.sort();
+    Ok(keys)
+}
+
+// Expands an abbreviated key to the one stored object whose key starts with it. A prefix of
+// two or more characters only needs its own subdirectory; a shorter one searches every
+// subdirectory it could begin.
+pub fn resolve_prefix(prefix: &str) -> io::Result<String> {
+    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is not a hexadecimal key prefix", prefix)));
+    }
+    let prefix = prefix.to_ascii_lowercase();
+    let objects_dir = get_object_database_path();
+
+    let mut matches = Vec::new();
+    if objects_dir.exists() {
+        for sub_dir in fs::read_dir(objects_dir)? {
+            let sub_dir = sub_dir?;
+            let sub_dir_name = sub_dir.file_name().to_string_lossy().to_string();
+            if !sub_dir.file_type()?.is_dir() || !(sub_dir_name.starts_with(&prefix) || prefix.starts_with(&sub_dir_name)) {
+                continue;
+            }
+            for object in fs::read_dir(sub_dir.path())? {
+                let key = format!("{}{}", sub_dir_name, object?.file_name().to_string_lossy());
+                // Skip anything that is not an object, such as a temporary file mid-write
+                if key.starts_with(&prefix) && key.chars().all(|c| c.is_ascii_hexdigit()) {
+                    matches.push(key);
+                }
+            }
+        }
+    }
+
+    match matches.len() {
+        0 => Err(io::Error::new(io::ErrorKind::NotFound, format!("No object matches '{}'", prefix))),
+        1 => Ok(matches.remove(0)),
+        _ => {
+            matches.sort();
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Short key '{}' is ambiguous; it matches {}", prefix, matches.join(", ")),
+            ))
+        }
+    }
+}
+
+// Checks whether an object with the given key is stored
+pub fn object_exists(key: &str) -> bool {
+    !key.is_empty() && get_object_path(key).is_file()
+}
+
+// Returns the size in bytes an object takes on disk
+pub fn object_disk_size(key: &str) -> io::Result<u64> {
+    Ok(fs::metadata(get_object_path(key))?.len())
+}
+
 // HELPERS
 // Returns the path to a specific object based on the key
-fn get_object_path(key: &str) -> PathBuf {
+pub(crate) fn get_object_path(key: &str) -> PathBuf {
     let sub_dir_name: String = key.chars().take(2).collect();
     let filename: String = key.chars().skip(2).collect();
     Path::new(DIRECTORY_PATH)
@@ -158,29 +305,59 @@ pub fn create_object_database() -> io::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::tempdir;
+    use crate::constants::TREE;
+    use crate::test_utils::TestDir;
     use std::fs;
-    use std::env;
-    use std::fs::metadata;
     use std::path::PathBuf;
 
     // Helper function to create a temp directory and set it as the current working directory
-    fn setup_test_env() -> PathBuf {
-        // Create a temporary directory for the test
-        let temp_dir = tempdir().unwrap();
-        let temp_dir_path = temp_dir.path().to_path_buf();
-        let original_dir = env::current_dir().unwrap();
+    // The original directory is restored when the returned guard is dropped
+    fn setup_test_env() -> TestDir {
+        TestDir::new()
+    }
 
-        // Change the current working directory to the temp directory
-        env::set_current_dir(&temp_dir_path).unwrap();
+    #[test]
+    fn test_verify_objects_on_read() {
+        let _test_dir = TestDir::with_repo();
+        let key = store_data(b"original", BLOB).unwrap();
+        let tampered = compress_data(&create_data(b"tampered", BLOB).unwrap()).unwrap();
+        fs::write(get_object_path(&key), tampered).unwrap();
+
+        // Off by default: the wrong bytes come back unnoticed
+        assert_eq!(get_data(&key).unwrap().2, b"tampered");
+
+        crate::config::set_value("core.verifyObjectsOnRead", "true").unwrap();
+        let error = get_data(&key).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains(&format!("Corrupt object {}", key)));
+
+        // Intact objects still read normally with the check on
+        let intact = store_data(b"intact", BLOB).unwrap();
+        assert_eq!(get_data(&intact).unwrap().2, b"intact");
+    }
 
-        // Return the original directory so we can restore it after the test
-        original_dir
+    #[test]
+    fn test_store_writes_complete_objects() {
+        let _test_dir = TestDir::with_repo();
+        let data = vec![b'x'; 64 * 1024];
+        for fsync in ["true", "false"] {
+            crate::config::set_value("core.fsyncObjectFiles", fsync).unwrap();
+            let key = store_data(&[data.as_slice(), fsync.as_bytes()].concat(), BLOB).unwrap();
+
+            // The object is complete on disk and no temporary file is left behind
+            let stored = fs::read(get_object_path(&key)).unwrap();
+            assert!(!stored.is_empty());
+            assert_eq!(uncompress_data(&stored).unwrap(), create_data(&[data.as_slice(), fsync.as_bytes()].concat(), BLOB).unwrap());
+            let leftovers: Vec<_> = fs::read_dir(get_object_database_path()).unwrap()
+                .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("tmp_obj_"))
+                .collect();
+            assert!(leftovers.is_empty());
+        }
     }
 
     #[test]
     fn test_store_and_get_data() {
-        let original_dir = setup_test_env(); // Switch to temp directory
+        let _test_dir = setup_test_env(); // Switch to temp directory
 
         // Perform the test
         let data = b"example data";
@@ -191,32 +368,37 @@ mod tests {
         assert_eq!(object_data, data);
         assert_eq!(object_type, BLOB);
         assert_eq!(object_size, data.len());
-
-        // Restore the original directory
-        env::set_current_dir(&original_dir).unwrap();
     }
 
     #[test]
     fn test_store_temp_and_create_data() {
-        let original_dir = setup_test_env(); // Switch to temp directory
+        let _test_dir = setup_test_env(); // Switch to temp directory
 
         // Perform the test
         let data = b"example data";
-        let key = create_data(data, BLOB).unwrap();
-        // Check if the data was stored and can be retrieved correctly
-        // match std::str::from_utf8(&key) {
-        //     Ok(decoded_data) => println!("{}", decoded_data),
-        //     Err(e) => eprintln!("Failed to decode bytes: {}", e),
-        // }
-        assert!(true);
-
-        // Restore the original directory
-        env::set_current_dir(&original_dir).unwrap();
+        let object = create_data(data, BLOB).unwrap();
+        // Check if the object carries the header followed by the data
+        assert!(object.starts_with(b"blob 12"));
+        assert!(object.ends_with(data));
+    }
+
+    #[test]
+    fn test_truncated_object_names_key() {
+        let _test_dir = setup_test_env();
+
+        let key = store_data(b"some data that will be damaged", BLOB).unwrap();
+        let object_path = get_object_path(&key);
+        let compressed = fs::read(&object_path).unwrap();
+        fs::write(&object_path, &compressed[..compressed.len() / 2]).unwrap();
+
+        let error = get_data(&key).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with(&format!("Corrupt object {}: ", key)), "{}", error);
     }
 
     #[test]
     fn test_store_and_delete_data() {
-        let original_dir = setup_test_env();
+        let _test_dir = setup_test_env();
 
         let data = b"example data";
         let key = store_data(data, BLOB).unwrap();
@@ -231,13 +413,11 @@ mod tests {
 
         delete_data(&key).unwrap();
         assert!(get_data(&key).is_err());
-
-        env::set_current_dir(&original_dir).unwrap();
     }
 
     #[test]
     fn test_store_file() {
-        let original_dir = setup_test_env();
+        let _test_dir = setup_test_env();
 
         // Create a temporary file with some content
         let file_path = PathBuf::from("test_file.txt");
@@ -252,26 +432,76 @@ mod tests {
         assert_eq!(object_data, file_data);
         assert_eq!(object_type, BLOB);
         assert_eq!(object_size, file_data.len());
+    }
+
+    #[test]
+    fn test_list_objects() {
+        let _test_dir = setup_test_env();
+
+        let first = store_data(b"first", BLOB).unwrap();
+        let second = store_data(b"second", BLOB).unwrap();
 
-        env::set_current_dir(&original_dir).unwrap();
+        let mut expected = vec![first, second];
+        expected.sort();
+        assert_eq!(list_objects().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_resolve_prefix() {
+        let _test_dir = setup_test_env();
+        let first = store_data(b"first", BLOB).unwrap();
+        // A single character searches every subdirectory it could begin
+        assert_eq!(resolve_prefix(&first[..1]).unwrap(), first);
+
+        let second = store_data(b"second", BLOB).unwrap();
+        assert_eq!(resolve_prefix(&first).unwrap(), first);
+        assert_eq!(resolve_prefix(&second[..7]).unwrap(), second);
+        assert_eq!(resolve_prefix(&second[..7].to_uppercase()).unwrap(), second);
+
+        // Two objects sharing a prefix make it ambiguous; a prefix no object has is not found
+        let twin = format!("{}{}", &first[..3], "0".repeat(first.len() - 3));
+        let twin = if twin == first { format!("{}{}", &first[..3], "1".repeat(first.len() - 3)) } else { twin };
+        fs::copy(get_object_path(&first), get_object_path(&twin)).unwrap();
+        let error = resolve_prefix(&first[..3]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("ambiguous"), "{}", error);
+        let unused = (0..16).map(|d| format!("{:x}", d)).find(|d| !first.starts_with(d.as_str()) && !second.starts_with(d.as_str())).unwrap();
+        assert_eq!(resolve_prefix(&unused).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(resolve_prefix("xyz").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(resolve_prefix("").is_err());
+    }
+
+    #[test]
+    fn test_empty_object_hashes() {
+        let _test_dir = setup_test_env();
+
+        assert_eq!(empty_tree_hash().unwrap(), "6ef19b41225c5369f1c104d45d8d85efa9b057b53b14b4b9b939dd74decc5321");
+        assert_eq!(empty_blob_hash().unwrap(), "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813");
+        assert_eq!(store_data(b"", TREE).unwrap(), empty_tree_hash().unwrap());
+        assert_eq!(store_data(b"", BLOB).unwrap(), empty_blob_hash().unwrap());
+
+        // Other object formats have their own empty objects, which read back without being stored
+        crate::config::set_value(crate::hash::OBJECT_FORMAT_KEY, "blake3").unwrap();
+        let empty_tree = empty_tree_hash().unwrap();
+        assert_eq!(empty_tree, hex::encode(crate::hash::blake3(b"tree 0\0")));
+        assert_eq!(empty_blob_hash().unwrap(), hex::encode(crate::hash::blake3(b"blob 0\0")));
+        assert!(crate::tree::read_tree(&empty_tree).unwrap().is_empty());
     }
 
     #[test]
     fn test_data_not_found() {
-        let original_dir = setup_test_env();
+        let _test_dir = setup_test_env();
 
         let non_existent_key = "nonexistentkey1234567890";
         assert!(get_data(non_existent_key).is_err());
-
-        env::set_current_dir(&original_dir).unwrap();
     }
 
     #[test]
     fn test_valid_input() {
-        let data: &[u8] = b"object_type 1234\0object_data";
+        let data: &[u8] = b"blob 1234\0object_data";
 
         // Expected output
-        let expected_type = "object_type";
+        let expected_type = "blob";
         let expected_size = 1234;
         let expected_data = b"object_data";
 
@@ -289,4 +519,21 @@ mod tests {
         assert_eq!(object_size, expected_size);
         assert_eq!(object_data, expected_data);
     }
+
+    #[test]
+    fn test_header_with_early_nul_or_space() {
+        // NUL and space bytes in the payload do not confuse the header
+        let (object_type, object_size, object_data) = parse_metadata_and_data(b"blob 5\0a\0 b\0").unwrap();
+        assert_eq!((object_type, object_size, object_data), ("blob", 5, &b"a\0 b\0"[..]));
+
+        // A NUL before the space, or a header with no terminator near the start, is rejected
+        assert!(parse_metadata_and_data(b"blob\0 5\0data").is_err());
+        assert!(parse_metadata_and_data(&[b"blob 5".as_slice(), &[b'x'; 40], b"\0"].concat()).is_err());
+        // So are unknown types and sizes that are not plain digits
+        assert!(parse_metadata_and_data(b"blab 5\0hello").is_err());
+        assert!(parse_metadata_and_data(b"blob +5\0hello").is_err());
+        assert!(parse_metadata_and_data(b"blob 5 6\0hello").is_err());
+        assert!(parse_metadata_and_data(b"blob \0hello").is_err());
+        assert!(parse_metadata_and_data(b"").is_err());
+    }
 }
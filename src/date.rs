@@ -0,0 +1,137 @@
+// Timezone offsets and date formatting for commit timestamps. Offsets are kept as
+// minutes east of UTC and written in git's `+HHMM` form.
+
+use crate::config::Config;
+use std::io;
+
+// Parses an offset such as `+0130` or `-0500` into minutes
+pub fn parse_offset(tz: &str) -> Option<i32> {
+    let sign = match tz.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = &tz[1..];
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+// Formats minutes east of UTC as `+HHMM`
+pub fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!("{}{:02}{:02}", sign, offset / 60, offset % 60)
+}
+
+// The offset to record in new commits: `user.timezone` from the config if set, otherwise
+// the system's current offset, falling back to UTC when it cannot be determined
+pub fn local_offset() -> io::Result<i32> {
+    if let Some(tz) = Config::load()?.get("user.timezone") {
+        return parse_offset(tz)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid user.timezone '{}': expected +HHMM", tz)));
+    }
+    Ok(system_offset().unwrap_or(0))
+}
+
+// Asks the C library for the current offset, which honours TZ and /etc/localtime
+#[cfg(unix)]
+fn system_offset() -> Option<i32> {
+    // SAFETY: time accepts a null pointer, a zeroed tm is a valid value, and localtime_r
+    // only writes through pointers to live locals without keeping either
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return None;
+    }
+    Some((tm.tm_gmtoff / 60) as i32)
+}
+
+#[cfg(not(unix))]
+fn system_offset() -> Option<i32> {
+    None
+}
+
+// Converts days since 1970-01-01 into a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's algorithm, counting in 400-year eras starting on 0000-03-01
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Formats a timestamp in the time of its offset, as `YYYY-MM-DD HH:MM:SS +HHMM`
+pub fn format_iso(timestamp: u64, offset: i32) -> String {
+    let local = timestamp as i64 + offset as i64 * 60;
+    let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+    let seconds = local.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        format_offset(offset)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn test_offsets_round_trip() {
+        assert_eq!(parse_offset("+0000"), Some(0));
+        assert_eq!(parse_offset("+0130"), Some(90));
+        assert_eq!(parse_offset("-0500"), Some(-300));
+        assert_eq!(parse_offset("0500"), None);
+        assert_eq!(parse_offset("+05"), None);
+        assert_eq!(parse_offset("+0575"), None);
+        for offset in [0, 90, -300, -570, 840] {
+            assert_eq!(parse_offset(&format_offset(offset)), Some(offset));
+        }
+    }
+
+    #[test]
+    fn test_format_iso() {
+        assert_eq!(format_iso(0, 0), "1970-01-01 00:00:00 +0000");
+        assert_eq!(format_iso(1705327402, 0), "2024-01-15 14:03:22 +0000");
+        assert_eq!(format_iso(1705327402, 330), "2024-01-15 19:33:22 +0530");
+        // West of UTC the local date can fall on the previous day
+        assert_eq!(format_iso(1705280000, -480), "2024-01-14 16:53:20 -0800");
+        assert_eq!(format_iso(951782400, 0), "2000-02-29 00:00:00 +0000");
+    }
+
+    #[test]
+    fn test_config_overrides_local_offset() {
+        let _test_dir = TestDir::with_repo();
+        crate::config::set_value("user.timezone", "-0330").unwrap();
+        assert_eq!(local_offset().unwrap(), -210);
+
+        crate::config::set_value("user.timezone", "later").unwrap();
+        assert!(local_offset().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_system_offset_is_a_real_offset() {
+        let offset = system_offset().unwrap();
+        assert!((-14 * 60..=14 * 60).contains(&offset), "{}", offset);
+    }
+}
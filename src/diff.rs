@@ -0,0 +1,901 @@
+// Compares trees to find which paths changed between them.
+
+use crate::commit::read_commit;
+use crate::constants::{COMMIT, TAG, TREE};
+use crate::attributes::is_binary_path;
+use crate::base85;
+use crate::branches;
+use crate::compression::{zlib_stored, zlib_unstore};
+use crate::database::{self, hash_working_file, ZERO_HASH};
+use crate::index::{load_index, working_file_mode, IndexEntry};
+use crate::tag::Tag;
+use crate::text::decode_text;
+use crate::tree::flatten_tree;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::Path;
+
+// Minimum similarity, in percent, for `-M` without a value
+pub const DEFAULT_RENAME_THRESHOLD: u8 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Deleted,
+    Modified,
+    // Holds the similarity score in percent
+    Renamed(u8),
+}
+
+impl ChangeStatus {
+    // The single letter git uses for the change
+    pub fn letter(&self) -> char {
+        match self {
+            ChangeStatus::Added => 'A',
+            ChangeStatus::Deleted => 'D',
+            ChangeStatus::Modified => 'M',
+            ChangeStatus::Renamed(_) => 'R',
+        }
+    }
+
+    // The word `status` uses for the change
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChangeStatus::Added => "new file",
+            ChangeStatus::Deleted => "deleted",
+            ChangeStatus::Modified => "modified",
+            ChangeStatus::Renamed(_) => "renamed",
+        }
+    }
+
+    // The letter plus the similarity score for renames, e.g. "R100"
+    pub fn code(&self) -> String {
+        match self {
+            ChangeStatus::Renamed(score) => format!("R{:03}", score),
+            other => other.letter().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineOp<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathChange {
+    pub path: String,
+    pub status: ChangeStatus,
+    pub old: Option<IndexEntry>,
+    pub new: Option<IndexEntry>,
+}
+
+// Compares two lists of entries keyed by path, returning the changes sorted by path
+pub fn compare_entries(old_entries: Vec<IndexEntry>, new_entries: Vec<IndexEntry>) -> Vec<PathChange> {
+    let mut old_map: BTreeMap<String, IndexEntry> = old_entries.into_iter().map(|e| (e.path.clone(), e)).collect();
+    let new_map: BTreeMap<String, IndexEntry> = new_entries.into_iter().map(|e| (e.path.clone(), e)).collect();
+    let mut changes = Vec::new();
+
+    for (path, new_entry) in new_map {
+        match old_map.remove(&path) {
+            Some(old_entry) if old_entry.blob_hash == new_entry.blob_hash && old_entry.mode == new_entry.mode => {}
+            Some(old_entry) => changes.push(PathChange {
+                path,
+                status: ChangeStatus::Modified,
+                old: Some(old_entry),
+                new: Some(new_entry),
+            }),
+            None => changes.push(PathChange {
+                path,
+                status: ChangeStatus::Added,
+                old: None,
+                new: Some(new_entry),
+            }),
+        }
+    }
+
+    for (path, old_entry) in old_map {
+        changes.push(PathChange {
+            path,
+            status: ChangeStatus::Deleted,
+            old: Some(old_entry),
+            new: None,
+        });
+    }
+
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+// Lists the paths that differ between two trees
+pub fn changed_paths(old_tree: &str, new_tree: &str) -> io::Result<Vec<PathChange>> {
+    Ok(compare_entries(flatten_tree(old_tree)?, flatten_tree(new_tree)?))
+}
+
+// Lists the paths a commit changed relative to its parent
+// A root commit is compared against the empty tree, so every path shows as added
+pub fn commit_changed_paths(commit_hash: &str) -> io::Result<Vec<PathChange>> {
+    let commit = read_commit(commit_hash)?;
+    let parent_tree = match &commit.parent {
+        Some(parent) => read_commit(parent)?.tree,
+        None => database::empty_tree_hash()?,
+    };
+    changed_paths(&parent_tree, &commit.tree)
+}
+
+// Finds the shortest edit script between two lists of lines. This is Myers' algorithm in
+// its linear-space form: find the middle of an optimal path, then solve each half, so
+// memory stays proportional to the input however different the lists are.
+pub fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<LineOp<'a>> {
+    // Compare small numbers rather than whole lines while searching
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    let mut intern = |lines: &[&'a str]| -> Vec<usize> {
+        lines.iter().map(|line| { let next = ids.len(); *ids.entry(*line).or_insert(next) }).collect()
+    };
+    let (old_ids, new_ids) = (intern(old), intern(new));
+
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    diff_range(&old_ids, &new_ids, 0, 0, &mut edits);
+
+    // Within each run of changes, list deletions before insertions
+    let mut ops = Vec::with_capacity(edits.len());
+    let mut inserts = Vec::new();
+    for edit in edits {
+        match edit {
+            Edit::Equal(x) => {
+                ops.append(&mut inserts);
+                ops.push(LineOp::Equal(old[x]));
+            }
+            Edit::Delete(x) => ops.push(LineOp::Delete(old[x])),
+            Edit::Insert(y) => inserts.push(LineOp::Insert(new[y])),
+        }
+    }
+    ops.append(&mut inserts);
+    ops
+}
+
+// One step of an edit script, by position in the old or new list
+enum Edit {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+// Appends the edits turning `old` into `new`, which start at `x0` and `y0` in the full lists
+fn diff_range(old: &[usize], new: &[usize], x0: usize, y0: usize, edits: &mut Vec<Edit>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    edits.extend((0..prefix).map(|i| Edit::Equal(x0 + i)));
+
+    let (old_mid, new_mid) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    let (x_mid, y_mid) = (x0 + prefix, y0 + prefix);
+    if old_mid.is_empty() {
+        edits.extend((0..new_mid.len()).map(|i| Edit::Insert(y_mid + i)));
+    } else if new_mid.is_empty() {
+        edits.extend((0..old_mid.len()).map(|i| Edit::Delete(x_mid + i)));
+    } else {
+        let (x, y) = middle_point(old_mid, new_mid);
+        diff_range(&old_mid[..x], &new_mid[..y], x_mid, y_mid, edits);
+        diff_range(&old_mid[x..], &new_mid[y..], x_mid + x, y_mid + y, edits);
+    }
+
+    let old_end = x0 + old.len() - suffix;
+    edits.extend((0..suffix).map(|i| Edit::Equal(old_end + i)));
+}
+
+// Finds a point on a shortest edit path by searching forwards from the start and backwards
+// from the end at once until the two searches overlap. Both lists are non-empty and differ
+// in their first and last lines, so the point always splits the problem in two.
+fn middle_point(old: &[usize], new: &[usize]) -> (usize, usize) {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let width = (2 * offset + 1) as usize;
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+
+    // forward[k] is the furthest x reached on diagonal k = x - y; backward[k] is how far
+    // from the end the reverse search got on diagonal k, counted from the end
+    let mut forward = vec![-1isize; width];
+    let mut backward = vec![-1isize; width];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+
+    for d in 0..=max {
+        let mut k = -d;
+        while k <= d {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && forward[i - 1] < forward[i + 1]) { forward[i + 1] } else { forward[i - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[i] = x;
+            if odd {
+                let back_k = delta - k;
+                if (-(d - 1)..=d - 1).contains(&back_k) {
+                    let back_x = backward[(offset + back_k) as usize];
+                    if back_x >= 0 && x + back_x >= n {
+                        return (x as usize, y as usize);
+                    }
+                }
+            }
+            k += 2;
+        }
+
+        let mut k = -d;
+        while k <= d {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && backward[i - 1] < backward[i + 1]) { backward[i + 1] } else { backward[i - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[i] = x;
+            if !odd {
+                let forward_k = delta - k;
+                if (-d..=d).contains(&forward_k) {
+                    let forward_x = forward[(offset + forward_k) as usize];
+                    if forward_x >= 0 && forward_x + x >= n {
+                        let forward_y = forward_x - forward_k;
+                        return (forward_x as usize, forward_y as usize);
+                    }
+                }
+            }
+            k += 2;
+        }
+    }
+    unreachable!("the forward and backward searches always meet")
+}
+
+// Lists the paths staged in the index relative to the HEAD commit
+pub fn index_changes() -> io::Result<Vec<PathChange>> {
+    let head_entries = match branches::get_current_branch_commit_hash()? {
+        Some(head) => flatten_tree(&read_commit(&head)?.tree)?,
+        None => Vec::new(),
+    };
+    Ok(compare_entries(head_entries, load_index()?))
+}
+
+// Lists tracked paths whose working copy differs from the index. Untracked files are
+// not included, as in `git diff`.
+pub fn worktree_changes() -> io::Result<Vec<PathChange>> {
+    let mut changes = Vec::new();
+    for entry in load_index()? {
+        let path = Path::new(&entry.path);
+        if !path.is_file() {
+            changes.push(PathChange { path: entry.path.clone(), status: ChangeStatus::Deleted, old: Some(entry), new: None });
+            continue;
+        }
+        let working_hash = hash_working_file(path)?;
+        let working_mode = working_file_mode(path)?;
+        if working_hash != entry.blob_hash || working_mode != entry.mode {
+            let new = IndexEntry { blob_hash: working_hash, mode: working_mode, ..entry.clone() };
+            changes.push(PathChange { path: entry.path.clone(), status: ChangeStatus::Modified, old: Some(entry), new: Some(new) });
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+// Formats a change as git's raw diff line: `:<old mode> <new mode> <old> <new> <status>\t<path>`
+pub fn raw_line(change: &PathChange) -> String {
+    let mode = |entry: &Option<IndexEntry>| entry.as_ref().map(|e| format!("{:06o}", e.mode)).unwrap_or_else(|| "000000".to_string());
+    let hash = |entry: &Option<IndexEntry>| entry.as_ref().map(|e| e.blob_hash.clone()).unwrap_or_else(|| ZERO_HASH.to_string());
+    let paths = match (&change.status, &change.old) {
+        (ChangeStatus::Renamed(_), Some(old)) => format!("{}\t{}", old.path, change.path),
+        _ => change.path.clone(),
+    };
+    format!(":{} {} {} {} {}\t{}", mode(&change.old), mode(&change.new), hash(&change.old), hash(&change.new), change.status.code(), paths)
+}
+
+// Lines of unchanged context around each hunk, as in git
+pub const DIFF_CONTEXT: usize = 3;
+
+// Renders the line differences between two texts as unified diff hunks
+pub fn unified_hunks(old: &str, new: &str, context: usize) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&old_lines, &new_lines);
+
+    // Position in the old and new file before each op
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for op in &ops {
+        positions.push((old_line, new_line));
+        match op {
+            LineOp::Equal(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            LineOp::Delete(_) => old_line += 1,
+            LineOp::Insert(_) => new_line += 1,
+        }
+    }
+    positions.push((old_line, new_line));
+
+    // Group changed ops that are close enough to share context
+    let changed: Vec<usize> = (0..ops.len()).filter(|&i| !matches!(ops[i], LineOp::Equal(_))).collect();
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &index in &changed {
+        match groups.last_mut() {
+            Some((_, last)) if index - *last <= 2 * context => *last = index,
+            _ => groups.push((index, index)),
+        }
+    }
+
+    let mut out = String::new();
+    for (first, last) in groups {
+        let start = first.saturating_sub(context);
+        let end = (last + context + 1).min(ops.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let (old_count, new_count) = (old_end - old_start, new_end - new_start);
+
+        // An empty side is numbered by the line before it, like git
+        let old_from = if old_count == 0 { old_start } else { old_start + 1 };
+        let new_from = if new_count == 0 { new_start } else { new_start + 1 };
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_from, old_count, new_from, new_count));
+
+        for op in &ops[start..end] {
+            match op {
+                LineOp::Equal(line) => out.push_str(&format!(" {}\n", line)),
+                LineOp::Delete(line) => out.push_str(&format!("-{}\n", line)),
+                LineOp::Insert(line) => out.push_str(&format!("+{}\n", line)),
+            }
+        }
+    }
+    out
+}
+
+// Renders one changed path as a patch
+pub fn path_patch(change: &PathChange) -> io::Result<String> {
+    path_patch_with(change, false, false)
+}
+
+// Like path_patch. With `from_worktree` the new side is read from the working file rather
+// than the object store, and with `binary` binary changes are written out in full as a
+// `GIT binary patch` section instead of only being noted.
+pub fn path_patch_with(change: &PathChange, from_worktree: bool, binary: bool) -> io::Result<String> {
+    let old_path = change.old.as_ref().map(|entry| entry.path.as_str()).unwrap_or(&change.path);
+    let mut out = format!("diff --rit a/{} b/{}\n", old_path, change.path);
+    match (&change.old, &change.new) {
+        (None, Some(new)) => out.push_str(&format!("new file mode {:o}\n", new.mode)),
+        (Some(old), None) => out.push_str(&format!("deleted file mode {:o}\n", old.mode)),
+        _ => {}
+    }
+
+    let old_bytes = match &change.old {
+        Some(entry) => database::get_data(&entry.blob_hash)?.2,
+        None => Vec::new(),
+    };
+    let new_bytes = match &change.new {
+        Some(_) if from_worktree => database::read_working_file(Path::new(&change.path))?,
+        Some(entry) => database::get_data(&entry.blob_hash)?.2,
+        None => Vec::new(),
+    };
+    // Paths the attributes mark as binary are never line-diffed, even if they decode as text
+    let texts = if is_binary_path(&change.path)? || is_binary_path(old_path)? {
+        None
+    } else {
+        decode_text(&old_bytes).zip(decode_text(&new_bytes))
+    };
+    let (old_text, new_text) = match texts {
+        Some(texts) => texts,
+        None if binary => {
+            out.push_str(&binary_patch(&old_bytes, &new_bytes));
+            return Ok(out);
+        }
+        None => {
+            out.push_str(&format!("Binary files a/{} and b/{} differ\n", old_path, change.path));
+            return Ok(out);
+        }
+    };
+
+    let old_label = if change.old.is_some() { format!("a/{}", old_path) } else { "/dev/null".to_string() };
+    let new_label = if change.new.is_some() { format!("b/{}", change.path) } else { "/dev/null".to_string() };
+    out.push_str(&format!("--- {}\n+++ {}\n", old_label, new_label));
+    out.push_str(&unified_hunks(&old_text, &new_text, DIFF_CONTEXT));
+    Ok(out)
+}
+
+// Bytes of data per line of a binary patch hunk, as in git
+const BINARY_LINE_BYTES: usize = 52;
+
+// One `literal` hunk: the zlib-wrapped content in base85 lines, each prefixed with a
+// letter giving its byte count (A-Z for 1-26, a-z for 27-52), then a blank line
+fn literal_hunk(data: &[u8]) -> String {
+    let mut out = format!("literal {}\n", data.len());
+    for line in zlib_stored(data).chunks(BINARY_LINE_BYTES) {
+        let prefix = if line.len() <= 26 { b'A' + line.len() as u8 - 1 } else { b'a' + line.len() as u8 - 27 };
+        out.push(prefix as char);
+        out.push_str(&base85::encode(line));
+        out.push('\n');
+    }
+    out.push('\n');
+    out
+}
+
+// A `GIT binary patch` section carrying the new content in full, followed by the old
+// content so the patch can be checked against (or reversed onto) what it applies to
+pub fn binary_patch(old: &[u8], new: &[u8]) -> String {
+    format!("GIT binary patch\n{}{}", literal_hunk(new), literal_hunk(old))
+}
+
+// Reads the `literal` hunk starting at `lines[at]`, returning its content and the line after it
+fn read_literal_hunk(lines: &[&str], at: usize) -> io::Result<(Vec<u8>, usize)> {
+    let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidData, format!("Corrupt binary patch: {}", reason));
+    let header = lines.get(at).ok_or_else(|| invalid("missing hunk".to_string()))?;
+    let size: usize = match header.strip_prefix("literal ") {
+        Some(size) => size.parse().map_err(|_| invalid(format!("bad size in {:?}", header)))?,
+        None if header.starts_with("delta ") => {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "Binary delta hunks are not supported; only literal hunks"));
+        }
+        None => return Err(invalid(format!("expected a literal hunk, found {:?}", header))),
+    };
+
+    let mut stream = Vec::new();
+    let mut next = at + 1;
+    while let Some(line) = lines.get(next).filter(|line| !line.is_empty()) {
+        let (&prefix, encoded) = line.as_bytes().split_first().expect("line is not empty");
+        let len = match prefix {
+            b'A'..=b'Z' => (prefix - b'A') as usize + 1,
+            b'a'..=b'z' => (prefix - b'a') as usize + 27,
+            _ => return Err(invalid(format!("bad line length character {:?}", prefix as char))),
+        };
+        stream.extend(base85::decode(std::str::from_utf8(encoded).unwrap_or_default(), len)?);
+        next += 1;
+    }
+
+    let data = zlib_unstore(&stream)?;
+    if data.len() != size {
+        return Err(invalid(format!("expected {} bytes, found {}", size, data.len())));
+    }
+    Ok((data, next + 1))
+}
+
+// Applies the binary section of a patch to the old content, returning the new content.
+// The old content must be what the patch was made from.
+pub fn apply_binary_patch(patch: &str, old: &[u8]) -> io::Result<Vec<u8>> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| *line == "GIT binary patch")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No binary patch section found"))?;
+
+    let (new, next) = read_literal_hunk(&lines, start + 1)?;
+    let (expected_old, _) = read_literal_hunk(&lines, next)?;
+    if expected_old != old {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Binary patch does not apply: the old content differs"));
+    }
+    Ok(new)
+}
+
+// Renders everything a commit changed against its first parent (or the empty tree)
+pub fn commit_patch(commit_hash: &str) -> io::Result<String> {
+    let mut out = String::new();
+    for change in commit_changed_paths(commit_hash)? {
+        out.push_str(&path_patch(&change)?);
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhitespaceError {
+    pub path: String,
+    pub line: usize,
+    pub problem: &'static str,
+    pub content: String,
+}
+
+// Finds whitespace problems in one line: trailing spaces or tabs, and a space
+// immediately before a tab in the indentation
+pub fn line_whitespace_problems(line: &str) -> Vec<&'static str> {
+    let mut problems = Vec::new();
+    if line.ends_with(' ') || line.ends_with('\t') {
+        problems.push("trailing whitespace");
+    }
+    let indent: &str = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+    if indent.contains(" \t") {
+        problems.push("space before tab in indent");
+    }
+    problems
+}
+
+// Checks the lines a change adds for whitespace problems. With `from_worktree` the new
+// side is read from the working file rather than the object store.
+pub fn check_whitespace(change: &PathChange, from_worktree: bool) -> io::Result<Vec<WhitespaceError>> {
+    if is_binary_path(&change.path)? {
+        return Ok(Vec::new());
+    }
+    let new_text = match &change.new {
+        Some(_) if from_worktree => decode_text(&std::fs::read(&change.path)?),
+        Some(entry) => blob_text(&entry.blob_hash)?,
+        None => return Ok(Vec::new()),
+    };
+    let old_text = match &change.old {
+        Some(entry) => blob_text(&entry.blob_hash)?,
+        None => Some(String::new()),
+    };
+    // Binary content has no lines to check
+    let (old_text, new_text) = match (old_text, new_text) {
+        (Some(old_text), Some(new_text)) => (old_text, new_text),
+        _ => return Ok(Vec::new()),
+    };
+
+    let old_lines: Vec<&str> = old_text.lines().collect();
+    let new_lines: Vec<&str> = new_text.lines().collect();
+    let mut errors = Vec::new();
+    let mut line_number = 0;
+    for op in diff_lines(&old_lines, &new_lines) {
+        match op {
+            LineOp::Equal(_) => line_number += 1,
+            LineOp::Delete(_) => {}
+            LineOp::Insert(line) => {
+                line_number += 1;
+                for problem in line_whitespace_problems(line) {
+                    errors.push(WhitespaceError { path: change.path.clone(), line: line_number, problem, content: line.to_string() });
+                }
+            }
+        }
+    }
+    Ok(errors)
+}
+
+// Loads a blob as text, or None when it is binary or cannot be decoded
+pub fn blob_text(blob_hash: &str) -> io::Result<Option<String>> {
+    let (_, _, data) = database::get_data(blob_hash)?;
+    Ok(decode_text(&data))
+}
+
+// Scores how alike two texts are, in percent of lines shared
+pub fn similarity(old: &str, new: &str) -> u8 {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let total = old_lines.len() + new_lines.len();
+    if total == 0 {
+        return 100;
+    }
+
+    let common = diff_lines(&old_lines, &new_lines)
+        .iter()
+        .filter(|op| matches!(op, LineOp::Equal(_)))
+        .count();
+    (common * 2 * 100 / total) as u8
+}
+
+// Pairs deleted and added paths into renames
+// Identical blobs always pair as R100; with a threshold, similar text blobs pair too
+pub fn detect_renames(changes: Vec<PathChange>, threshold: Option<u8>) -> io::Result<Vec<PathChange>> {
+    let (mut deleted, rest): (Vec<PathChange>, Vec<PathChange>) =
+        changes.into_iter().partition(|change| change.status == ChangeStatus::Deleted);
+    let (mut added, mut result): (Vec<PathChange>, Vec<PathChange>) =
+        rest.into_iter().partition(|change| change.status == ChangeStatus::Added);
+
+    // Exact renames: the same blob disappeared from one path and appeared at another
+    let mut unmatched_added = Vec::new();
+    for add in added.drain(..) {
+        let new_blob = add.new.as_ref().map(|entry| &entry.blob_hash);
+        match deleted.iter().position(|del| del.old.as_ref().map(|entry| &entry.blob_hash) == new_blob) {
+            Some(position) => {
+                let del = deleted.remove(position);
+                result.push(PathChange { path: add.path, status: ChangeStatus::Renamed(100), old: del.old, new: add.new });
+            }
+            None => unmatched_added.push(add),
+        }
+    }
+
+    // Inexact renames: pick the most similar deleted text above the threshold
+    if let Some(threshold) = threshold {
+        let mut still_unmatched = Vec::new();
+        for add in unmatched_added.drain(..) {
+            let new_text = match &add.new {
+                Some(_) if is_binary_path(&add.path)? => None,
+                Some(entry) => blob_text(&entry.blob_hash)?,
+                None => None,
+            };
+            let mut best: Option<(usize, u8)> = None;
+            if let Some(new_text) = new_text {
+                for (position, del) in deleted.iter().enumerate() {
+                    let old_text = match &del.old {
+                        Some(_) if is_binary_path(&del.path)? => None,
+                        Some(entry) => blob_text(&entry.blob_hash)?,
+                        None => None,
+                    };
+                    if let Some(old_text) = old_text {
+                        let score = similarity(&old_text, &new_text);
+                        if score >= threshold && best.is_none_or(|(_, best_score)| score > best_score) {
+                            best = Some((position, score));
+                        }
+                    }
+                }
+            }
+            match best {
+                Some((position, score)) => {
+                    let del = deleted.remove(position);
+                    result.push(PathChange { path: add.path, status: ChangeStatus::Renamed(score), old: del.old, new: add.new });
+                }
+                None => still_unmatched.push(add),
+            }
+        }
+        unmatched_added = still_unmatched;
+    }
+
+    result.extend(unmatched_added);
+    result.extend(deleted);
+    result.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(result)
+}
+
+// Resolves a commit or tree key, or an annotated tag of one, to the tree it stands for
+pub fn tree_of(key: &str) -> io::Result<String> {
+    let (object_type, _, data) = database::get_data(key)?;
+    match object_type.as_str() {
+        COMMIT => Ok(read_commit(key)?.tree),
+        TREE => Ok(key.to_string()),
+        TAG => tree_of(&Tag::deserialize(&data)?.object),
+        other => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Object {} is a {}, not a commit or tree", key, other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::commit;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::{commit_files, TestDir};
+    use std::fs;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = ["a", "b", "c"];
+        let new = ["a", "c", "d"];
+        assert_eq!(diff_lines(&old, &new), vec![
+            LineOp::Equal("a"),
+            LineOp::Delete("b"),
+            LineOp::Equal("c"),
+            LineOp::Insert("d"),
+        ]);
+        assert_eq!(diff_lines(&[], &["x"]), vec![LineOp::Insert("x")]);
+        assert_eq!(diff_lines(&["x"], &[]), vec![LineOp::Delete("x")]);
+    }
+
+    #[test]
+    fn test_diff_lines_is_shortest() {
+        // Length of the longest common subsequence, by the quadratic table
+        fn lcs(old: &[&str], new: &[&str]) -> usize {
+            let mut table = vec![vec![0; new.len() + 1]; old.len() + 1];
+            for i in 0..old.len() {
+                for j in 0..new.len() {
+                    table[i + 1][j + 1] = if old[i] == new[j] { table[i][j] + 1 } else { table[i][j + 1].max(table[i + 1][j]) };
+                }
+            }
+            table[old.len()][new.len()]
+        }
+
+        let mut seed: u32 = 7;
+        let mut next = || { seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345); (seed >> 16) % 4 };
+        let words = ["a", "b", "c", "d"];
+        for _ in 0..300 {
+            let old: Vec<&str> = (0..next() * 3).map(|_| words[next() as usize]).collect();
+            let new: Vec<&str> = (0..next() * 3).map(|_| words[next() as usize]).collect();
+            let ops = diff_lines(&old, &new);
+
+            let kept: Vec<&str> = ops.iter().filter_map(|op| match op { LineOp::Equal(l) | LineOp::Delete(l) => Some(*l), _ => None }).collect();
+            let made: Vec<&str> = ops.iter().filter_map(|op| match op { LineOp::Equal(l) | LineOp::Insert(l) => Some(*l), _ => None }).collect();
+            assert_eq!((kept, made), (old.clone(), new.clone()));
+            let equal = ops.iter().filter(|op| matches!(op, LineOp::Equal(_))).count();
+            assert_eq!(equal, lcs(&old, &new), "{:?} -> {:?}", old, new);
+        }
+
+        // A full rewrite of a long file is a plain delete-then-insert
+        let old: Vec<String> = (0..4000).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..4000).map(|i| format!("new {}", i)).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+        let ops = diff_lines(&old, &new);
+        assert_eq!(ops.len(), 8000);
+        assert!(ops[..4000].iter().all(|op| matches!(op, LineOp::Delete(_))));
+    }
+
+    #[test]
+    fn test_unified_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n11\n";
+        assert_eq!(
+            unified_hunks(old, new, 3),
+            "@@ -2,9 +2,10 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n 9\n 10\n+11\n"
+        );
+        assert_eq!(unified_hunks("", "a\nb\n", 3), "@@ -0,0 +1,2 @@\n+a\n+b\n");
+        assert_eq!(unified_hunks(old, old, 3), "");
+    }
+
+    #[test]
+    fn test_commit_patch() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_files(&[("file.txt", "one\ntwo\n")], "first");
+        let second = commit_files(&[("file.txt", "one\ntwo\nthree\n")], "second");
+
+        assert_eq!(
+            commit_patch(&first).unwrap(),
+            "diff --rit a/file.txt b/file.txt\nnew file mode 100644\n--- /dev/null\n+++ b/file.txt\n@@ -0,0 +1,2 @@\n+one\n+two\n"
+        );
+        assert_eq!(
+            commit_patch(&second).unwrap(),
+            "diff --rit a/file.txt b/file.txt\n--- a/file.txt\n+++ b/file.txt\n@@ -1,2 +1,3 @@\n one\n two\n+three\n"
+        );
+    }
+
+    #[test]
+    fn test_raw_lines() {
+        let _test_dir = TestDir::with_repo();
+        commit_files(&[("kept.txt", "kept\n"), ("changed.txt", "old\n"), ("removed.txt", "gone\n")], "first");
+        let old_hash = database::blob_key(b"old\n").unwrap();
+        let gone_hash = database::blob_key(b"gone\n").unwrap();
+
+        fs::write("changed.txt", "new\n").unwrap();
+        add_file_to_staging("changed.txt").unwrap();
+        fs::write("added.txt", "added\n").unwrap();
+        add_file_to_staging("added.txt").unwrap();
+        crate::index::remove_from_index("removed.txt").unwrap();
+        let new_hash = database::blob_key(b"new\n").unwrap();
+        let added_hash = database::blob_key(b"added\n").unwrap();
+
+        let lines: Vec<String> = index_changes().unwrap().iter().map(raw_line).collect();
+        assert_eq!(lines, vec![
+            format!(":000000 100644 {} {} A\tadded.txt", ZERO_HASH, added_hash),
+            format!(":100644 100644 {} {} M\tchanged.txt", old_hash, new_hash),
+            format!(":100644 000000 {} {} D\tremoved.txt", gone_hash, ZERO_HASH),
+        ]);
+
+        // Against the working tree: an edit and a deleted file
+        fs::write("kept.txt", "edited\n").unwrap();
+        fs::remove_file("added.txt").unwrap();
+        let lines: Vec<String> = worktree_changes().unwrap().iter().map(raw_line).collect();
+        assert_eq!(lines, vec![
+            format!(":100644 000000 {} {} D\tadded.txt", added_hash, ZERO_HASH),
+            format!(":100644 100644 {} {} M\tkept.txt", database::blob_key(b"kept\n").unwrap(), database::blob_key(b"edited\n").unwrap()),
+        ]);
+    }
+
+    #[test]
+    fn test_exact_rename() {
+        let _test_dir = TestDir::with_repo();
+        commit_files(&[("old.txt", "same content\n")], "first");
+        fs::remove_file("old.txt").unwrap();
+        crate::index::remove_from_index("old.txt").unwrap();
+        let second = commit_files(&[("new.txt", "same content\n")], "rename");
+
+        let changes = detect_renames(commit_changed_paths(&second).unwrap(), None).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "new.txt");
+        assert_eq!(changes[0].status, ChangeStatus::Renamed(100));
+        assert_eq!(changes[0].status.code(), "R100");
+        assert_eq!(changes[0].old.as_ref().unwrap().path, "old.txt");
+    }
+
+    #[test]
+    fn test_rename_with_edits() {
+        let _test_dir = TestDir::with_repo();
+        commit_files(&[("old.txt", "one\ntwo\nthree\nfour\n")], "first");
+        fs::remove_file("old.txt").unwrap();
+        crate::index::remove_from_index("old.txt").unwrap();
+        let second = commit_files(&[("new.txt", "one\ntwo\nthree\nfive\n")], "rename and edit");
+
+        // Without a threshold the edited file is a delete plus an add
+        let changes = detect_renames(commit_changed_paths(&second).unwrap(), None).unwrap();
+        assert_eq!(changes.len(), 2);
+
+        let changes = detect_renames(commit_changed_paths(&second).unwrap(), Some(DEFAULT_RENAME_THRESHOLD)).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "new.txt");
+        assert_eq!(changes[0].status, ChangeStatus::Renamed(75));
+
+        // A stricter threshold rejects the pairing
+        let changes = detect_renames(commit_changed_paths(&second).unwrap(), Some(90)).unwrap();
+        assert_eq!(changes.len(), 2);
+    }
+
+    #[test]
+    fn test_root_commit_compares_against_empty_tree() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("a.txt", "a").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+
+        let changes = commit_changed_paths(&first).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "a.txt");
+        assert_eq!(changes[0].status, ChangeStatus::Added);
+    }
+
+    #[test]
+    fn test_changed_paths_between_commits() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("a.txt", "a").unwrap();
+        fs::write("b.txt", "b").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        add_file_to_staging("b.txt").unwrap();
+        commit("first", "Tester <tester@example.com>").unwrap();
+
+        fs::write("a.txt", "a changed").unwrap();
+        fs::write("c.txt", "c").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        add_file_to_staging("c.txt").unwrap();
+        crate::index::remove_from_index("b.txt").unwrap();
+        let second = commit("second", "Tester <tester@example.com>").unwrap();
+
+        let changes: Vec<(String, ChangeStatus)> = commit_changed_paths(&second)
+            .unwrap()
+            .into_iter()
+            .map(|change| (change.path, change.status))
+            .collect();
+        assert_eq!(changes, vec![
+            ("a.txt".to_string(), ChangeStatus::Modified),
+            ("b.txt".to_string(), ChangeStatus::Deleted),
+            ("c.txt".to_string(), ChangeStatus::Added),
+        ]);
+    }
+
+    #[test]
+    fn test_check_whitespace_reports_added_lines_only() {
+        let _test_dir = TestDir::with_repo();
+        commit_files(&[("file.txt", "old trailing \nkept\n")], "first");
+
+        fs::write("file.txt", "old trailing \nkept\nnew trailing\t\n \tmixed indent\n").unwrap();
+        let changes = worktree_changes().unwrap();
+        let errors = check_whitespace(&changes[0], true).unwrap();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.problem)).collect();
+        // The pre-existing trailing space on line 1 is not reported
+        assert_eq!(found, vec![(3, "trailing whitespace"), (4, "space before tab in indent")]);
+    }
+
+    #[test]
+    fn test_binary_attribute_suppresses_line_diff() {
+        let _test_dir = TestDir::with_repo();
+        commit_files(&[("data.dat", "one\n")], "first");
+        fs::write("data.dat", "two\n").unwrap();
+        add_file_to_staging("data.dat").unwrap();
+        let changes = index_changes().unwrap();
+        assert!(path_patch(&changes[0]).unwrap().contains("+two\n"));
+
+        fs::write(".ritattributes", "*.dat binary\n").unwrap();
+        assert_eq!(
+            path_patch(&changes[0]).unwrap(),
+            "diff --rit a/data.dat b/data.dat\nBinary files a/data.dat and b/data.dat differ\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_patch_round_trip() {
+        let _test_dir = TestDir::with_repo();
+        let old: Vec<u8> = (0..300u32).map(|i| (i * 7 % 256) as u8).chain([0, 0xFF]).collect();
+        let new: Vec<u8> = old.iter().rev().copied().chain(0..40).collect();
+        fs::write("image.bin", &old).unwrap();
+        add_file_to_staging("image.bin").unwrap();
+        commit("first", "Tester <tester@example.com>").unwrap();
+        fs::write("image.bin", &new).unwrap();
+
+        // Without --binary the change is only noted
+        let changes = worktree_changes().unwrap();
+        assert!(path_patch_with(&changes[0], true, false).unwrap().ends_with("Binary files a/image.bin and b/image.bin differ\n"));
+
+        let patch = path_patch_with(&changes[0], true, true).unwrap();
+        assert!(patch.starts_with("diff --rit a/image.bin b/image.bin\nGIT binary patch\nliteral 342\n"), "{}", patch);
+        assert_eq!(apply_binary_patch(&patch, &old).unwrap(), new);
+
+        // The patch only applies to the content it was made from
+        assert!(apply_binary_patch(&patch, &new).is_err());
+        assert!(apply_binary_patch("diff --rit a/x b/x\nBinary files a/x and b/x differ\n", &old).is_err());
+
+        // Adding a file has empty old content
+        assert_eq!(apply_binary_patch(&binary_patch(b"", &new), b"").unwrap(), new);
+    }
+}
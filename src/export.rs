@@ -0,0 +1,227 @@
+// Exports a repository as a git object store, so it can be opened with git itself.
+//
+// Every object reachable from a branch or tag is re-encoded the way git stores loose
+// objects: `<type> <size>\0<payload>`, zlib-compressed and addressed by its SHA-1.
+// Trees and commits name other objects, so their payloads are rewritten with the SHA-1
+// keys of what they point to.
+
+use crate::branches;
+use crate::commit::Commit;
+use crate::compression::zlib_stored;
+use crate::constants::{BLOB, COMMIT, HEADS_DIR, REFS_DIR, TAG, TAGS_DIR, TREE};
+use crate::database;
+use crate::date;
+use crate::hash::sha1_hex;
+use crate::tag::{self, Tag};
+use crate::tree::read_tree;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ExportSummary {
+    pub objects: usize,
+    pub branches: usize,
+    pub tags: usize,
+}
+
+struct Exporter<'a> {
+    git_dir: &'a Path,
+    // rit key to git SHA-1, for every object exported so far
+    exported: HashMap<String, String>,
+}
+
+impl Exporter<'_> {
+    // Writes one git loose object and returns its SHA-1
+    fn write_object(&self, object_type: &str, payload: &[u8]) -> io::Result<String> {
+        let mut object = format!("{} {}\0", object_type, payload.len()).into_bytes();
+        object.extend_from_slice(payload);
+        let key = sha1_hex(&object);
+
+        let path = self.git_dir.join("objects").join(&key[..2]).join(&key[2..]);
+        if !path.exists() {
+            fs::create_dir_all(path.parent().expect("object paths have a parent"))?;
+            fs::write(path, zlib_stored(&object))?;
+        }
+        Ok(key)
+    }
+
+    // Exports an object and everything it refers to, returning its git SHA-1
+    fn export(&mut self, key: &str) -> io::Result<String> {
+        if let Some(sha1) = self.exported.get(key) {
+            return Ok(sha1.clone());
+        }
+
+        // The empty tree need not be stored, so it may not be in the database
+        let (object_type, data) = match database::get_data(key) {
+            Ok((object_type, _, data)) => (object_type, data),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && key == database::empty_tree_hash()? => (TREE.to_string(), Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let sha1 = match object_type.as_str() {
+            BLOB => self.write_object(BLOB, &data)?,
+            TREE => self.export_tree(key)?,
+            COMMIT => self.export_commit(&data)?,
+            TAG => self.export_tag(&data)?,
+            other => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Cannot export object {} of type '{}'", key, other)));
+            }
+        };
+        self.exported.insert(key.to_string(), sha1.clone());
+        Ok(sha1)
+    }
+
+    fn export_tree(&mut self, key: &str) -> io::Result<String> {
+        let mut entries = Vec::new();
+        for entry in read_tree(key)? {
+            let sha1 = self.export(&entry.hash)?;
+            entries.push((entry.name, entry.mode, entry.object_type == TREE, sha1));
+        }
+
+        // git sorts tree entries as if directory names ended in '/'
+        entries.sort_by(|a, b| {
+            let name = |(name, _, is_tree, _): &(String, u32, bool, String)| {
+                let mut bytes = name.as_bytes().to_vec();
+                if *is_tree {
+                    bytes.push(b'/');
+                }
+                bytes
+            };
+            name(a).cmp(&name(b))
+        });
+
+        let mut payload = Vec::new();
+        for (name, mode, is_tree, sha1) in entries {
+            let mode = if is_tree { 0o040000 } else { mode };
+            payload.extend_from_slice(format!("{:o} {}\0", mode, name).as_bytes());
+            payload.extend(hex::decode(&sha1).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?);
+        }
+        self.write_object(TREE, &payload)
+    }
+
+    fn export_commit(&mut self, data: &[u8]) -> io::Result<String> {
+        let commit = Commit::deserialize(data)?;
+        let mut payload = format!("tree {}\n", self.export(&commit.tree)?);
+        for parent in commit.parents() {
+            payload.push_str(&format!("parent {}\n", self.export(&parent)?));
+        }
+        let stamp = format!("{} {}", commit.timestamp, date::format_offset(commit.timezone));
+        payload.push_str(&format!("author {} {}\ncommitter {} {}\n\n{}\n", commit.author, stamp, commit.committer, stamp, commit.message));
+        self.write_object(COMMIT, payload.as_bytes())
+    }
+
+    fn export_tag(&mut self, data: &[u8]) -> io::Result<String> {
+        let tag = Tag::deserialize(data)?;
+        let exported = Tag { object: self.export(&tag.object)?, message: format!("{}\n", tag.message), ..tag };
+        self.write_object(TAG, &exported.serialize())
+    }
+
+    fn write_ref(&self, kind: &str, name: &str, sha1: &str) -> io::Result<()> {
+        let path = self.git_dir.join(REFS_DIR).join(kind).join(name);
+        fs::create_dir_all(path.parent().expect("ref paths have a parent"))?;
+        fs::write(path, format!("{}\n", sha1))
+    }
+}
+
+// Writes every branch and tag, and the objects they reach, into a git directory at
+// `git_dir`. A directory named `.git` becomes a normal repository's git directory;
+// any other becomes a bare repository.
+pub fn export_git(git_dir: &Path) -> io::Result<ExportSummary> {
+    if git_dir.join("objects").exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already holds a git repository", git_dir.display())));
+    }
+    for dir in ["objects", "refs/heads", "refs/tags"] {
+        fs::create_dir_all(git_dir.join(dir))?;
+    }
+
+    let mut exporter = Exporter { git_dir, exported: HashMap::new() };
+    let mut summary = ExportSummary::default();
+
+    for branch in branches::list_branches()? {
+        if let Some(commit_hash) = branches::get_commit_hash(&branch)? {
+            let sha1 = exporter.export(&commit_hash)?;
+            exporter.write_ref(HEADS_DIR, &branch, &sha1)?;
+            summary.branches += 1;
+        }
+    }
+    for name in tag::list_tags()? {
+        if let Some(key) = tag::tag_ref(&name)? {
+            let sha1 = exporter.export(&key)?;
+            exporter.write_ref(TAGS_DIR, &name, &sha1)?;
+            summary.tags += 1;
+        }
+    }
+
+    let head = branches::get_current_branch_name().unwrap_or_else(|| crate::constants::DEFAULT_BRANCH.to_string());
+    fs::write(git_dir.join("HEAD"), format!("ref: refs/heads/{}\n", head))?;
+    let bare = git_dir.file_name().is_none_or(|name| name != ".git");
+    fs::write(git_dir.join("config"), format!("[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = {}\n", bare))?;
+
+    summary.objects = exporter.exported.len();
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::commit;
+    use crate::compression::zlib_unstore;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::TestDir;
+
+    // Reads a git loose object, checking it is stored under the SHA-1 of its contents
+    fn read_git_object(git_dir: &Path, sha1: &str) -> (String, Vec<u8>) {
+        let stored = fs::read(git_dir.join("objects").join(&sha1[..2]).join(&sha1[2..])).unwrap();
+        let object = zlib_unstore(&stored).unwrap();
+        assert_eq!(sha1_hex(&object), sha1);
+        let nul = object.iter().position(|&b| b == 0).unwrap();
+        let header = String::from_utf8(object[..nul].to_vec()).unwrap();
+        let (object_type, size) = header.split_once(' ').unwrap();
+        assert_eq!(size.parse::<usize>().unwrap(), object.len() - nul - 1);
+        (object_type.to_string(), object[nul + 1..].to_vec())
+    }
+
+    #[test]
+    fn test_export_git_object_store() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("hello.txt", "hello\n").unwrap();
+        fs::create_dir_all("docs").unwrap();
+        fs::write("docs/guide.md", "guide\n").unwrap();
+        add_file_to_staging("hello.txt").unwrap();
+        add_file_to_staging("docs/guide.md").unwrap();
+        commit("first", "Tester <tester@example.com>").unwrap();
+        tag::create_annotated_tag("v1", "HEAD", "Tester <tester@example.com>", "release").unwrap();
+
+        let git_dir = Path::new("exported.git");
+        let summary = export_git(git_dir).unwrap();
+        assert_eq!(summary, ExportSummary { objects: 6, branches: 1, tags: 1 });
+        assert_eq!(fs::read_to_string(git_dir.join("HEAD")).unwrap(), "ref: refs/heads/master\n");
+
+        // The branch names a commit whose tree holds git's well-known blob for "hello\n"
+        let head = fs::read_to_string(git_dir.join("refs/heads/master")).unwrap().trim().to_string();
+        let (object_type, commit_data) = read_git_object(git_dir, &head);
+        assert_eq!(object_type, COMMIT);
+        let commit_text = String::from_utf8(commit_data).unwrap();
+        assert!(commit_text.contains("\nauthor Tester <tester@example.com> "));
+        assert!(commit_text.ends_with("\n\nfirst\n"));
+
+        let tree_sha1 = commit_text.lines().next().unwrap().strip_prefix("tree ").unwrap();
+        let (object_type, tree_data) = read_git_object(git_dir, tree_sha1);
+        assert_eq!(object_type, TREE);
+        let hello_entry = b"100644 hello.txt\0";
+        let at = tree_data.windows(hello_entry.len()).position(|w| w == hello_entry).unwrap() + hello_entry.len();
+        assert_eq!(hex::encode(&tree_data[at..at + 20]), "ce013625030ba8dba906f756967f9e9ca394464a");
+        assert!(tree_data.starts_with(b"40000 docs\0"));
+        read_git_object(git_dir, "ce013625030ba8dba906f756967f9e9ca394464a");
+
+        let tag_sha1 = fs::read_to_string(git_dir.join("refs/tags/v1")).unwrap().trim().to_string();
+        let (object_type, tag_data) = read_git_object(git_dir, &tag_sha1);
+        assert_eq!(object_type, TAG);
+        assert!(String::from_utf8(tag_data).unwrap().starts_with(&format!("object {}\ntype commit\ntag v1\n", head)));
+
+        // Exporting over an existing store is refused
+        assert!(export_git(git_dir).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+// Checks the object database for corrupt objects, references to objects that are not
+// there, and objects nothing refers to.
+
+use crate::branches;
+use crate::commit::Commit;
+use crate::constants::{lost_found_dir_path, COMMIT, TAG, TREE};
+use crate::database;
+use crate::index;
+use crate::merge;
+use crate::parallel;
+use crate::tag::{self, Tag};
+use crate::tree::deserialize_tree_entries;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DanglingObject {
+    pub hash: String,
+    pub object_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorruptObject {
+    pub hash: String,
+    pub error: String,
+}
+
+// An object something refers to that the database does not hold
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingObject {
+    pub hash: String,
+    // The object, ref or index entry that refers to it
+    pub referenced_by: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct FsckReport {
+    pub dangling: Vec<DanglingObject>,
+    pub corrupt: Vec<CorruptObject>,
+    pub missing: Vec<MissingObject>,
+}
+
+impl FsckReport {
+    // Whether anything is damaged; dangling objects are only clutter
+    pub fn has_problems(&self) -> bool {
+        !self.corrupt.is_empty() || !self.missing.is_empty()
+    }
+}
+
+// What checking a single object found: its type and the objects it references
+struct ObjectScan {
+    object_type: String,
+    references: Vec<String>,
+}
+
+fn scan_object(key: &str) -> io::Result<ObjectScan> {
+    let (object_type, _, data) = database::read_verified(key)?;
+    let mut references = Vec::new();
+    if object_type == COMMIT {
+        let commit = Commit::deserialize(&data)?;
+        references.extend(commit.parents());
+        references.push(commit.tree);
+    } else if object_type == TREE {
+        references.extend(deserialize_tree_entries(&data)?.into_iter().map(|entry| entry.hash));
+    } else if object_type == TAG {
+        references.push(Tag::deserialize(&data)?.object);
+    }
+    Ok(ObjectScan { object_type, references })
+}
+
+// The objects history is reached from, as gc sees it: branch tips, tags and staged blobs,
+// each with a name to report it by
+fn root_objects() -> io::Result<Vec<(String, String)>> {
+    let mut roots = Vec::new();
+    for branch in branches::list_branches()? {
+        if let Some(commit_hash) = branches::get_commit_hash(&branch)? {
+            roots.push((format!("branch {}", branch), commit_hash));
+        }
+    }
+    if let Some(commit_hash) = branches::get_detached_head() {
+        roots.push(("HEAD".to_string(), commit_hash));
+    }
+    if let Some(state) = merge::read_state()? {
+        roots.push(("MERGE_HEAD".to_string(), state.merge_head));
+        roots.push(("ORIG_HEAD".to_string(), state.orig_head));
+    }
+    for name in tag::list_tags()? {
+        if let Some(key) = tag::tag_ref(&name)? {
+            roots.push((format!("tag {}", name), key));
+        }
+    }
+    for entry in index::load_index()? {
+        roots.push((format!("index entry {}", entry.path), entry.blob_hash));
+    }
+    Ok(roots)
+}
+
+// Verifies every object, finds references to objects that are not stored, and finds
+// unreachable objects not referenced by any other object, checking objects in parallel
+pub fn fsck() -> io::Result<FsckReport> {
+    fsck_with_threads(parallel::default_threads())
+}
+
+pub fn fsck_with_threads(threads: usize) -> io::Result<FsckReport> {
+    let objects = database::list_objects()?;
+    let scans = parallel::map_ordered(&objects, threads, |key| scan_object(key));
+    let stored: HashSet<&String> = objects.iter().collect();
+
+    let mut report = FsckReport::default();
+    // The type and references of every object that could be read
+    let mut scanned = HashMap::new();
+    for (hash, scan) in objects.iter().zip(scans) {
+        match scan {
+            Ok(scan) => {
+                scanned.insert(hash.clone(), scan);
+            }
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                report.corrupt.push(CorruptObject { hash: hash.clone(), error: e.to_string() });
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    // The empty tree is never stored, since reading it needs no object
+    let empty_tree = database::empty_tree_hash()?;
+    let is_missing = |key: &String| !stored.contains(key) && *key != empty_tree;
+    let roots = root_objects()?;
+    for (name, key) in &roots {
+        if is_missing(key) {
+            report.missing.push(MissingObject { hash: key.clone(), referenced_by: name.clone() });
+        }
+    }
+    let mut referenced = HashSet::new();
+    for hash in &objects {
+        if let Some(scan) = scanned.get(hash) {
+            for reference in &scan.references {
+                if is_missing(reference) {
+                    report.missing.push(MissingObject { hash: reference.clone(), referenced_by: format!("{} {}", scan.object_type, hash) });
+                }
+                referenced.insert(reference);
+            }
+        }
+    }
+
+    // Walk from the roots through every reference that could be read
+    let mut reachable = HashSet::new();
+    let mut pending: Vec<&String> = roots.iter().map(|(_, key)| key).collect();
+    while let Some(key) = pending.pop() {
+        if reachable.insert(key) {
+            if let Some(scan) = scanned.get(key) {
+                pending.extend(&scan.references);
+            }
+        }
+    }
+
+    // Objects referenced from other objects are not the tip of anything
+    report.dangling = objects
+        .iter()
+        .filter(|hash| !reachable.contains(hash) && !referenced.contains(hash))
+        .filter_map(|hash| scanned.get(hash).map(|scan| DanglingObject { hash: hash.clone(), object_type: scan.object_type.clone() }))
+        .collect();
+    Ok(report)
+}
+
+// Writes dangling commits to lost-found/commit and everything else to lost-found/other
+pub fn write_lost_found(report: &FsckReport) -> io::Result<()> {
+    let commit_dir = lost_found_dir_path().join("commit");
+    let other_dir = lost_found_dir_path().join("other");
+    fs::create_dir_all(&commit_dir)?;
+    fs::create_dir_all(&other_dir)?;
+
+    for object in &report.dangling {
+        let (_, _, data) = database::get_data(&object.hash)?;
+        if object.object_type == COMMIT {
+            // The hash plus the first line of the message, so it can be recognised
+            let commit = Commit::deserialize(&data)?;
+            let summary = commit.message.lines().next().unwrap_or("");
+            fs::write(commit_dir.join(&object.hash), format!("{} {}\n", object.hash, summary))?;
+        } else {
+            // Other objects keep their contents so they can be recovered directly
+            fs::write(other_dir.join(&object.hash), data)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branches;
+    use crate::commit::commit;
+    use crate::constants::BLOB;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn test_dangling_commit_written_to_lost_found() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("file.txt", "one").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+
+        fs::write("file.txt", "two").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let second = commit("second commit", "Tester <tester@example.com>").unwrap();
+
+        // Simulate a bad reset: the branch no longer points at the second commit
+        branches::update_current_branch(&first).unwrap();
+        let dangling_blob = database::store_data(b"lost blob", BLOB).unwrap();
+
+        let report = fsck().unwrap();
+        assert!(report.dangling.contains(&DanglingObject { hash: second.clone(), object_type: COMMIT.to_string() }));
+        assert!(report.dangling.contains(&DanglingObject { hash: dangling_blob.clone(), object_type: BLOB.to_string() }));
+        // The second commit's tree is referenced by the commit, so it is not dangling itself
+        assert_eq!(report.dangling.len(), 2);
+
+        write_lost_found(&report).unwrap();
+        let summary = fs::read_to_string(lost_found_dir_path().join("commit").join(&second)).unwrap();
+        assert_eq!(summary, format!("{} second commit\n", second));
+        let blob = fs::read(lost_found_dir_path().join("other").join(&dangling_blob)).unwrap();
+        assert_eq!(blob, b"lost blob");
+
+        // A detached HEAD is a root, so the commit it names is not dangling
+        branches::detach_head(&second).unwrap();
+        let report = fsck().unwrap();
+        assert!(!report.dangling.iter().any(|object| object.hash == second));
+        branches::detach_head(&"cd".repeat(32)).unwrap();
+        assert!(fsck().unwrap().missing.contains(&MissingObject { hash: "cd".repeat(32), referenced_by: "HEAD".to_string() }));
+    }
+
+    #[test]
+    fn test_missing_objects_reported() {
+        let _test_dir = TestDir::with_repo();
+        fs::create_dir_all("dir").unwrap();
+        fs::write("dir/file.txt", "nested").unwrap();
+        add_file_to_staging("dir/file.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+        assert!(!fsck().unwrap().has_problems());
+
+        // Lose the nested blob and the subtree that lists it
+        let commit = crate::commit::read_commit(&first).unwrap();
+        let subtree = crate::tree::read_tree(&commit.tree).unwrap().remove(0);
+        let blob = crate::tree::read_tree(&subtree.hash).unwrap().remove(0);
+        database::delete_data(&subtree.hash).unwrap();
+        database::delete_data(&blob.hash).unwrap();
+
+        let report = fsck().unwrap();
+        assert!(report.has_problems());
+        assert!(report.corrupt.is_empty());
+        assert!(report.missing.contains(&MissingObject { hash: subtree.hash.clone(), referenced_by: format!("tree {}", commit.tree) }));
+        assert!(report.missing.contains(&MissingObject { hash: blob.hash.clone(), referenced_by: "index entry dir/file.txt".to_string() }));
+        assert_eq!(report.missing.len(), 2);
+        assert!(report.dangling.is_empty());
+
+        // A branch pointing at a commit that is gone is caught too
+        branches::update_current_branch(&"ab".repeat(32)).unwrap();
+        let report = fsck().unwrap();
+        assert!(report.missing.contains(&MissingObject { hash: "ab".repeat(32), referenced_by: "branch master".to_string() }));
+    }
+
+    #[test]
+    fn test_parallel_fsck_matches_serial() {
+        let _test_dir = TestDir::with_repo();
+
+        for i in 0..20 {
+            database::store_data(format!("blob {}", i).as_bytes(), BLOB).unwrap();
+        }
+        fs::write("file.txt", "committed").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        commit("first", "Tester <tester@example.com>").unwrap();
+
+        // One object whose content no longer matches its key, and one that no longer decompresses
+        let tampered = database::store_data(b"original", BLOB).unwrap();
+        let garbled = database::store_data(b"garbled", BLOB).unwrap();
+        let object_path = |key: &str| std::path::Path::new(crate::constants::DIRECTORY_PATH)
+            .join(crate::constants::OBJECTS_DIR)
+            .join(&key[..2])
+            .join(&key[2..]);
+        let replacement = crate::compression::compress_data(&database::create_data(b"tampered", BLOB).unwrap()).unwrap();
+        fs::write(object_path(&tampered), replacement).unwrap();
+        fs::write(object_path(&garbled), b"not zstd").unwrap();
+
+        let serial = fsck_with_threads(1).unwrap();
+        let parallel = fsck_with_threads(4).unwrap();
+        assert_eq!(parallel, serial);
+
+        let corrupt: Vec<&str> = serial.corrupt.iter().map(|object| object.hash.as_str()).collect();
+        let mut expected = vec![tampered.as_str(), garbled.as_str()];
+        expected.sort();
+        assert_eq!(corrupt, expected);
+        assert_eq!(serial.dangling.len(), 20);
+    }
+}
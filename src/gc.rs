@@ -0,0 +1,367 @@
+// Garbage collection: removes objects that no branch, HEAD, merge in progress, tag or the
+// index can reach.
+
+use crate::branches;
+use crate::commit::read_commit;
+use crate::config::Config;
+use crate::constants::{COMMIT, TAG, TREE};
+use crate::database;
+use crate::index;
+use crate::merge;
+use crate::parallel;
+use crate::reflog;
+use crate::tag::{self, Tag};
+use crate::tree::read_tree;
+use std::collections::HashSet;
+use std::io;
+
+// Loose object count above which `gc --auto` runs, as in git
+pub const DEFAULT_GC_AUTO: u64 = 6700;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GcStats {
+    pub objects_before: usize,
+    pub objects_after: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub pruned: Vec<String>,
+}
+
+impl GcStats {
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+
+    pub fn print(&self) {
+        println!("Objects before:  {}", self.objects_before);
+        println!("Objects after:   {}", self.objects_after);
+        println!("Bytes before:    {}", self.bytes_before);
+        println!("Bytes after:     {}", self.bytes_after);
+        println!("Bytes reclaimed: {}", self.bytes_reclaimed());
+    }
+}
+
+// Collects every object reachable from a branch tip, a detached HEAD, a merge in progress,
+// a tag or the index
+pub fn reachable_objects() -> io::Result<HashSet<String>> {
+    let mut reachable = HashSet::new();
+
+    for branch in branches::list_branches()? {
+        if let Some(commit_hash) = branches::get_commit_hash(&branch)? {
+            mark_commit(&commit_hash, &mut reachable)?;
+        }
+    }
+
+    if let Some(commit_hash) = branches::get_detached_head() {
+        mark_commit(&commit_hash, &mut reachable)?;
+    }
+    if let Some(state) = merge::read_state()? {
+        mark_commit(&state.merge_head, &mut reachable)?;
+        mark_commit(&state.orig_head, &mut reachable)?;
+    }
+
+    for name in tag::list_tags()? {
+        if let Some(key) = tag::tag_ref(&name)? {
+            mark_object(&key, &mut reachable)?;
+        }
+    }
+
+    // Staged blobs are not in any commit yet but must survive
+    for entry in index::load_index()? {
+        reachable.insert(entry.blob_hash);
+    }
+
+    Ok(reachable)
+}
+
+// Marks an object of any type, following annotated tags to what they point at
+fn mark_object(key: &str, reachable: &mut HashSet<String>) -> io::Result<()> {
+    let (object_type, _, data) = database::get_data(key)?;
+    match object_type.as_str() {
+        COMMIT => mark_commit(key, reachable),
+        TREE => mark_tree(key, reachable),
+        TAG => {
+            if reachable.insert(key.to_string()) {
+                mark_object(&Tag::deserialize(&data)?.object, reachable)?;
+            }
+            Ok(())
+        }
+        _ => {
+            reachable.insert(key.to_string());
+            Ok(())
+        }
+    }
+}
+
+fn mark_commit(commit_hash: &str, reachable: &mut HashSet<String>) -> io::Result<()> {
+    let mut pending = vec![commit_hash.to_string()];
+
+    while let Some(hash) = pending.pop() {
+        if !reachable.insert(hash.clone()) {
+            continue;
+        }
+        let commit = read_commit(&hash)?;
+        mark_tree(&commit.tree, reachable)?;
+        pending.extend(commit.parents());
+    }
+
+    Ok(())
+}
+
+fn mark_tree(tree_hash: &str, reachable: &mut HashSet<String>) -> io::Result<()> {
+    if !reachable.insert(tree_hash.to_string()) {
+        return Ok(());
+    }
+
+    for entry in read_tree(tree_hash)? {
+        if entry.object_type == TREE {
+            mark_tree(&entry.hash, reachable)?;
+        } else {
+            reachable.insert(entry.hash);
+        }
+    }
+
+    Ok(())
+}
+
+// Sums the on-disk size of every object
+fn store_size(keys: &[String]) -> io::Result<u64> {
+    parallel::map_ordered(keys, parallel::default_threads(), |key| database::object_disk_size(key))
+        .into_iter()
+        .sum()
+}
+
+// Everything gc keeps: objects reachable from branches or the index, plus commits still
+// recorded in a reflog until their entries expire
+fn live_objects() -> io::Result<HashSet<String>> {
+    let mut reachable = reachable_objects()?;
+    for commit_hash in reflog::reflog_commits()? {
+        if database::object_exists(&commit_hash) {
+            mark_commit(&commit_hash, &mut reachable)?;
+        }
+    }
+    Ok(reachable)
+}
+
+// Deletes unreachable objects and reports the effect on the store
+pub fn gc() -> io::Result<GcStats> {
+    gc_with(false)
+}
+
+// Like gc; with `dry_run` nothing is deleted, and the stats describe what gc would do
+pub fn gc_with(dry_run: bool) -> io::Result<GcStats> {
+    let objects = database::list_objects()?;
+    let mut stats = GcStats {
+        objects_before: objects.len(),
+        bytes_before: store_size(&objects)?,
+        ..GcStats::default()
+    };
+
+    // Find candidates without blocking writers, then re-check them under the index lock:
+    // an add or commit in flight may have started referring to one in the meantime
+    let reachable = live_objects()?;
+    let candidates: Vec<String> = objects.iter().filter(|key| !reachable.contains(*key)).cloned().collect();
+    if dry_run {
+        let remaining: Vec<String> = objects.into_iter().filter(|key| reachable.contains(key)).collect();
+        stats.objects_after = remaining.len();
+        stats.bytes_after = store_size(&remaining)?;
+        stats.pruned = candidates;
+        return Ok(stats);
+    }
+    let _lock = index::lock_index()?;
+    let reachable = live_objects()?;
+
+    for key in candidates {
+        if !reachable.contains(&key) {
+            database::delete_data(&key)?;
+            stats.pruned.push(key);
+        }
+    }
+
+    let remaining = database::list_objects()?;
+    stats.objects_after = remaining.len();
+    stats.bytes_after = store_size(&remaining)?;
+
+    Ok(stats)
+}
+
+// Runs gc only once the store holds more objects than `gc.auto` (0 disables it)
+pub fn gc_auto() -> io::Result<Option<GcStats>> {
+    let threshold = Config::load()?.get_int("gc.auto")?.unwrap_or(DEFAULT_GC_AUTO);
+    if threshold == 0 || (database::list_objects()?.len() as u64) <= threshold {
+        return Ok(None);
+    }
+    gc().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::commit;
+    use crate::constants::BLOB;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_gc_reports_reclaimed_bytes() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("kept.txt", "kept").unwrap();
+        add_file_to_staging("kept.txt").unwrap();
+        commit("keep", "Tester <tester@example.com>").unwrap();
+
+        // Nothing references this blob
+        let dangling = database::store_data(b"dangling content", BLOB).unwrap();
+
+        let stats = gc().unwrap();
+        assert_eq!(stats.pruned, vec![dangling.clone()]);
+        assert_eq!(stats.objects_after, stats.objects_before - 1);
+        assert!(stats.bytes_reclaimed() > 0);
+        assert!(database::get_data(&dangling).is_err());
+    }
+
+    #[test]
+    fn test_gc_dry_run_deletes_nothing() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("kept.txt", "kept").unwrap();
+        add_file_to_staging("kept.txt").unwrap();
+        commit("keep", "Tester <tester@example.com>").unwrap();
+        fs::write("staged.txt", "staged").unwrap();
+        add_file_to_staging("staged.txt").unwrap();
+        let dangling = database::store_data(b"dangling content", BLOB).unwrap();
+        let objects = database::list_objects().unwrap();
+
+        let preview = gc_with(true).unwrap();
+        assert_eq!(preview.pruned, vec![dangling.clone()]);
+        assert_eq!(preview.objects_after, preview.objects_before - 1);
+        assert!(preview.bytes_reclaimed() > 0);
+        assert_eq!(database::list_objects().unwrap(), objects);
+
+        // The real run does what the dry run described
+        assert_eq!(gc().unwrap(), preview);
+    }
+
+    #[test]
+    fn test_gc_keeps_staged_blobs() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("staged.txt", "staged").unwrap();
+        add_file_to_staging("staged.txt").unwrap();
+
+        let stats = gc().unwrap();
+        assert!(stats.pruned.is_empty());
+        assert_eq!(stats.bytes_reclaimed(), 0);
+    }
+
+    #[test]
+    fn test_gc_keeps_tagged_objects() {
+        let _test_dir = TestDir::with_repo();
+
+        // A tag on a blob nothing else refers to keeps both the tag and the blob
+        let blob = database::store_data(b"tagged content", BLOB).unwrap();
+        let tag_key = tag::create_annotated_tag("blob-tag", &blob, "Tagger <tagger@example.com>", "keep me").unwrap();
+
+        let stats = gc().unwrap();
+        assert!(stats.pruned.is_empty());
+        assert!(database::object_exists(&blob));
+        assert!(database::object_exists(&tag_key));
+    }
+
+    #[test]
+    fn test_gc_keeps_detached_head_and_merge_heads() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "one").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+
+        // A commit only a detached HEAD knows about
+        crate::checkout::checkout_commit(&first, false).unwrap();
+        fs::write("file.txt", "detached").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let detached = commit("detached", "Tester <tester@example.com>").unwrap();
+        reflog::expire_all(&[]).unwrap();
+
+        assert!(gc().unwrap().pruned.is_empty());
+        assert!(database::object_exists(&detached));
+        assert_eq!(crate::commit::log().unwrap()[0].0, detached);
+
+        // A merge in progress keeps both sides while MERGE_HEAD names them
+        crate::checkout::switch_branch("master", false, false).unwrap();
+        crate::checkout::switch_branch("other", true, false).unwrap();
+        fs::write("file.txt", "other").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let other = commit("other", "Tester <tester@example.com>").unwrap();
+        crate::checkout::switch_branch("master", false, false).unwrap();
+        fs::write("file.txt", "master").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        commit("master", "Tester <tester@example.com>").unwrap();
+        let outcome = merge::start_merge_with("other", "Tester <tester@example.com>").unwrap();
+        assert!(matches!(outcome, merge::MergeOutcome::Merged { .. }));
+        fs::remove_file(crate::constants::heads_dir_path().join("other")).unwrap();
+        reflog::expire_all(&[]).unwrap();
+
+        gc().unwrap();
+        assert!(database::object_exists(&other));
+        assert!(!database::object_exists(&detached));
+    }
+
+    #[test]
+    fn test_gc_keeps_nested_tags() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "release").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        commit("release", "Tester <tester@example.com>").unwrap();
+        let tag_key = tag::create_annotated_tag("release/v2", "HEAD", "Tagger <tagger@example.com>", "rel").unwrap();
+
+        assert_eq!(tag::list_tags().unwrap(), vec!["release/v2"]);
+        assert!(gc().unwrap().pruned.is_empty());
+        assert!(database::object_exists(&tag_key));
+        assert!(tag::verify_tag("release/v2").is_ok());
+    }
+
+    #[test]
+    fn test_gc_auto_threshold() {
+        let _test_dir = TestDir::with_repo();
+        crate::config::set_value("gc.auto", "3").unwrap();
+
+        fs::write("kept.txt", "kept").unwrap();
+        add_file_to_staging("kept.txt").unwrap();
+        let dangling = database::store_data(b"dangling", BLOB).unwrap();
+
+        // Two objects is below the threshold, so nothing happens
+        assert_eq!(database::list_objects().unwrap().len(), 2);
+        assert_eq!(gc_auto().unwrap(), None);
+        assert!(database::object_exists(&dangling));
+
+        database::store_data(b"more", BLOB).unwrap();
+        database::store_data(b"and more", BLOB).unwrap();
+        let stats = gc_auto().unwrap().expect("gc runs above the threshold");
+        assert_eq!(stats.pruned.len(), 3);
+        assert!(!database::object_exists(&dangling));
+
+        crate::config::set_value("gc.auto", "0").unwrap();
+        database::store_data(b"disabled", BLOB).unwrap();
+        assert_eq!(gc_auto().unwrap(), None);
+    }
+
+    #[test]
+    fn test_gc_keeps_blob_added_concurrently() {
+        let _test_dir = TestDir::with_repo();
+
+        // An add in flight: the blob is stored under the index lock but not yet staged
+        let lock = index::lock_index().unwrap();
+        let blob = database::store_data(b"in flight", BLOB).unwrap();
+
+        // gc sees the blob as unreachable, then waits for the lock before pruning
+        let collector = std::thread::spawn(gc);
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        index::bulk_add_to_index_locked(lock, &[("in_flight.txt", &blob)]).unwrap();
+
+        let stats = collector.join().unwrap().unwrap();
+        assert!(!stats.pruned.contains(&blob));
+        assert!(database::object_exists(&blob));
+        assert_eq!(database::get_data(&blob).unwrap().2, b"in flight");
+    }
+}
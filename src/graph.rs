@@ -0,0 +1,183 @@
+// Helpers for walking the commit graph.
+
+use crate::branches;
+use crate::commit::read_commit;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::io;
+
+// Returns the parent hashes of a commit
+pub fn parents(commit_hash: &str) -> io::Result<Vec<String>> {
+    let commit = read_commit(commit_hash)?;
+    Ok(commit.parents())
+}
+
+// Returns every commit reachable from the given commit, including itself
+pub fn ancestors(commit_hash: &str) -> io::Result<HashSet<String>> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(commit_hash.to_string());
+
+    while let Some(hash) = queue.pop_front() {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        for parent in parents(&hash)? {
+            queue.push_back(parent);
+        }
+    }
+
+    Ok(seen)
+}
+
+// Orders every commit reachable from the given commit so that no commit comes before
+// its parents; commits that become ready together are emitted oldest first
+pub fn topo_order(commit_hash: &str) -> io::Result<Vec<String>> {
+    let commits = ancestors(commit_hash)?;
+
+    // Count the unemitted parents of each commit and remember who depends on whom
+    let mut pending: HashMap<String, usize> = HashMap::new();
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    let mut timestamps: HashMap<String, u64> = HashMap::new();
+    for hash in &commits {
+        let commit = read_commit(hash)?;
+        timestamps.insert(hash.clone(), commit.timestamp);
+        let commit_parents: Vec<String> = commit.parents().into_iter().filter(|p| commits.contains(p)).collect();
+        pending.insert(hash.clone(), commit_parents.len());
+        for parent in commit_parents {
+            children.entry(parent).or_default().push(hash.clone());
+        }
+    }
+
+    let mut ready: BinaryHeap<Reverse<(u64, String)>> = pending
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(hash, _)| Reverse((timestamps[hash], hash.clone())))
+        .collect();
+
+    let mut order = Vec::with_capacity(commits.len());
+    while let Some(Reverse((_, hash))) = ready.pop() {
+        for child in children.remove(&hash).unwrap_or_default() {
+            let count = pending.get_mut(&child).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                ready.push(Reverse((timestamps[&child], child)));
+            }
+        }
+        order.push(hash);
+    }
+
+    Ok(order)
+}
+
+// Finds the closest commit reachable from both commits
+pub fn merge_base(first: &str, second: &str) -> io::Result<Option<String>> {
+    let first_ancestors = ancestors(first)?;
+
+    // Breadth-first from the second commit, so the first shared commit is the closest
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(second.to_string());
+
+    while let Some(hash) = queue.pop_front() {
+        if first_ancestors.contains(&hash) {
+            return Ok(Some(hash));
+        }
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        for parent in parents(&hash)? {
+            queue.push_back(parent);
+        }
+    }
+
+    Ok(None)
+}
+
+// Counts the commits only reachable from `local` (ahead) and only from `other` (behind)
+pub fn ahead_behind(local: &str, other: &str) -> io::Result<(usize, usize)> {
+    let local_ancestors = ancestors(local)?;
+    let other_ancestors = ancestors(other)?;
+
+    let ahead = local_ancestors.difference(&other_ancestors).count();
+    let behind = other_ancestors.difference(&local_ancestors).count();
+
+    Ok((ahead, behind))
+}
+
+// Describes how the current branch relates to another branch, like git's status header
+pub fn compare_with_branch(other_branch: &str) -> io::Result<String> {
+    let other_hash = branches::get_commit_hash(other_branch)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Branch '{}' has no commits", other_branch)))?;
+    let local_hash = match branches::get_current_branch_commit_hash()? {
+        Some(hash) => hash,
+        None => return Ok(format!("Your branch has no commits yet; '{}' is ahead.", other_branch)),
+    };
+
+    let (ahead, behind) = ahead_behind(&local_hash, &other_hash)?;
+    let plural = |count: usize| if count == 1 { "commit" } else { "commits" };
+
+    let message = match (ahead, behind) {
+        (0, 0) => format!("Your branch is up to date with '{}'.", other_branch),
+        (ahead, 0) => format!("Your branch is ahead of '{}' by {} {}.", other_branch, ahead, plural(ahead)),
+        (0, behind) => format!("Your branch is behind '{}' by {} {}.", other_branch, behind, plural(behind)),
+        (ahead, behind) => format!(
+            "Your branch and '{}' have diverged,\nand have {} and {} different commits each, respectively.",
+            other_branch, ahead, behind
+        ),
+    };
+
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branches;
+    use crate::constants::head_file_path;
+    use crate::test_utils::{commit_file, TestDir};
+    use std::fs;
+
+    #[test]
+    fn test_ahead_behind_diverged_branches() {
+        let _test_dir = TestDir::with_repo();
+
+        // Shared history on master
+        let base = commit_file("file.txt", "base", "base");
+
+        // Branch off and add two commits on the feature branch
+        branches::create_banch("feature", &base).unwrap();
+        fs::write(head_file_path(), "ref: refs/heads/feature\n").unwrap();
+        commit_file("feature.txt", "one", "feature one");
+        let feature_tip = commit_file("feature.txt", "two", "feature two");
+
+        // Back on master add one commit
+        fs::write(head_file_path(), "ref: refs/heads/master\n").unwrap();
+        let master_tip = commit_file("master.txt", "one", "master one");
+
+        assert_eq!(merge_base(&master_tip, &feature_tip).unwrap(), Some(base.clone()));
+        assert_eq!(ahead_behind(&master_tip, &feature_tip).unwrap(), (1, 2));
+        assert_eq!(ahead_behind(&feature_tip, &master_tip).unwrap(), (2, 1));
+        assert_eq!(ahead_behind(&base, &base).unwrap(), (0, 0));
+
+        // The status header for master compared against feature
+        let message = compare_with_branch("feature").unwrap();
+        assert!(message.contains("have 1 and 2 different commits"));
+    }
+
+    #[test]
+    fn test_topo_order_puts_parents_first() {
+        let _test_dir = TestDir::with_repo();
+
+        let base = commit_file("file.txt", "base", "base");
+        branches::create_banch("feature", &base).unwrap();
+        fs::write(head_file_path(), "ref: refs/heads/feature\n").unwrap();
+        let feature_one = commit_file("feature.txt", "one", "feature one");
+        let feature_two = commit_file("feature.txt", "two", "feature two");
+        fs::write(head_file_path(), "ref: refs/heads/master\n").unwrap();
+        let master_one = commit_file("master.txt", "one", "master one");
+
+        assert_eq!(topo_order(&feature_two).unwrap(), vec![base.clone(), feature_one, feature_two]);
+        assert_eq!(topo_order(&master_one).unwrap(), vec![base, master_one]);
+    }
+}
@@ -1,16 +1,243 @@
+use crate::config::Config;
 use sha2::{Digest, Sha256};
 use std::io;
 
+// The config key recording which hash a repository addresses its objects with
+pub const OBJECT_FORMAT_KEY: &str = "extensions.objectformat";
 
-// hash the file, then returns the key of the file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgo {
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Blake3 => "blake3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> io::Result<Self> {
+        match name.to_lowercase().as_str() {
+            "sha256" => Ok(HashAlgo::Sha256),
+            "blake3" => Ok(HashAlgo::Blake3),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown object format '{}': expected sha256 or blake3", name))),
+        }
+    }
+
+    // The algorithm the current repository was created with; sha256 unless `rit init` chose otherwise
+    pub fn current() -> io::Result<Self> {
+        match Config::load()?.get(OBJECT_FORMAT_KEY) {
+            Some(name) => HashAlgo::from_name(name),
+            None => Ok(HashAlgo::Sha256),
+        }
+    }
+
+    // Bytes in a digest, which is how trees store the keys of their entries
+    pub fn digest_len(self) -> usize {
+        match self {
+            HashAlgo::Sha256 => 32,
+            HashAlgo::Blake3 => 32,
+        }
+    }
+
+    pub fn hash_hex(self, buffer: &[u8]) -> String {
+        match self {
+            HashAlgo::Sha256 => format!("{:x}", Sha256::digest(buffer)),
+            HashAlgo::Blake3 => hex::encode(blake3(buffer)),
+        }
+    }
+}
+
+// hash the file with the repository's algorithm, then returns the key of the file
 pub fn hash_data(buffer: &[u8]) -> io::Result<String> {
-    // Create a new SHA-256 hasher
-    let mut hasher = Sha256::new();
+    Ok(HashAlgo::current()?.hash_hex(buffer))
+}
+
+// BLAKE3 in its default hashing mode, following the reference implementation: the input
+// is split into 1 KiB chunks, each compressed block by block, and the chunks' chaining
+// values are merged pairwise up a binary tree whose root gives the 32-byte digest.
+const BLAKE3_IV: [u32; 8] = [0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19];
+const BLAKE3_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+const BLAKE3_BLOCK_LEN: usize = 64;
+const BLAKE3_CHUNK_LEN: usize = 1024;
+const CHUNK_START: u32 = 1;
+const CHUNK_END: u32 = 2;
+const PARENT: u32 = 4;
+const ROOT: u32 = 8;
+
+fn blake3_g(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, mx: u32, my: u32) {
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(mx);
+    state[d] = (state[d] ^ state[a]).rotate_right(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(12);
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(my);
+    state[d] = (state[d] ^ state[a]).rotate_right(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(7);
+}
+
+fn blake3_compress(cv: &[u32; 8], block: &[u32; 16], counter: u64, block_len: u32, flags: u32) -> [u32; 16] {
+    let mut state = [
+        cv[0], cv[1], cv[2], cv[3], cv[4], cv[5], cv[6], cv[7],
+        BLAKE3_IV[0], BLAKE3_IV[1], BLAKE3_IV[2], BLAKE3_IV[3],
+        counter as u32, (counter >> 32) as u32, block_len, flags,
+    ];
+    let mut m = *block;
+    for round in 0..7 {
+        blake3_g(&mut state, 0, 4, 8, 12, m[0], m[1]);
+        blake3_g(&mut state, 1, 5, 9, 13, m[2], m[3]);
+        blake3_g(&mut state, 2, 6, 10, 14, m[4], m[5]);
+        blake3_g(&mut state, 3, 7, 11, 15, m[6], m[7]);
+        blake3_g(&mut state, 0, 5, 10, 15, m[8], m[9]);
+        blake3_g(&mut state, 1, 6, 11, 12, m[10], m[11]);
+        blake3_g(&mut state, 2, 7, 8, 13, m[12], m[13]);
+        blake3_g(&mut state, 3, 4, 9, 14, m[14], m[15]);
+        if round < 6 {
+            m = BLAKE3_PERMUTATION.map(|i| m[i]);
+        }
+    }
+    for i in 0..8 {
+        state[i] ^= state[i + 8];
+        state[i + 8] ^= cv[i];
+    }
+    state
+}
+
+// The last compression of a chunk or parent node, kept unevaluated until we know
+// whether it is the root
+struct Blake3Output {
+    cv: [u32; 8],
+    block: [u32; 16],
+    counter: u64,
+    block_len: u32,
+    flags: u32,
+}
+
+impl Blake3Output {
+    fn chaining_value(&self) -> [u32; 8] {
+        let out = blake3_compress(&self.cv, &self.block, self.counter, self.block_len, self.flags);
+        std::array::from_fn(|i| out[i])
+    }
+}
+
+fn blake3_block_words(bytes: &[u8]) -> [u32; 16] {
+    let mut block = [0u8; BLAKE3_BLOCK_LEN];
+    block[..bytes.len()].copy_from_slice(bytes);
+    std::array::from_fn(|i| u32::from_le_bytes([block[4 * i], block[4 * i + 1], block[4 * i + 2], block[4 * i + 3]]))
+}
+
+fn blake3_chunk(chunk: &[u8], counter: u64) -> Blake3Output {
+    let mut cv = BLAKE3_IV;
+    let mut blocks: Vec<&[u8]> = chunk.chunks(BLAKE3_BLOCK_LEN).collect();
+    let last = blocks.pop().unwrap_or(&[]);
+    let mut flags = CHUNK_START;
+    for block in blocks {
+        let out = blake3_compress(&cv, &blake3_block_words(block), counter, BLAKE3_BLOCK_LEN as u32, flags);
+        cv = std::array::from_fn(|i| out[i]);
+        flags = 0;
+    }
+    Blake3Output { cv, block: blake3_block_words(last), counter, block_len: last.len() as u32, flags: flags | CHUNK_END }
+}
+
+// The output of the subtree over `chunks`, the first of which is chunk number `counter`.
+// The left subtree always takes the largest power of two chunks that leaves some for the right.
+fn blake3_subtree(chunks: &[&[u8]], counter: u64) -> Blake3Output {
+    if chunks.len() == 1 {
+        return blake3_chunk(chunks[0], counter);
+    }
+    let left_len = 1 << (usize::BITS - 1 - (chunks.len() - 1).leading_zeros());
+    let left = blake3_subtree(&chunks[..left_len], counter).chaining_value();
+    let right = blake3_subtree(&chunks[left_len..], counter + left_len as u64).chaining_value();
+    let mut block = [0u32; 16];
+    block[..8].copy_from_slice(&left);
+    block[8..].copy_from_slice(&right);
+    Blake3Output { cv: BLAKE3_IV, block, counter: 0, block_len: BLAKE3_BLOCK_LEN as u32, flags: PARENT }
+}
+
+pub fn blake3(data: &[u8]) -> [u8; 32] {
+    let mut chunks: Vec<&[u8]> = data.chunks(BLAKE3_CHUNK_LEN).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    let root = blake3_subtree(&chunks, 0);
+    let out = blake3_compress(&root.cv, &root.block, root.counter, root.block_len, root.flags | ROOT);
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_mut(4).zip(out) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+// SHA-1, which git uses to address objects; only needed to export repositories to git
+pub fn sha1_hex(data: &[u8]) -> String {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // Pad with a 1 bit, zeros, then the message length in bits, to a multiple of 64 bytes
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    state.iter().map(|word| format!("{:08x}", word)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Update the hasher with the file contents
-    hasher.update(buffer);
+    #[test]
+    fn test_sha1_known_digests() {
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(sha1_hex(&[b'a'; 1000]), "291e9a6c66994949b57ba5e650361e98fc36b1ba");
+        // git's key for a blob holding "hello\n"
+        assert_eq!(sha1_hex(b"blob 6\0hello\n"), "ce013625030ba8dba906f756967f9e9ca394464a");
+    }
 
-    // Finalize the hash and convert it to a hexadecimal string
-    let result = hasher.finalize();
-    Ok(format!("{:x}", result))
-}
\ No newline at end of file
+    #[test]
+    fn test_blake3_known_digests() {
+        // Inputs from the BLAKE3 test vectors: the bytes 0, 1, ..., 250, 0, 1, ... repeated
+        let input = |len: usize| -> Vec<u8> { (0..len).map(|i| (i % 251) as u8).collect() };
+        assert_eq!(hex::encode(blake3(b"")), "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+        assert_eq!(hex::encode(blake3(b"abc")), "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85");
+        assert_eq!(hex::encode(blake3(&input(1))), "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213");
+        assert_eq!(hex::encode(blake3(&input(1024))), "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7");
+        assert_eq!(hex::encode(blake3(&input(1025))), "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444");
+        assert_eq!(hex::encode(blake3(&input(2048))), "e776b6028c7cd22a4d0ba182a8bf62205d2ef576467e838ed6f2529b85fba24a");
+    }
+}
@@ -0,0 +1,162 @@
+// Hook scripts live in .rit/hooks and run at fixed points, like git's hooks.
+
+use crate::constants::hooks_dir_path;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookOutcome {
+    // The hook is absent or exited successfully
+    Allow,
+    // The hook exited non-zero; holds the exit code when there is one
+    Abort(Option<i32>),
+}
+
+const PRE_COMMIT_SAMPLE: &str = "#!/bin/sh
+#
+# An example hook script to verify what is about to be committed.
+# Exit with a non-zero status to stop the commit.
+#
+# To enable this hook, rename this file to \"pre-commit\" and make it executable.
+
+exit 0
+";
+
+const COMMIT_MSG_SAMPLE: &str = "#!/bin/sh
+#
+# An example hook script to check the commit message.
+# It is called with one argument, the name of the file holding the message.
+# Exit with a non-zero status to stop the commit; the script may edit the file.
+#
+# To enable this hook, rename this file to \"commit-msg\" and make it executable.
+
+test -s \"$1\" || {
+    echo >&2 \"Aborting commit due to empty commit message.\"
+    exit 1
+}
+";
+
+// Creates the hooks directory with disabled sample hooks
+pub fn init_hooks() -> io::Result<()> {
+    let hooks_dir = hooks_dir_path();
+    fs::create_dir_all(&hooks_dir)?;
+
+    fs::write(hooks_dir.join("pre-commit.sample"), PRE_COMMIT_SAMPLE)?;
+    fs::write(hooks_dir.join("commit-msg.sample"), COMMIT_MSG_SAMPLE)?;
+
+    Ok(())
+}
+
+// Runs .rit/hooks/<name> with the given arguments and optional stdin
+// A missing or non-executable hook is a no-op that allows the operation
+pub fn run_hook(name: &str, args: &[&str], stdin: Option<&[u8]>) -> io::Result<HookOutcome> {
+    let hook_path = hooks_dir_path().join(name);
+    if !is_executable(&hook_path) {
+        return Ok(HookOutcome::Allow);
+    }
+
+    let mut command = Command::new(&hook_path);
+    command.args(args);
+    command.stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() });
+
+    let mut child = command.spawn()?;
+    if let (Some(input), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        // A hook that exits without reading its input is not an error
+        match child_stdin.write_all(input) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+    }
+    let status = child.wait()?;
+
+    if status.success() {
+        Ok(HookOutcome::Allow)
+    } else {
+        Ok(HookOutcome::Abort(status.code()))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    // Hooks are shell scripts; skip where there is no shell
+    #[cfg(unix)]
+    fn install_hook(name: &str, script: &str) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+        if !Path::new("/bin/sh").exists() {
+            return false;
+        }
+        let hook_path = hooks_dir_path().join(name);
+        fs::write(&hook_path, script).unwrap();
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755)).unwrap();
+        true
+    }
+
+    #[test]
+    fn test_init_creates_sample_hooks() {
+        let _test_dir = TestDir::with_repo();
+        assert!(hooks_dir_path().join("pre-commit.sample").exists());
+        assert!(hooks_dir_path().join("commit-msg.sample").exists());
+    }
+
+    #[test]
+    fn test_missing_hook_allows() {
+        let _test_dir = TestDir::with_repo();
+        assert_eq!(run_hook("pre-commit", &[], None).unwrap(), HookOutcome::Allow);
+    }
+
+    #[test]
+    fn test_sample_hook_is_not_run() {
+        let _test_dir = TestDir::with_repo();
+        fs::rename(hooks_dir_path().join("commit-msg.sample"), hooks_dir_path().join("commit-msg")).unwrap();
+
+        // Samples are written without the executable bit
+        assert_eq!(run_hook("commit-msg", &["missing-file"], None).unwrap(), HookOutcome::Allow);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_passing_hook_allows() {
+        let _test_dir = TestDir::with_repo();
+        if !install_hook("pre-commit", "#!/bin/sh\nexit 0\n") {
+            return;
+        }
+        assert_eq!(run_hook("pre-commit", &[], None).unwrap(), HookOutcome::Allow);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failing_hook_aborts() {
+        let _test_dir = TestDir::with_repo();
+        if !install_hook("pre-commit", "#!/bin/sh\nexit 3\n") {
+            return;
+        }
+        assert_eq!(run_hook("pre-commit", &[], None).unwrap(), HookOutcome::Abort(Some(3)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hook_receives_args_and_stdin() {
+        let _test_dir = TestDir::with_repo();
+        if !install_hook("check", "#!/bin/sh\nread line\ntest \"$line\" = \"$1\"\n") {
+            return;
+        }
+        assert_eq!(run_hook("check", &["expected"], Some(b"expected\n")).unwrap(), HookOutcome::Allow);
+        assert_eq!(run_hook("check", &["expected"], Some(b"other\n")).unwrap(), HookOutcome::Abort(Some(1)));
+    }
+}
@@ -0,0 +1,209 @@
+// Ignore rules in the style of .gitignore.
+// Patterns come from `.rit/info/exclude` and then `.ritignore`; later patterns win,
+// and a leading `!` re-includes a path an earlier pattern ignored.
+
+use crate::constants::{info_exclude_path, RITIGNORE_FILE};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IgnorePattern {
+    // The pattern exactly as written in its file
+    pub pattern: String,
+    pub source: PathBuf,
+    pub line: usize,
+    pub negated: bool,
+    glob: String,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    // Parses one line of an ignore file; blank lines and comments yield nothing
+    fn parse(line: &str, source: &Path, line_number: usize) -> Option<Self> {
+        let text = line.trim_end();
+        if text.is_empty() || text.starts_with('#') {
+            return None;
+        }
+
+        let (negated, rest) = match text.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('\\').unwrap_or(text)),
+        };
+        let (dir_only, rest) = match rest.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        // A slash anywhere but the end ties the pattern to the repository root
+        let anchored = rest.contains('/');
+        let glob = rest.strip_prefix('/').unwrap_or(rest).to_string();
+        if glob.is_empty() {
+            return None;
+        }
+
+        Some(IgnorePattern {
+            pattern: text.to_string(),
+            source: source.to_path_buf(),
+            line: line_number,
+            negated,
+            glob,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            glob_match(&self.glob, path)
+        } else {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            glob_match(&self.glob, name)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    // Reads the repository's exclude file and `.ritignore`; missing files contribute nothing
+    pub fn load() -> io::Result<Self> {
+        let mut rules = IgnoreRules::default();
+        for source in [info_exclude_path(), PathBuf::from(RITIGNORE_FILE)] {
+            match fs::read_to_string(&source) {
+                Ok(contents) => rules.add_patterns(&contents, &source),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn add_patterns(&mut self, contents: &str, source: &Path) {
+        let parsed = contents
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| IgnorePattern::parse(line, source, index + 1));
+        self.patterns.extend(parsed);
+    }
+
+    // Returns the pattern that decides the path, if any. A matching negated pattern
+    // means the path is explicitly not ignored. Paths inside an ignored directory
+    // are ignored by that directory's pattern.
+    pub fn matching(&self, path: &str, is_dir: bool) -> Option<&IgnorePattern> {
+        let path = path.strip_prefix("./").unwrap_or(path).trim_end_matches('/');
+
+        let mut prefix_end = 0;
+        while let Some(offset) = path[prefix_end..].find('/') {
+            prefix_end += offset;
+            if let Some(pattern) = self.last_match(&path[..prefix_end], true) {
+                if !pattern.negated {
+                    return Some(pattern);
+                }
+            }
+            prefix_end += 1;
+        }
+
+        self.last_match(path, is_dir)
+    }
+
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        matches!(self.matching(path, is_dir), Some(pattern) if !pattern.negated)
+    }
+
+    fn last_match(&self, path: &str, is_dir: bool) -> Option<&IgnorePattern> {
+        self.patterns.iter().rev().find(|pattern| pattern.matches(path, is_dir))
+    }
+}
+
+// Matches a path against a glob: `*` and `?` stay within one path component,
+// `**` spans directories and `\` escapes the next character
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_chars(&pattern, &text)
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` may also match no directories at all
+            if let ['/', after @ ..] = rest {
+                if glob_match_chars(after, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|start| glob_match_chars(rest, &text[start..]))
+        }
+        ['*', rest @ ..] => {
+            for start in 0..=text.len() {
+                if glob_match_chars(rest, &text[start..]) {
+                    return true;
+                }
+                if start < text.len() && text[start] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => matches!(text, [c, ..] if *c != '/') && glob_match_chars(rest, &text[1..]),
+        ['\\', literal, rest @ ..] => text.first() == Some(literal) && glob_match_chars(rest, &text[1..]),
+        [literal, rest @ ..] => text.first() == Some(literal) && glob_match_chars(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    fn rules(contents: &str) -> IgnoreRules {
+        let mut rules = IgnoreRules::default();
+        rules.add_patterns(contents, Path::new(RITIGNORE_FILE));
+        rules
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.log", "debug.log"));
+        assert!(!glob_match("*.log", "logs/debug.txt"));
+        assert!(!glob_match("*.log", "dir/debug.log"));
+        assert!(glob_match("doc/**/*.txt", "doc/a/b/notes.txt"));
+        assert!(glob_match("**/build", "build"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file/.txt"));
+    }
+
+    #[test]
+    fn test_patterns() {
+        let rules = rules("# build output\ntarget/\n*.log\n!keep.log\n/root.txt\n");
+        assert!(rules.is_ignored("target", true));
+        assert!(rules.is_ignored("target/debug/rit", false));
+        assert!(!rules.is_ignored("target", false));
+        assert!(rules.is_ignored("src/debug.log", false));
+        assert!(!rules.is_ignored("src/keep.log", false));
+        assert!(rules.is_ignored("root.txt", false));
+        assert!(!rules.is_ignored("src/root.txt", false));
+        assert!(!rules.is_ignored("src/main.rs", false));
+    }
+
+    #[test]
+    fn test_matching_reports_pattern_and_source() {
+        let _test_dir = TestDir::with_repo();
+        fs::write(RITIGNORE_FILE, "*.tmp\n\n*.log\n").unwrap();
+
+        let rules = IgnoreRules::load().unwrap();
+        let pattern = rules.matching("logs/app.log", false).unwrap();
+        assert_eq!(pattern.pattern, "*.log");
+        assert_eq!(pattern.source, PathBuf::from(RITIGNORE_FILE));
+        assert_eq!(pattern.line, 3);
+        assert!(rules.matching("src/main.rs", false).is_none());
+    }
+}
@@ -3,29 +3,43 @@
 // The index file stores the file name, the hash value of the file, and the file path.
 
 
-use crate::constants::{DIRECTORY_PATH, INDEX_FILE,SOURCE_PATH};
-use crate::database::store_temporary;
-use crate::tree::{self, convert_tree_entry_to_hashmap};
-use std::collections::HashMap;
+use crate::constants::{DIRECTORY_PATH, INDEX_FILE, MODE_EXECUTABLE, MODE_FILE, MODE_SYMLINK};
+use crate::database::hash_working_file;
+use crate::tree::convert_tree_entry_to_hashmap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File};
-use std::io::{self, Read, Write, BufReader, BufWriter};
-use std::ops::Index;
+use std::io::{self, Read, Write, BufReader};
 use std::path::{Path, PathBuf};
-use crate::hash::{hash_data};
 use std::fs;
-use tempdir::TempDir;
-use crate::branches::{self, get_current_branch_commit_hash, get_current_tree_from_commit_hash};
-use crate::staging;
+use crate::branches::{self, get_current_tree_from_commit_hash};
+use crate::graph;
+use crate::ignore::IgnoreRules;
+use crate::merge;
+use crate::lock::LockFile;
+use crate::database::ZERO_HASH;
 use colored::Colorize;
 
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IndexEntry {
     pub mode: u32,
     pub blob_hash: String,
     pub path: String
 }
 
+// Entries order by path, the order the index is written in; mode and hash only break ties
+impl Ord for IndexEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.path, self.mode, &self.blob_hash).cmp(&(&other.path, other.mode, &other.blob_hash))
+    }
+}
+
+impl PartialOrd for IndexEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 pub fn get_index_path() -> PathBuf {
     Path::new(DIRECTORY_PATH).join(INDEX_FILE)
 }
@@ -41,28 +55,49 @@ pub fn create_index() -> io::Result<()> {
 
 // This function will add the file to the index file.
 pub fn add_to_index(file_path: &str, blob_hash: &str) -> io::Result<()> {
-    // TODO trim off the ./ and validate the file path to ensure is in your repo
-    let index = load_index()?;
-
-    let mut index_map: HashMap<String, IndexEntry> = index.into_iter()
-        .map(|entry| (entry.path.clone(), entry))
-        .collect();
+    bulk_add_to_index(&[(file_path, blob_hash)])
+}
 
-    let entry = index_map.entry(file_path.to_string()).or_insert(IndexEntry {
-        mode: 0o100644,
-        blob_hash: blob_hash.to_string(),
-        path: file_path.to_string(),
-    });
-    entry.blob_hash = blob_hash.to_string();
+// Takes the index lock. It is also the repository's write lock: gc holds it while pruning,
+// so objects stored while holding it survive until the index or a branch refers to them.
+pub fn lock_index() -> io::Result<LockFile> {
+    LockFile::acquire(&get_index_path())
+}
 
-    let index: Vec<IndexEntry> = index_map.into_values().collect();
-    save_index(&index)?;
+// Adds several files at once. The index lock is held across the read-modify-write,
+// so concurrent adds compose instead of the last save winning.
+pub fn bulk_add_to_index(entries: &[(&str, &str)]) -> io::Result<()> {
+    bulk_add_to_index_locked(lock_index()?, entries)
+}
 
-    Ok(())
+// Turns a path given on the command line into the form the index stores: relative to the
+// repository root, `/`-separated, without `.` or `..` parts. Absolute paths must lie inside
+// the repository and relative ones must not climb out of it. The root itself is "".
+pub fn normalize_path(path: &str) -> io::Result<String> {
+    let escapes = || io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is outside the repository", path));
+
+    let relative = if Path::new(path).is_absolute() {
+        let root = std::env::current_dir()?;
+        Path::new(path).strip_prefix(&root).map_err(|_| escapes())?.to_string_lossy().into_owned()
+    } else {
+        path.to_string()
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in relative.split(['/', std::path::MAIN_SEPARATOR]) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop().ok_or_else(escapes)?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Ok(parts.join("/"))
 }
 
-pub fn bulk_add_to_index(entries: &[(&str, &str)]) -> io::Result<()> {
-    // TODO trim off the ./ and validate the file path to ensure is in your repo
+// Adds several files while already holding the index lock, releasing it afterwards
+pub fn bulk_add_to_index_locked(lock: LockFile, entries: &[(&str, &str)]) -> io::Result<()> {
     let index = load_index()?;
 
     let mut index_map: HashMap<String, IndexEntry> = index.into_iter()
@@ -70,20 +105,51 @@ pub fn bulk_add_to_index(entries: &[(&str, &str)]) -> io::Result<()> {
         .collect();
 
     for (file_path, blob_hash) in entries {
-        let entry = index_map.entry(file_path.to_string()).or_insert(IndexEntry {
-            mode: 0o100644,
+        // `./a.txt` and `a.txt` must be one entry
+        let file_path = normalize_path(file_path)?;
+        if file_path.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot add the repository root as a file"));
+        }
+        let entry = index_map.entry(file_path.clone()).or_insert(IndexEntry {
+            mode: MODE_FILE,
             blob_hash: blob_hash.to_string(),
-            path: file_path.to_string(),
+            path: file_path.clone(),
         });
         entry.blob_hash = blob_hash.to_string();
+        // Take the mode from the working file when there is one
+        if let Ok(mode) = working_file_mode(Path::new(&file_path)) {
+            entry.mode = mode;
+        }
     }
 
     let index: Vec<IndexEntry> = index_map.into_values().collect();
-    save_index(&index)?;
+    lock.commit(&serialize_index(&index)?)
+}
 
-    Ok(())
+// Stages complete entries, keeping their modes, replacing any entry with the same path
+pub fn add_index_entries(entries: &[IndexEntry]) -> io::Result<()> {
+    add_index_entries_locked(lock_index()?, entries)
+}
+
+// Stages complete entries while already holding the index lock, releasing it afterwards
+pub fn add_index_entries_locked(lock: LockFile, entries: &[IndexEntry]) -> io::Result<()> {
+    let mut index_map: HashMap<String, IndexEntry> = load_index()?.into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect();
+
+    for entry in entries {
+        index_map.insert(entry.path.clone(), entry.clone());
+    }
+
+    let index: Vec<IndexEntry> = index_map.into_values().collect();
+    lock.commit(&serialize_index(&index)?)
 }
 
+// The index file starts with this signature and a format version byte, followed by the
+// entries. Version 1 entries hold a 4-byte mode, a 1-byte hash length, the hash, a 2-byte
+// path length and the path, all big-endian.
+const INDEX_SIGNATURE: &[u8; 4] = b"RIDX";
+const INDEX_VERSION: u8 = 1;
 
 // This function will read the index file and return the entries.
 pub fn load_index() -> io::Result<Vec<IndexEntry>> {
@@ -97,38 +163,67 @@ pub fn load_index() -> io::Result<Vec<IndexEntry>> {
     let file = File::open(&index_path)?;
     let mut reader = BufReader::new(file);
 
-    while let Ok(entry) = read_index_entry(&mut reader) {
-        entries.push(entry);
+    // An empty file is an empty index, as `create_index` and `clear_index` leave it
+    let mut header = Vec::new();
+    (&mut reader).take(INDEX_SIGNATURE.len() as u64 + 1).read_to_end(&mut header)?;
+    if header.is_empty() {
+        return Ok(entries);
+    }
+    let unsupported = |reason: String| io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported index format in {}: {}", index_path.display(), reason));
+    if !header.starts_with(INDEX_SIGNATURE) || header.len() <= INDEX_SIGNATURE.len() {
+        return Err(unsupported("missing the index signature; it may have been written by an older version of rit".to_string()));
+    }
+    let version = header[INDEX_SIGNATURE.len()];
+    if version != INDEX_VERSION {
+        return Err(unsupported(format!("version {} (expected {})", version, INDEX_VERSION)));
+    }
+
+    // Running out of bytes between entries ends the index; anything else is corruption
+    loop {
+        match read_index_entry(&mut reader) {
+            Ok(entry) => entries.push(entry),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(io::Error::new(e.kind(), format!("Corrupt index {}: {}", index_path.display(), e))),
+        }
     }
 
-    // println!("{:?}", entries);
     Ok(entries)
 }
 
-// This function will save the index entries to the index file.
-pub fn save_index(entries: &Vec<IndexEntry>) -> io::Result<()> {
-    let index_path = get_index_path();
-    let file = File::create(&index_path)?;
-    let mut writer = BufWriter::new(file);
+// This function will save the index entries to the index file, taking the index lock.
+pub fn save_index(entries: &[IndexEntry]) -> io::Result<()> {
+    save_index_locked(lock_index()?, entries)
+}
 
-    for entry in entries {
-        write_index_entry(&mut writer, entry)?;
-    }
+// Replaces the whole index while already holding the index lock, releasing it afterwards
+pub fn save_index_locked(lock: LockFile, entries: &[IndexEntry]) -> io::Result<()> {
+    lock.commit(&serialize_index(entries)?)
+}
 
-    writer.flush()?;
-    Ok(())
+// This function will encode the index entries as the bytes of the index file.
+// Entries are written sorted by path so the same index always has the same bytes.
+fn serialize_index(entries: &[IndexEntry]) -> io::Result<Vec<u8>> {
+    let mut sorted: Vec<&IndexEntry> = entries.iter().collect();
+    sorted.sort();
+    let mut buffer = INDEX_SIGNATURE.to_vec();
+    buffer.push(INDEX_VERSION);
+    for entry in sorted {
+        write_index_entry(&mut buffer, entry)?;
+    }
+    Ok(buffer)
 }
 
 // This function will remove the file from the index file.
 pub fn remove_from_index(file_path: &str) -> io::Result<()> {
+    let lock = lock_index()?;
     let mut entries = load_index()?;
     entries.retain(|entry| entry.path != file_path);
-    save_index(&entries)?;
-    Ok(())
+    save_index_locked(lock, &entries)
 }
 
 // This function will update the index file with the new hash value.
 pub fn update_index(file_path: &str, blob_hash: &str) -> io::Result<()> {
+    let lock = lock_index()?;
     let mut entries = load_index()?;
     for entry in &mut entries {
         if entry.path == file_path {
@@ -136,8 +231,7 @@ pub fn update_index(file_path: &str, blob_hash: &str) -> io::Result<()> {
             break;
         }
     }
-    save_index(&entries)?;
-    Ok(())
+    save_index_locked(lock, &entries)
 }
 
 // This function will clear the index file.
@@ -151,34 +245,53 @@ pub fn clear_index() -> io::Result<()> {
 }
 
 // This function will read an index entry from the index file.
+// Fails with UnexpectedEof only when the reader ends cleanly before an entry; an entry cut
+// short partway through is InvalidData.
 fn read_index_entry<R: Read>(reader: &mut R) -> io::Result<IndexEntry> {
     use std::io::ErrorKind;
 
-    // Read the mode
+    // Read the mode, telling a clean end from one inside it
     let mut mode_bytes = [0u8; 4];
-    if reader.read_exact(&mut mode_bytes).is_err() {
-        return Err(io::Error::new(ErrorKind::UnexpectedEof, "No more entries"));
+    let mut read = 0;
+    while read < mode_bytes.len() {
+        match reader.read(&mut mode_bytes[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    match read {
+        0 => return Err(io::Error::new(ErrorKind::UnexpectedEof, "No more entries")),
+        4 => {}
+        _ => return Err(io::Error::new(ErrorKind::InvalidData, "entry is truncated")),
     }
     let mode = u32::from_be_bytes(mode_bytes);
 
+    // The rest of the entry must be present in full
+    let truncated = |e: io::Error| match e.kind() {
+        ErrorKind::UnexpectedEof => io::Error::new(ErrorKind::InvalidData, "entry is truncated"),
+        _ => e,
+    };
+
     // Read the hash length
     let mut hash_len_bytes = [0u8; 1];
-    reader.read_exact(&mut hash_len_bytes)?;
+    reader.read_exact(&mut hash_len_bytes).map_err(truncated)?;
     let hash_len = u8::from_be_bytes(hash_len_bytes) as usize;
 
     // Read the hash
     let mut hash_bytes = vec![0u8; hash_len];
-    reader.read_exact(&mut hash_bytes)?;
+    reader.read_exact(&mut hash_bytes).map_err(truncated)?;
     let blob_hash = String::from_utf8(hash_bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
 
     // Read the path length
     let mut path_len_bytes = [0u8; 2];
-    reader.read_exact(&mut path_len_bytes)?;
+    reader.read_exact(&mut path_len_bytes).map_err(truncated)?;
     let path_len = u16::from_be_bytes(path_len_bytes) as usize;
 
     // Read the path
     let mut path_bytes = vec![0u8; path_len];
-    reader.read_exact(&mut path_bytes)?;
+    reader.read_exact(&mut path_bytes).map_err(truncated)?;
     let path = String::from_utf8(path_bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
 
     Ok(IndexEntry {
@@ -192,73 +305,179 @@ fn read_index_entry<R: Read>(reader: &mut R) -> io::Result<IndexEntry> {
 fn write_index_entry<W: Write>(writer: &mut W, entry: &IndexEntry) -> io::Result<()> {
     writer.write_all(&entry.mode.to_be_bytes())?;
 
+    // The lengths are stored in a byte and two bytes; longer values would wrap and corrupt the index
     let hash_bytes = entry.blob_hash.as_bytes();
-    let hash_len = hash_bytes.len() as u8;
+    let hash_len = u8::try_from(hash_bytes.len()).map_err(|_| io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Hash for '{}' is {} bytes, longer than the index limit of {}", entry.path, hash_bytes.len(), u8::MAX),
+    ))?;
     writer.write_all(&hash_len.to_be_bytes())?;
     writer.write_all(hash_bytes)?;
 
     let path_bytes = entry.path.as_bytes();
-    let path_len = path_bytes.len() as u16;
+    let path_len = u16::try_from(path_bytes.len()).map_err(|_| io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Path is {} bytes, longer than the index limit of {}", path_bytes.len(), u16::MAX),
+    ))?;
     writer.write_all(&path_len.to_be_bytes())?;
     writer.write_all(path_bytes)?;
 
     Ok(())
 }
 
+// Checks whether a path is the pathspec itself or lies beneath it
+pub fn matches_pathspec(path: &str, pathspec: &str) -> bool {
+    let pathspec = pathspec.strip_prefix("./").unwrap_or(pathspec).trim_end_matches('/');
+    pathspec.is_empty() || pathspec == "." || path == pathspec || path.starts_with(&format!("{}/", pathspec))
+}
+
+// Checks a path against a list of pathspecs; an empty list matches everything
+pub fn matches_any_pathspec(path: &str, pathspecs: &[String]) -> bool {
+    pathspecs.is_empty() || pathspecs.iter().any(|pathspec| matches_pathspec(path, pathspec))
+}
+
+// The mode to record for a working file: a symbolic link, an executable file or a regular
+// file. Platforms without these notions record every file as regular.
+pub fn working_file_mode(path: &Path) -> io::Result<u32> {
+    let metadata = path.symlink_metadata()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.file_type().is_symlink() {
+            return Ok(MODE_SYMLINK);
+        }
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return Ok(MODE_EXECUTABLE);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    Ok(MODE_FILE)
+}
+
+// Lists the files under a path with their working-tree hashes, leaving out the `.rit`
+// directory and untracked paths the ignore rules match. Tracked files are always listed,
+// even if a pattern matches them, so they are never mistaken for deleted.
 fn create_index_from_path(directory: &Path) -> io::Result<Vec<IndexEntry>> {
+    let rules = IgnoreRules::load()?;
+    let tracked: HashSet<String> = load_index()?.into_iter().map(|entry| entry.path).collect();
     let mut index = Vec::new();
+    walk_working_tree(directory, &rules, &tracked, &mut index)?;
+    Ok(index)
+}
 
-    if let Ok(entries) = fs::read_dir(directory) {
-        for entry in entries.filter_map(|e| e.ok()) {
-            let path = entry.path();
+// Lists the files under a directory as `add` would stage them: everything outside `.rit`,
+// less untracked paths the ignore rules match unless `force` is set
+pub fn working_files_under(directory: &Path, force: bool) -> io::Result<Vec<IndexEntry>> {
+    if force {
+        let mut index = Vec::new();
+        walk_working_tree(directory, &IgnoreRules::default(), &HashSet::new(), &mut index)?;
+        Ok(index)
+    } else {
+        create_index_from_path(directory)
+    }
+}
 
-            // If the path is a directory, recurse into it
-            if path.is_dir() {
-                let subdir_state = create_index_from_path(&path).unwrap();
-                index.extend(subdir_state);
-            } else {
-                // If it's a file, get its state
-                let mut object = store_temporary(&path.to_string_lossy().to_string()).unwrap();
+// Whether a walked path is ignored and nothing tracked lives at or under it
+fn skip_ignored(path: &str, is_dir: bool, rules: &IgnoreRules, tracked: &HashSet<String>) -> bool {
+    if !rules.is_ignored(path, is_dir) {
+        return false;
+    }
+    if is_dir {
+        let prefix = format!("{}/", path);
+        !tracked.iter().any(|tracked_path| tracked_path.starts_with(&prefix))
+    } else {
+        !tracked.contains(path)
+    }
+}
 
-                let key = hash_data(&object)?;
+// How many directories this thread has listed while walking the working tree
+#[cfg(test)]
+thread_local! {
+    static DIRECTORIES_WALKED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
 
-                let entry = IndexEntry {
-                    mode: 0o100644,
-                    blob_hash: key.to_string(),
-                    path: path.to_string_lossy().to_string(),
-                };
+fn walk_working_tree(directory: &Path, rules: &IgnoreRules, tracked: &HashSet<String>, index: &mut Vec<IndexEntry>) -> io::Result<()> {
+    // A pathspec may name a single file or symbolic link rather than a directory
+    if directory.is_file() || directory.is_symlink() {
+        let path = directory.to_string_lossy().to_string();
+        if !skip_ignored(&path, false, rules, tracked) {
+            index.push(IndexEntry {
+                mode: working_file_mode(directory)?,
+                blob_hash: hash_working_file(directory)?,
+                path,
+            });
+        }
+        return Ok(());
+    }
 
-                index.push(entry);    
-                }
-            }
+    // An empty path is the working-tree root; paths under it stay relative to the root
+    let read_path = if directory.as_os_str().is_empty() { Path::new(".") } else { directory };
+    #[cfg(test)]
+    DIRECTORIES_WALKED.with(|walked| walked.set(walked.get() + 1));
+    let entries = match fs::read_dir(read_path) {
+        Ok(entries) => entries,
+        // A directory that does not exist has no files to report
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let path = directory.join(entry.file_name());
+
+        // The repository's own directory is never part of the working tree
+        if entry.file_name() == DIRECTORY_PATH {
+            continue;
         }
-    Ok(index)
+
+        // Symbolic links are recorded as links, never followed into directories
+        let is_dir = entry.file_type()?.is_dir();
+        let path_str = path.to_string_lossy().to_string();
+        // Ignored directories are not even read, which keeps build output from slowing status
+        if skip_ignored(&path_str, is_dir, rules, tracked) {
+            continue;
+        }
+
+        // If the path is a directory, recurse into it
+        if is_dir {
+            walk_working_tree(&path, rules, tracked, index)?;
+        } else {
+            // If it's a file, get its state
+            index.push(IndexEntry {
+                mode: working_file_mode(&path)?,
+                blob_hash: hash_working_file(&path)?,
+                path: path_str,
+            });
+        }
+    }
+    Ok(())
 }
 
-fn check_for_changes(previous_index_entry: &Vec<IndexEntry>, current_index_entry: &Vec<IndexEntry>) -> HashMap<String, String> {
+fn check_for_changes(previous_index_entry: &[IndexEntry], current_index_entry: &[IndexEntry]) -> HashMap<String, String> {
     let mut changes = HashMap::new();
 
-    // Create HashMap from path to blob_hash for quick lookup by borrowing values
-    let previous_files: HashMap<String, String> = previous_index_entry.iter()
-        .map(|entry| (entry.path.clone(), entry.blob_hash.clone()))
+    // Create HashMap from path to blob_hash and mode for quick lookup
+    let previous_files: HashMap<String, (String, u32)> = previous_index_entry.iter()
+        .map(|entry| (entry.path.clone(), (entry.blob_hash.clone(), entry.mode)))
         .collect();
 
-    let current_files: HashMap<String, String> = current_index_entry.iter()
-        .map(|entry| (entry.path.clone(), entry.blob_hash.clone()))
+    let current_files: HashMap<String, (String, u32)> = current_index_entry.iter()
+        .map(|entry| (entry.path.clone(), (entry.blob_hash.clone(), entry.mode)))
         .collect();
 
     // Check for changes in current files
     for curr_index in current_index_entry.iter() {
         let curr_path = &curr_index.path;
-        let curr_hash = &curr_index.blob_hash;
+        let curr_state = (curr_index.blob_hash.clone(), curr_index.mode);
 
         match previous_files.get(curr_path) {
-            Some(prev_hash) if prev_hash == curr_hash => {
-                // File has not changed (same blob_hash and path)
+            Some(prev_state) if *prev_state == curr_state => {
+                // File has not changed (same blob_hash, mode and path)
                 changes.insert(curr_path.clone(), "unmodified".to_string());
             }
             Some(_) => {
-                // File content has changed (hash is different)
+                // File content or mode has changed
                 changes.insert(curr_path.clone(), "modified".to_string());
             }
             None => {
@@ -280,108 +499,380 @@ fn check_for_changes(previous_index_entry: &Vec<IndexEntry>, current_index_entry
     changes
 }
 
-pub fn file_changes(path: &Path) -> HashMap<String, String>{
-
-    let previous_index_entry: Vec<IndexEntry> = load_index().unwrap();
-    let current_index_entry = create_index_from_path(path).unwrap();
+pub fn file_changes(path: &Path) -> io::Result<HashMap<String, String>> {
+    file_changes_in(path, &[])
+}
 
-    let changes = check_for_changes(&previous_index_entry, &current_index_entry);
+// Like file_changes, but only scans and reports paths matching the pathspecs
+pub fn file_changes_in(path: &Path, pathspecs: &[String]) -> io::Result<HashMap<String, String>> {
 
-    changes
-}
+    let previous_index_entry: Vec<IndexEntry> = load_index()?
+        .into_iter()
+        .filter(|entry| matches_any_pathspec(&entry.path, pathspecs))
+        .collect();
 
-pub fn get_status(){
-    let path = PathBuf::from(SOURCE_PATH);
-    let result = file_changes(&path);
+    // Only walk the requested subtrees instead of the whole source directory
+    let current_index_entry = if pathspecs.is_empty() {
+        create_index_from_path(path)?
+    } else {
+        let mut entries = Vec::new();
+        for pathspec in pathspecs {
+            let pathspec = pathspec.strip_prefix("./").unwrap_or(pathspec).trim_end_matches('/');
+            entries.extend(create_index_from_path(Path::new(pathspec))?);
+        }
+        entries
+    };
 
-    let branch_name = branches::get_current_branch_name().unwrap();
-    print!("On branch {}\n\n", branch_name);
+    Ok(check_for_changes(&previous_index_entry, &current_index_entry))
+}
 
-    // Compares the tree with Index files 
-    let tree_hash = get_current_tree_from_commit_hash();
-    let tree_hashmap = convert_tree_entry_to_hashmap(tree_hash);
-    let tree_index_entry = create_entry_from_hashmap(tree_hashmap);
-    let current_index_entry = load_index().unwrap();
-    let staged_changes = check_for_changes(&tree_index_entry, &current_index_entry);
+// Everything status reports, keyed by path
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StatusReport {
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    // The configured upstream and how far the branch is (ahead, behind) of it
+    pub upstream: Option<String>,
+    pub ahead_behind: Option<(usize, usize)>,
+    // HEAD tree against the index
+    pub staged: HashMap<String, String>,
+    // Index against the working tree
+    pub unstaged: HashMap<String, String>,
+    pub untracked: Vec<String>,
+    // Blob hashes in HEAD and in the index, for porcelain output
+    pub head_hashes: HashMap<String, String>,
+    pub index_hashes: HashMap<String, String>,
+    // Modes in HEAD, in the index and of tracked files in the working tree
+    pub head_modes: HashMap<String, u32>,
+    pub index_modes: HashMap<String, u32>,
+    pub worktree_modes: HashMap<String, u32>,
+}
 
-    println!("Changes to be committed:\n    (use \"git reset HEAD <file>...\" to unstage)");
-    for (path, change) in &staged_changes {
-        println!("{}", format!("{}:   {}", change, path).green());
+impl StatusReport {
+    // Whether nothing is staged, modified or untracked
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty() && self.unstaged.is_empty() && self.untracked.is_empty()
     }
 
-    println!("\n\n");
-    // compares the index files to current directory 
-    println!("Changes not staged for commit:\n  (use \"rit add <file>... to update what will be committed)");
-    for (path, change) in &result {
-        if change == "modified" || change == "deleted" {
-            println!("{}", format!("{}:   {}", change, path).red());
+    // The closing line of `status`, counting each kind of change
+    pub fn summary(&self) -> String {
+        if self.is_clean() {
+            return "nothing to commit, working tree clean".to_string();
         }
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        format!(
+            "{} staged change{}, {} unstaged change{}, {} untracked file{}",
+            self.staged.len(),
+            plural(self.staged.len()),
+            self.unstaged.len(),
+            plural(self.unstaged.len()),
+            self.untracked.len(),
+            plural(self.untracked.len())
+        )
     }
+}
 
+// The staged paths matching the pathspecs whose files are no longer in the working tree
+pub fn deleted_files(pathspecs: &[String]) -> io::Result<Vec<String>> {
+    Ok(load_index()?
+        .into_iter()
+        .filter(|entry| matches_any_pathspec(&entry.path, pathspecs))
+        .filter(|entry| {
+            let path = Path::new(&entry.path);
+            !path.is_file() && !path.is_symlink()
+        })
+        .map(|entry| entry.path)
+        .collect())
+}
 
-    println!("\n\n");
-    // compares the index files to current directory 
-    println!("Untracked files:\n    (use \"rit add <file>... to include in what will be committed)");
-    for (path, change) in &result {
-        if change == "new file"{
-            println!("{}", format!("{}:   {}", change, path).red());
+// Compares only the tracked files with the working tree, without walking it for new files
+fn tracked_changes(pathspecs: &[String]) -> io::Result<HashMap<String, String>> {
+    let deleted: HashSet<String> = deleted_files(pathspecs)?.into_iter().collect();
+    let mut changes = HashMap::new();
+    for entry in load_index()? {
+        if !matches_any_pathspec(&entry.path, pathspecs) || deleted.contains(&entry.path) {
+            continue;
+        }
+        let path = Path::new(&entry.path);
+        if hash_working_file(path)? != entry.blob_hash || working_file_mode(path)? != entry.mode {
+            changes.insert(entry.path, "modified".to_string());
         }
     }
+    for path in deleted {
+        changes.insert(path, "deleted".to_string());
+    }
+    Ok(changes)
+}
 
-
+// Builds the status of the repository, limited to the pathspecs when any are given
+pub fn status_report(pathspecs: &[String]) -> io::Result<StatusReport> {
+    status_report_with(pathspecs, true)
 }
 
-pub fn create_entry_from_hashmap(tree: HashMap<String, String>) -> Vec<IndexEntry>{
-    tree.into_iter()
-    .map(|(path, blob_hash)| IndexEntry {
-        mode: 0o100644, 
-        blob_hash,
-        path,
+// Like status_report; without `show_untracked` the working tree is not scanned for new
+// files, which is much cheaper on large trees, and the report lists no untracked files
+pub fn status_report_with(pathspecs: &[String], show_untracked: bool) -> io::Result<StatusReport> {
+    // Scan the whole working tree, which is the directory holding .rit
+    let mut result = if show_untracked {
+        file_changes_in(Path::new(""), pathspecs)?
+    } else {
+        tracked_changes(pathspecs)?
+    };
+    // A staged file gone from disk is a deletion, whatever the walk made of its path
+    for path in deleted_files(pathspecs)? {
+        result.insert(path, "deleted".to_string());
+    }
+
+    // Compares the tree with Index files
+    let tree_hash = get_current_tree_from_commit_hash()?;
+    let tree_hashmap = convert_tree_entry_to_hashmap(tree_hash)?;
+    let tree_index_entry: Vec<IndexEntry> = create_entry_from_hashmap(tree_hashmap)
+        .into_iter()
+        .filter(|entry| matches_any_pathspec(&entry.path, pathspecs))
+        .collect();
+    let current_index_entry: Vec<IndexEntry> = load_index()?
+        .into_iter()
+        .filter(|entry| matches_any_pathspec(&entry.path, pathspecs))
+        .collect();
+    let staged = check_for_changes(&tree_index_entry, &current_index_entry)
+        .into_iter()
+        .filter(|(_, change)| change != "unmodified")
+        .collect();
+
+    let mut untracked: Vec<String> = result.iter()
+        .filter(|(_, change)| *change == "new file")
+        .map(|(path, _)| path.clone())
+        .collect();
+    untracked.sort();
+    let unstaged = result.into_iter()
+        .filter(|(_, change)| change == "modified" || change == "deleted")
+        .collect();
+
+    let worktree_modes = current_index_entry.iter()
+        .filter_map(|e| working_file_mode(Path::new(&e.path)).ok().map(|mode| (e.path.clone(), mode)))
+        .collect();
+
+    let branch = branches::get_current_branch_name();
+    let head = branches::get_current_branch_commit_hash()?;
+    let upstream = match &branch {
+        Some(branch) => branches::get_upstream(branch)?,
+        None => None,
+    };
+    let ahead_behind = match (&head, &upstream) {
+        (Some(head), Some(upstream)) => match branches::get_commit_hash(upstream)? {
+            Some(upstream_hash) => Some(graph::ahead_behind(head, &upstream_hash)?),
+            None => None,
+        },
+        _ => None,
+    };
+
+    Ok(StatusReport {
+        branch,
+        head,
+        upstream,
+        ahead_behind,
+        staged,
+        unstaged,
+        untracked,
+        head_modes: tree_index_entry.iter().map(|e| (e.path.clone(), e.mode)).collect(),
+        index_modes: current_index_entry.iter().map(|e| (e.path.clone(), e.mode)).collect(),
+        worktree_modes,
+        head_hashes: tree_index_entry.into_iter().map(|e| (e.path, e.blob_hash)).collect(),
+        index_hashes: current_index_entry.into_iter().map(|e| (e.path, e.blob_hash)).collect(),
     })
-    .collect()
 }
 
-pub fn get_status_test(result: HashMap<String, String>,  staged_changes: HashMap<String, String>){
+// Prints the status for people and returns the report it was built from
+pub fn get_status(compare: Option<&str>, pathspecs: &[String]) -> io::Result<StatusReport> {
+    get_status_with(compare, pathspecs, true)
+}
+
+pub fn get_status_with(compare: Option<&str>, pathspecs: &[String], show_untracked: bool) -> io::Result<StatusReport> {
+    let report = status_report_with(pathspecs, show_untracked)?;
+    print_status(&report, compare, show_untracked)?;
+    Ok(report)
+}
 
-    println!("Changes to be committed:\n    (use \"git reset HEAD <file>...\" to unstage)");
-    for (path, change) in &staged_changes {
-        println!("{}", format!("{}:   {}", change, path).green());
+// Prints the long status for people
+pub fn print_status(report: &StatusReport, compare: Option<&str>, show_untracked: bool) -> io::Result<()> {
+    print!("{}", format_status(report, compare, show_untracked)?);
+    Ok(())
+}
+
+// Renders the long status: the branch, any merge in progress, then the staged, unstaged and
+// untracked paths in order, and the summary line
+pub fn format_status(report: &StatusReport, compare: Option<&str>, show_untracked: bool) -> io::Result<String> {
+    let mut out = String::new();
+    let sorted = |changes: &HashMap<String, String>| {
+        let mut changes: Vec<(String, String)> = changes.iter().map(|(path, change)| (path.clone(), change.clone())).collect();
+        changes.sort();
+        changes
+    };
+
+    out.push_str(&format!("On branch {}\n", report.branch.as_deref().unwrap_or("(detached)")));
+
+    if report.head.is_none() {
+        out.push_str("\nNo commits yet\n");
     }
 
-    println!("\n\n");
-    // compares the index files to current directory 
-    println!("Changes not staged for commit:\n  (use \"rit add <file>... to update what will be committed)");
-    for (path, change) in &result {
-        if change == "modified" || change == "deleted" {
-            println!("{}", format!("{}:   {}", change, path).red());
+    // Compares the current branch with another branch, or else with its upstream
+    if let Some(other_branch) = compare.or(report.upstream.as_deref()) {
+        out.push_str(&format!("{}\n", graph::compare_with_branch(other_branch)?));
+    }
+    out.push('\n');
+
+    if let Some(state) = merge::read_state()? {
+        let conflicts = state.conflicts();
+        if conflicts.is_empty() {
+            out.push_str("All conflicts fixed but you are still merging.\n    (use \"rit merge --abort\" to abort the merge)\n\n");
+        } else {
+            out.push_str("You have unmerged paths.\n    (fix conflicts, or use \"rit merge --abort\" to abort the merge)\n\n");
+            out.push_str("Unmerged paths:\n");
+            for path in &conflicts {
+                out.push_str(&format!("{}\n", format!("unmerged:   {}", path).red()));
+            }
+            out.push('\n');
         }
     }
 
-    println!("\n\n");
+    out.push_str("Changes to be committed:\n    (use \"rit reset HEAD <file>...\" to unstage)\n");
+    for (path, change) in sorted(&report.staged) {
+        out.push_str(&format!("{}\n", format!("{}:   {}", change, path).green()));
+    }
+
+    out.push_str("\n\n\n");
     // compares the index files to current directory 
-    println!("Untracked files:\n    (use \"rit add <file>... to include in what will be committed)");
-    for (path, change) in &result {
-        if change == "new file"{
-            println!("{}", format!("{}:   {}", change, path).red());
+    out.push_str("Changes not staged for commit:\n  (use \"rit add <file>... to update what will be committed)\n");
+    for (path, change) in sorted(&report.unstaged) {
+        out.push_str(&format!("{}\n", format!("{}:   {}", change, path).red()));
+    }
+
+    out.push_str("\n\n\n");
+    if show_untracked {
+        out.push_str("Untracked files:\n    (use \"rit add <file>... to include in what will be committed)\n");
+        for path in &report.untracked {
+            out.push_str(&format!("{}\n", format!("{}:   {}", "new file", path).red()));
         }
+    } else {
+        out.push_str("Untracked files not listed (use -u option to show untracked files)\n");
     }
 
+    out.push_str(&format!("\n{}\n", report.summary()));
+
+    Ok(out)
+}
 
+// Formats the report as git's `status --porcelain=v2`, optionally with the branch header
+pub fn porcelain_v2(report: &StatusReport, show_branch: bool) -> String {
+    porcelain_v2_with(report, show_branch, '\n')
 }
 
+// Porcelain v2 with each line ended by `terminator`; `\0` keeps paths with newlines intact
+pub fn porcelain_v2_with(report: &StatusReport, show_branch: bool, terminator: char) -> String {
+    let mut out = String::new();
 
+    if show_branch {
+        out.push_str(&format!("# branch.oid {}{}", report.head.as_deref().unwrap_or("(initial)"), terminator));
+        out.push_str(&format!("# branch.head {}{}", report.branch.as_deref().unwrap_or("(detached)"), terminator));
+        if let Some(upstream) = &report.upstream {
+            out.push_str(&format!("# branch.upstream {}{}", upstream, terminator));
+        }
+        if let Some((ahead, behind)) = report.ahead_behind {
+            out.push_str(&format!("# branch.ab +{} -{}{}", ahead, behind, terminator));
+        }
+    }
+
+    let code = |change: Option<&String>| match change.map(String::as_str) {
+        Some("modified") => 'M',
+        Some("new file") => 'A',
+        Some("deleted") => 'D',
+        _ => '.',
+    };
+    let mode = |mode: Option<&u32>| mode.map(|mode| format!("{:06o}", mode)).unwrap_or_else(|| "000000".to_string());
+
+    let mut paths: Vec<&String> = report.staged.keys().chain(report.unstaged.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    for path in paths {
+        let x = code(report.staged.get(path));
+        let y = code(report.unstaged.get(path));
+        let head_hash = report.head_hashes.get(path);
+        let index_hash = report.index_hashes.get(path);
+        out.push_str(&format!(
+            "1 {}{} N... {} {} {} {} {} {}{}",
+            x,
+            y,
+            mode(report.head_modes.get(path)),
+            mode(report.index_modes.get(path)),
+            mode(report.worktree_modes.get(path)),
+            head_hash.map(String::as_str).unwrap_or(ZERO_HASH),
+            index_hash.map(String::as_str).unwrap_or(ZERO_HASH),
+            path,
+            terminator
+        ));
+    }
+
+    for path in &report.untracked {
+        out.push_str(&format!("? {}{}", path, terminator));
+    }
+
+    out
+}
+
+// Formats the report as git's `status --short`: an `XY path` line per changed path, where X
+// is the staged change and Y the unstaged one, then `?? path` for each untracked file
+pub fn short_status(report: &StatusReport, show_branch: bool) -> String {
+    let mut out = String::new();
+    if show_branch {
+        out.push_str(&format!("## {}\n", report.branch.as_deref().unwrap_or("HEAD (no branch)")));
+    }
+
+    let code = |change: Option<&String>| match change.map(String::as_str) {
+        Some("modified") => 'M',
+        Some("new file") => 'A',
+        Some("deleted") => 'D',
+        _ => ' ',
+    };
+
+    let mut paths: Vec<&String> = report.staged.keys().chain(report.unstaged.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    for path in paths {
+        out.push_str(&format!("{}{} {}\n", code(report.staged.get(path)), code(report.unstaged.get(path)), path));
+    }
+    for path in &report.untracked {
+        out.push_str(&format!("?? {}\n", path));
+    }
+    out
+}
+
+pub fn create_entry_from_hashmap(tree: HashMap<String, (String, u32)>) -> Vec<IndexEntry>{
+    tree.into_iter()
+    .map(|(path, (blob_hash, mode))| IndexEntry {
+        mode,
+        blob_hash,
+        path,
+    })
+    .collect()
+}
 
 #[cfg(test)]
 mod tests {
     use crate::constants::SOURCE_PATH;
+    use crate::test_utils::TestDir;
 
     use super::*;
     use std::fs;
     use std::io::Cursor;
+    use tempdir::TempDir;
+
+    fn setup() -> TestDir {
+        // Run each test in its own scratch directory
+        let test_dir = TestDir::new();
 
-    fn setup() {
         // Ensure the directory exists for testing
         let _ = fs::create_dir_all(DIRECTORY_PATH);
+        test_dir
     }
 
     fn cleanup() {
@@ -395,7 +886,7 @@ mod tests {
     // TESTS
     #[test]
     fn test_create_index() {
-        setup();
+        let _test_dir = setup();
         // Ensure the index file doesn't exist before the test
         assert!(!get_index_path().exists());
 
@@ -408,7 +899,7 @@ mod tests {
 
     #[test]
     fn test_add_to_index() {
-        setup();
+        let _test_dir = setup();
         create_index().unwrap(); // Ensure the index file exists
 
         // Add a file to the index
@@ -423,9 +914,34 @@ mod tests {
         cleanup();
     }
 
+    #[test]
+    fn test_concurrent_bulk_adds_compose() {
+        let _test_dir = setup();
+        create_index().unwrap();
+
+        // Each thread adds its own disjoint set of paths, one add at a time
+        let handles: Vec<_> = (0..8)
+            .map(|thread_id| {
+                std::thread::spawn(move || {
+                    for file_id in 0..10 {
+                        let path = format!("thread{}/file{}.txt", thread_id, file_id);
+                        bulk_add_to_index(&[(&path, "hash123")]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let entries = load_index().unwrap();
+        assert_eq!(entries.len(), 80);
+        assert!(!Path::new(".rit/index.lock").exists());
+    }
+
     #[test]
     fn test_load_index_empty() {
-        setup();
+        let _test_dir = setup();
         create_index().unwrap();
 
         // Test loading an empty index
@@ -437,7 +953,7 @@ mod tests {
 
     #[test]
     fn test_remove_from_index() {
-        setup();
+        let _test_dir = setup();
         create_index().unwrap();
         add_to_index("test_file.txt", "hash123").unwrap();
         add_to_index("test_file2.txt", "hash456").unwrap();
@@ -456,7 +972,7 @@ mod tests {
     // Test Save Index
     #[test]
     fn test_save_index() {
-        setup();
+        let _test_dir = setup();
         create_index().unwrap();
         add_to_index("test_file.txt", "hash123").unwrap();
         add_to_index("test_file2.txt", "hash456").unwrap();
@@ -472,6 +988,53 @@ mod tests {
         cleanup();
     }
 
+    #[test]
+    fn test_index_writers_wait_for_index_lock() {
+        let _test_dir = setup();
+        create_index().unwrap();
+        add_to_index("test_file.txt", "hash123").unwrap();
+
+        // None of the whole-index writers touch the file while someone else holds the lock
+        let lock = lock_index().unwrap();
+        let writer = std::thread::spawn(|| {
+            update_index("test_file.txt", "hash456")?;
+            remove_from_index("test_file.txt")?;
+            save_index(&[])
+        });
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(load_index().unwrap()[0].blob_hash, "hash123");
+        drop(lock);
+
+        writer.join().unwrap().unwrap();
+        assert!(load_index().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_save_index_is_canonical() {
+        let _test_dir = setup();
+        let entry = |path: &str, hash: &str| IndexEntry { mode: 0o100644, blob_hash: hash.to_string(), path: path.to_string() };
+        let entries = vec![entry("b.txt", "hash2"), entry("a/z.txt", "hash3"), entry("a.txt", "hash1")];
+
+        save_index(&entries).unwrap();
+        let first = fs::read(get_index_path()).unwrap();
+        let mut reversed = entries.clone();
+        reversed.reverse();
+        save_index(&reversed).unwrap();
+        assert_eq!(fs::read(get_index_path()).unwrap(), first);
+
+        // Loading and saving again changes nothing, and entries come back in path order
+        let loaded = load_index().unwrap();
+        let paths: Vec<&str> = loaded.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "a/z.txt", "b.txt"]);
+        save_index(&loaded).unwrap();
+        assert_eq!(fs::read(get_index_path()).unwrap(), first);
+
+        // Adds through the HashMap-based path are written in the same order
+        add_to_index("0.txt", "hash0").unwrap();
+        let paths: Vec<String> = load_index().unwrap().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["0.txt", "a.txt", "a/z.txt", "b.txt"]);
+    }
+
     // Test write_index_entry
     #[test]
     fn test_write_index_entry() {
@@ -536,9 +1099,75 @@ mod tests {
         assert_eq!(entry.path, "test_file.txt"); // Check file path
     }
 
+    #[test]
+    fn test_load_index_rejects_truncated_entry() {
+        let _test_dir = setup();
+        let first = IndexEntry { mode: 0o100644, blob_hash: "a".repeat(64), path: "first.txt".to_string() };
+        let second = IndexEntry { path: "second.txt".to_string(), ..first.clone() };
+        save_index(&[first.clone(), second]).unwrap();
+        let bytes = fs::read(get_index_path()).unwrap();
+
+        // Cutting the index at an entry boundary loses nothing that was written
+        let first_len = bytes.len() - (4 + 1 + 64 + 2 + "second.txt".len());
+        fs::write(get_index_path(), &bytes[..first_len]).unwrap();
+        assert_eq!(load_index().unwrap(), vec![first]);
+
+        // Cutting it inside the second entry, even inside its mode, is corruption
+        for len in [first_len + 2, first_len + 4, bytes.len() - 1] {
+            fs::write(get_index_path(), &bytes[..len]).unwrap();
+            let err = load_index().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains("truncated"), "{}", err);
+        }
+
+        cleanup();
+    }
+
+    #[test]
+    fn test_load_index_checks_header() {
+        let _test_dir = setup();
+        let entry = IndexEntry { mode: 0o100644, blob_hash: "a".repeat(64), path: "file.txt".to_string() };
+        save_index(std::slice::from_ref(&entry)).unwrap();
+        let bytes = fs::read(get_index_path()).unwrap();
+        assert!(bytes.starts_with(b"RIDX\x01"));
+        assert_eq!(load_index().unwrap(), vec![entry.clone()]);
+
+        // An index from before the header, a later version, and a cut-off header are all refused
+        let mut headerless = Vec::new();
+        write_index_entry(&mut headerless, &entry).unwrap();
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        for contents in [headerless, newer, b"RIDX".to_vec()] {
+            fs::write(get_index_path(), contents).unwrap();
+            let err = load_index().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains("Unsupported index format"), "{}", err);
+        }
+
+        cleanup();
+    }
+
+    #[test]
+    fn test_index_entry_length_limits() {
+        // The longest path the index can hold round-trips
+        let entry = IndexEntry { mode: 0o100644, blob_hash: "a".repeat(64), path: "p".repeat(u16::MAX as usize) };
+        let mut buffer: Vec<u8> = Vec::new();
+        write_index_entry(&mut buffer, &entry).unwrap();
+        assert_eq!(read_index_entry(&mut Cursor::new(buffer)).unwrap(), entry);
+
+        // One byte more is rejected instead of truncated
+        let too_long = IndexEntry { path: "p".repeat(u16::MAX as usize + 1), ..entry.clone() };
+        let error = write_index_entry(&mut Vec::new(), &too_long).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(serialize_index(&[too_long]).is_err());
+
+        let long_hash = IndexEntry { blob_hash: "a".repeat(256), ..entry };
+        assert!(write_index_entry(&mut Vec::new(), &long_hash).is_err());
+    }
+
     #[test]
     fn test_clear_index() {
-        setup();
+        let _test_dir = setup();
         // Arrange
         // Create the index file with some initial content
         create_index().unwrap();
@@ -560,7 +1189,7 @@ mod tests {
 
     #[test]
     fn test_load_index() {
-        setup();
+        let _test_dir = setup();
         // Arrange
         create_index().unwrap();
         let index_path = get_index_path();
@@ -575,6 +1204,8 @@ mod tests {
             0x74, 0x65, 0x73, 0x74, 0x5f, 0x66, 0x69, 0x6c, 0x65, 0x2e, 0x74, 0x78, 0x74 // Path: "test_file.txt"
         ];
 
+        file.write_all(INDEX_SIGNATURE).unwrap();
+        file.write_all(&[INDEX_VERSION]).unwrap();
         file.write_all(&entry_bytes).unwrap();
         file.flush().unwrap();
 
@@ -596,7 +1227,7 @@ mod tests {
 
     #[test]
     fn test_update_index() {
-        setup();
+        let _test_dir = setup();
         // Arrange
         // Create the index file and add an entry
         create_index().unwrap();
@@ -611,6 +1242,8 @@ mod tests {
             0x74, 0x65, 0x73, 0x74, 0x5f, 0x66, 0x69, 0x6c, 0x65, 0x2e, 0x74, 0x78, 0x74 // Path: "test_file.txt"
         ];
         let mut file = File::create(&index_path).unwrap();
+        file.write_all(INDEX_SIGNATURE).unwrap();
+        file.write_all(&[INDEX_VERSION]).unwrap();
         file.write_all(&initial_entry_bytes).unwrap();
         file.flush().unwrap();
 
@@ -930,50 +1563,458 @@ mod tests {
 
     #[test]
     fn test_file_changes() {
+        let _test_dir = setup();
+        fs::create_dir_all(SOURCE_PATH).unwrap();
+        create_test_file(Path::new(SOURCE_PATH).join("main.rs"), "fn main() {}").unwrap();
+
         let path = PathBuf::from(SOURCE_PATH);
-        let result = file_changes(&path);
+        let result = file_changes(&path).unwrap();
         // for (path, change) in &result {
         //     println!("Path: {}, Change: {}", path, change);
         // }
-        assert!(!result.is_empty());  
+        assert!(!result.is_empty());
+    }
+
+    #[test]
+    fn test_committed_file_is_unmodified() {
+        let _test_dir = TestDir::with_repo();
+        fs::create_dir_all(SOURCE_PATH).unwrap();
+        create_test_file(PathBuf::from("src/main.rs"), "fn main() {}").unwrap();
+        crate::staging::add_file_to_staging("src/main.rs").unwrap();
+        crate::commit::commit("Initial commit", "Tester <tester@example.com>").unwrap();
+
+        let result = file_changes(Path::new(SOURCE_PATH)).unwrap();
+        assert_eq!(result.get("src/main.rs"), Some(&"unmodified".to_string()));
+    }
+
+    #[test]
+    fn test_added_file_key_matches_hash_object() {
+        let _test_dir = TestDir::with_repo();
+        fs::create_dir_all(SOURCE_PATH).unwrap();
+        create_test_file(PathBuf::from("src/lib.rs"), "pub fn lib() {}").unwrap();
+        crate::staging::add_file_to_staging("src/lib.rs").unwrap();
+
+        let result = file_changes(Path::new(SOURCE_PATH)).unwrap();
+        assert_eq!(result.get("src/lib.rs"), Some(&"unmodified".to_string()));
+
+        let staged = load_index().unwrap();
+        let hash_object_key = crate::database::store_file("src/lib.rs").unwrap();
+        assert_eq!(staged[0].blob_hash, hash_object_key);
+        assert_eq!(crate::database::blob_key(b"pub fn lib() {}").unwrap(), hash_object_key);
+    }
+
+    #[test]
+    fn test_file_changes_limited_to_pathspec() {
+        let _test_dir = TestDir::with_repo();
+        fs::create_dir_all("src/a").unwrap();
+        fs::create_dir_all("src/b").unwrap();
+        create_test_file(PathBuf::from("src/a/x.txt"), "x").unwrap();
+        create_test_file(PathBuf::from("src/b/y.txt"), "y").unwrap();
+        crate::staging::add_file_to_staging("src/a/x.txt").unwrap();
+        crate::staging::add_file_to_staging("src/b/y.txt").unwrap();
+
+        create_test_file(PathBuf::from("src/a/x.txt"), "x changed").unwrap();
+        create_test_file(PathBuf::from("src/b/y.txt"), "y changed").unwrap();
+
+        let result = file_changes_in(Path::new(SOURCE_PATH), &["src/a/".to_string()]).unwrap();
+        assert_eq!(result.get("src/a/x.txt"), Some(&"modified".to_string()));
+        assert!(!result.contains_key("src/b/y.txt"));
+
+        let result = file_changes_in(Path::new(SOURCE_PATH), &["src/b/y.txt".to_string()]).unwrap();
+        assert_eq!(result.get("src/b/y.txt"), Some(&"modified".to_string()));
+        assert_eq!(result.len(), 1);
     }
 
     #[test]
     fn test_get_status() {
-        get_status();
-    }
-
-    #[test]
-    fn test_get_status_test() {
-        // result are from the current directory and the index entries
-        let mut result = HashMap::new();
-        result.insert("file1.rs".to_string(), "modified".to_string());
-        result.insert("file2.rs".to_string(), "new file".to_string());
-        result.insert("file3.rs".to_string(), "deleted".to_string());
-    
-        //staged entries are from the tree and index entries
-        let mut staged_changes = HashMap::new();
-        staged_changes.insert("file4.rs".to_string(), "modified".to_string());
-        staged_changes.insert("file5.rs".to_string(), "added".to_string());
-    
-        // Call the function with the example data
-        get_status_test(result, staged_changes);
-    
-    }
-
-    #[test]
-    fn test_get_status_two_staged_change() {
-        // result are from the current directory and the index entries
-        let mut result = HashMap::new();
-        result.insert("file2.rs".to_string(), "new file".to_string());
-    
-        //staged entries are from the tree and index entries
-        let mut staged_changes = HashMap::new();
-        staged_changes.insert("file4.rs".to_string(), "modified".to_string());
-        staged_changes.insert("file5.rs".to_string(), "added".to_string());
-    
-        // Call the function with the example data
-        get_status_test(result, staged_changes);
-    
+        let _test_dir = TestDir::with_repo();
+        fs::create_dir_all(SOURCE_PATH).unwrap();
+        create_test_file(Path::new(SOURCE_PATH).join("main.rs"), "fn main() {}").unwrap();
+        crate::staging::add_file_to_staging("src/main.rs").unwrap();
+        crate::commit::commit("Initial commit", "Tester <tester@example.com>").unwrap();
+
+        get_status(None, &[]).unwrap();
+    }
+
+    #[test]
+    fn test_status_without_untracked_files() {
+        let _test_dir = TestDir::with_repo();
+        create_test_file(PathBuf::from("tracked.txt"), "tracked").unwrap();
+        crate::staging::add_file_to_staging("tracked.txt").unwrap();
+        crate::commit::commit("Initial commit", "Tester <tester@example.com>").unwrap();
+        create_test_file(PathBuf::from("tracked.txt"), "changed").unwrap();
+        fs::create_dir_all("build").unwrap();
+        for i in 0..3 {
+            create_test_file(PathBuf::from(format!("build/out{}.o", i)), "object").unwrap();
+        }
+        let walked = || DIRECTORIES_WALKED.with(|walked| walked.get());
+
+        let before = walked();
+        let full = status_report(&[]).unwrap();
+        assert!(walked() > before);
+        let before = walked();
+        let tracked_only = status_report_with(&[], false).unwrap();
+        // The working tree is never listed, only the tracked paths looked at
+        assert_eq!(walked(), before);
+
+        assert_eq!(full.untracked.len(), 3);
+        assert!(tracked_only.untracked.is_empty());
+        // Tracked changes are still reported the same way
+        assert_eq!(tracked_only.unstaged, full.unstaged);
+        assert_eq!(tracked_only.unstaged.get("tracked.txt"), Some(&"modified".to_string()));
+        assert_eq!(tracked_only.staged, full.staged);
+
+        // Deleted tracked files are still noticed
+        fs::remove_file("tracked.txt").unwrap();
+        assert_eq!(status_report_with(&[], false).unwrap().unstaged.get("tracked.txt"), Some(&"deleted".to_string()));
+    }
+
+    #[test]
+    fn test_status_skips_ignored_paths() {
+        let _test_dir = TestDir::with_repo();
+        create_test_file(PathBuf::from(".ritignore"), "target/\n*.log\n!keep.log\n").unwrap();
+        create_test_file(PathBuf::from("tracked.log"), "tracked").unwrap();
+        // Ignored files are only added when forced, and never from inside .rit
+        assert!(crate::staging::add_file_to_staging("tracked.log").is_err());
+        assert!(crate::staging::add_file_to_staging_with(".rit/HEAD", true).is_err());
+        crate::staging::add_file_to_staging_with("tracked.log", true).unwrap();
+        // Once tracked, it can be updated without forcing
+        crate::staging::add_file_to_staging("tracked.log").unwrap();
+        crate::commit::commit("Initial commit", "Tester <tester@example.com>").unwrap();
+
+        fs::create_dir_all("target/debug").unwrap();
+        create_test_file(PathBuf::from("target/debug/rit"), "binary").unwrap();
+        create_test_file(PathBuf::from("build.log"), "log").unwrap();
+        create_test_file(PathBuf::from("keep.log"), "kept").unwrap();
+        create_test_file(PathBuf::from("main.rs"), "fn main() {}").unwrap();
+
+        let report = status_report(&[]).unwrap();
+        assert_eq!(report.untracked, vec![".ritignore", "keep.log", "main.rs"]);
+        // A tracked file matching a pattern is still tracked, not deleted
+        assert!(report.unstaged.is_empty());
+        create_test_file(PathBuf::from("tracked.log"), "changed").unwrap();
+        let report = status_report(&["tracked.log".to_string()]).unwrap();
+        assert_eq!(report.unstaged.get("tracked.log"), Some(&"modified".to_string()));
+        assert!(status_report(&["build.log".to_string()]).unwrap().untracked.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("a.txt").unwrap(), "a.txt");
+        assert_eq!(normalize_path("./a.txt").unwrap(), "a.txt");
+        assert_eq!(normalize_path("docs//./guide/../a.md").unwrap(), "docs/a.md");
+        assert_eq!(normalize_path(".").unwrap(), "");
+        assert!(normalize_path("../a.txt").is_err());
+        assert!(normalize_path("docs/../../a.txt").is_err());
+        assert!(normalize_path("/definitely/not/the/repo/a.txt").is_err());
+    }
+
+    #[test]
+    fn test_dot_slash_paths_share_one_entry() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("a.txt", "one").unwrap();
+        crate::staging::add_file_to_staging("./a.txt").unwrap();
+        fs::write("a.txt", "two").unwrap();
+        crate::staging::add_file_to_staging("a.txt").unwrap();
+        let absolute = std::env::current_dir().unwrap().join("a.txt");
+        crate::staging::add_file_to_staging(absolute.to_str().unwrap()).unwrap();
+        add_to_index("./b/../a.txt", &"1".repeat(64)).unwrap();
+
+        let entries = load_index().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "a.txt");
+        assert_eq!(entries[0].blob_hash, "1".repeat(64));
+        assert!(add_to_index("../a.txt", &"1".repeat(64)).is_err());
+        assert!(add_to_index(".", &"1".repeat(64)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_add_records_executable_and_symlink_modes() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        let _test_dir = TestDir::with_repo();
+        create_test_file(PathBuf::from("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions("run.sh", fs::Permissions::from_mode(0o755)).unwrap();
+        create_test_file(PathBuf::from("plain.txt"), "plain").unwrap();
+        symlink("plain.txt", "link").unwrap();
+        symlink("missing.txt", "dangling").unwrap();
+        for path in ["run.sh", "plain.txt", "link", "dangling"] {
+            crate::staging::add_file_to_staging(path).unwrap();
+        }
+
+        let modes: HashMap<String, u32> = load_index().unwrap().into_iter().map(|entry| (entry.path, entry.mode)).collect();
+        assert_eq!(modes["run.sh"], MODE_EXECUTABLE);
+        assert_eq!(modes["plain.txt"], MODE_FILE);
+        assert_eq!(modes["link"], MODE_SYMLINK);
+        assert_eq!(modes["dangling"], MODE_SYMLINK);
+
+        // A link's blob is the path it points to, and the tree keeps every mode
+        let entries = load_index().unwrap();
+        let link = entries.iter().find(|entry| entry.path == "link").unwrap();
+        assert_eq!(link.blob_hash, crate::database::blob_key(b"plain.txt").unwrap());
+        let commit_hash = crate::commit::commit("modes", "Tester <tester@example.com>").unwrap();
+        let tree = crate::commit::read_commit(&commit_hash).unwrap().tree;
+        let tree_modes: HashMap<String, u32> = crate::tree::read_tree(&tree).unwrap().into_iter().map(|entry| (entry.name, entry.mode)).collect();
+        assert_eq!(tree_modes, modes);
+
+        // The working tree scan sees the same modes, so nothing shows as changed
+        assert!(status_report(&[]).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_status_without_src_directory() {
+        let _test_dir = TestDir::with_repo();
+        assert!(!Path::new(SOURCE_PATH).exists());
+        create_test_file(PathBuf::from("README.md"), "readme").unwrap();
+        crate::staging::add_file_to_staging("README.md").unwrap();
+        crate::commit::commit("Initial commit", "Tester <tester@example.com>").unwrap();
+        assert!(status_report(&[]).unwrap().is_clean());
+
+        // Files anywhere in the working tree are seen, but nothing under .rit
+        create_test_file(PathBuf::from("README.md"), "changed").unwrap();
+        fs::create_dir_all("docs").unwrap();
+        create_test_file(PathBuf::from("docs/guide.md"), "guide").unwrap();
+        let report = status_report(&[]).unwrap();
+        assert_eq!(report.unstaged.get("README.md"), Some(&"modified".to_string()));
+        assert_eq!(report.untracked, vec!["docs/guide.md"]);
+
+        // A pathspec naming a missing directory reports nothing rather than failing
+        assert!(file_changes_in(Path::new(""), &["missing/".to_string()]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_status_summary() {
+        let mut report = StatusReport::default();
+        assert!(report.is_clean());
+        assert_eq!(report.summary(), "nothing to commit, working tree clean");
+
+        report.staged.insert("a.txt".to_string(), "new file".to_string());
+        report.untracked = vec!["b.txt".to_string(), "c.txt".to_string()];
+        assert!(!report.is_clean());
+        assert_eq!(report.summary(), "1 staged change, 0 unstaged changes, 2 untracked files");
+    }
+
+    #[test]
+    fn test_porcelain_v2() {
+        let _test_dir = TestDir::with_repo();
+        fs::create_dir_all(SOURCE_PATH).unwrap();
+
+        // A staged file, a staged file edited again, and an untracked one
+        create_test_file(PathBuf::from("src/added.rs"), "added").unwrap();
+        crate::staging::add_file_to_staging("src/added.rs").unwrap();
+        create_test_file(PathBuf::from("src/edited.rs"), "staged").unwrap();
+        crate::staging::add_file_to_staging("src/edited.rs").unwrap();
+        create_test_file(PathBuf::from("src/edited.rs"), "unstaged").unwrap();
+        create_test_file(PathBuf::from("src/untracked.rs"), "untracked").unwrap();
+        let added_hash = crate::database::blob_key(b"added").unwrap();
+        let staged_hash = crate::database::blob_key(b"staged").unwrap();
+
+        let report = status_report(&[]).unwrap();
+        let expected = format!(
+            "# branch.oid (initial)\n# branch.head master\n\
+             1 A. N... 000000 100644 100644 {zero} {added_hash} src/added.rs\n\
+             1 AM N... 000000 100644 100644 {zero} {staged_hash} src/edited.rs\n\
+             ? src/untracked.rs\n",
+            zero = ZERO_HASH,
+        );
+        assert_eq!(porcelain_v2(&report, true), expected);
+        assert!(!porcelain_v2(&report, false).starts_with('#'));
+
+        let nul_terminated = porcelain_v2_with(&report, false, '\0');
+        assert!(!nul_terminated.contains('\n'));
+        assert!(nul_terminated.ends_with("\0? src/untracked.rs\0"));
+        assert_eq!(nul_terminated.split_terminator('\0').count(), 3);
+    }
+
+    #[test]
+    fn test_status_uses_upstream() {
+        let _test_dir = TestDir::with_repo();
+        create_test_file(PathBuf::from("file.txt"), "base").unwrap();
+        crate::staging::add_file_to_staging("file.txt").unwrap();
+        let base = crate::commit::commit("base", "Tester <tester@example.com>").unwrap();
+
+        branches::create_banch("feature", &base).unwrap();
+        fs::write(crate::constants::head_file_path(), "ref: refs/heads/feature\n").unwrap();
+        create_test_file(PathBuf::from("file.txt"), "feature").unwrap();
+        crate::staging::add_file_to_staging("file.txt").unwrap();
+        crate::commit::commit("feature", "Tester <tester@example.com>").unwrap();
+
+        assert_eq!(status_report(&[]).unwrap().upstream, None);
+        branches::set_upstream("feature", "master").unwrap();
+
+        // No --compare needed: the upstream drives ahead/behind
+        let report = status_report(&[]).unwrap();
+        assert_eq!(report.upstream, Some("master".to_string()));
+        assert_eq!(report.ahead_behind, Some((1, 0)));
+        assert!(porcelain_v2(&report, true).contains("# branch.upstream master\n# branch.ab +1 -0\n"));
+        assert!(branches::set_upstream("feature", "missing").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_status_reports_mode_change() {
+        use std::os::unix::fs::PermissionsExt;
+        let _test_dir = TestDir::with_repo();
+        fs::write("run.sh", "echo hi").unwrap();
+        fs::set_permissions("run.sh", fs::Permissions::from_mode(0o644)).unwrap();
+        crate::staging::add_file_to_staging("run.sh").unwrap();
+        crate::commit::commit("first", "Tester <tester@example.com>").unwrap();
+        fs::set_permissions("run.sh", fs::Permissions::from_mode(0o755)).unwrap();
+
+        let modified = HashMap::from([("run.sh".to_string(), "modified".to_string())]);
+        let report = status_report(&[]).unwrap();
+        assert_eq!(report.unstaged, modified);
+        assert_eq!(status_report_with(&[], false).unwrap().unstaged, modified);
+        assert_eq!(short_status(&report, false), " M run.sh\n");
+        assert!(porcelain_v2(&report, false).starts_with("1 .M N... 100644 100644 100755 "), "{}", porcelain_v2(&report, false));
+        assert_eq!(crate::diff::worktree_changes().unwrap()[0].new.as_ref().unwrap().mode, MODE_EXECUTABLE);
+
+        // Once staged, the new mode is a change to be committed
+        crate::staging::add_file_to_staging("run.sh").unwrap();
+        let report = status_report(&[]).unwrap();
+        assert_eq!(report.staged, modified);
+        assert!(report.unstaged.is_empty());
+        assert!(porcelain_v2(&report, false).starts_with("1 M. N... 100644 100755 100755 "), "{}", porcelain_v2(&report, false));
+    }
+
+    #[test]
+    fn test_short_status() {
+        let report = StatusReport {
+            branch: Some("master".to_string()),
+            staged: HashMap::from([
+                ("added.txt".to_string(), "new file".to_string()),
+                ("both.txt".to_string(), "modified".to_string()),
+            ]),
+            unstaged: HashMap::from([
+                ("both.txt".to_string(), "modified".to_string()),
+                ("gone.txt".to_string(), "deleted".to_string()),
+            ]),
+            untracked: vec!["new.txt".to_string()],
+            ..StatusReport::default()
+        };
+        assert_eq!(short_status(&report, false), "A  added.txt\nMM both.txt\n D gone.txt\n?? new.txt\n");
+        assert_eq!(short_status(&report, true), "## master\nA  added.txt\nMM both.txt\n D gone.txt\n?? new.txt\n");
+        assert_eq!(short_status(&StatusReport::default(), false), "");
+    }
+
+    #[test]
+    fn test_format_status() {
+        let _test_dir = TestDir::with_repo();
+        let report = StatusReport {
+            branch: Some("master".to_string()),
+            head: Some("c".repeat(64)),
+            staged: HashMap::from([
+                ("file5.rs".to_string(), "new file".to_string()),
+                ("file4.rs".to_string(), "modified".to_string()),
+            ]),
+            unstaged: HashMap::from([
+                ("file1.rs".to_string(), "modified".to_string()),
+                ("file3.rs".to_string(), "deleted".to_string()),
+            ]),
+            untracked: vec!["file2.rs".to_string()],
+            ..StatusReport::default()
+        };
+
+        let out = format_status(&report, None, true).unwrap();
+        assert!(out.starts_with("On branch master\n\n"), "{}", out);
+        assert!(!out.contains("No commits yet"));
+        let position = |text: &str| out.find(text).unwrap_or_else(|| panic!("{} missing from {}", text, out));
+        // Each section lists its paths in order, under its own heading
+        assert!(position("Changes to be committed:") < position("modified:   file4.rs"));
+        assert!(position("modified:   file4.rs") < position("new file:   file5.rs"));
+        assert!(position("new file:   file5.rs") < position("Changes not staged for commit:"));
+        assert!(position("Changes not staged for commit:") < position("modified:   file1.rs"));
+        assert!(position("modified:   file1.rs") < position("deleted:   file3.rs"));
+        assert!(position("deleted:   file3.rs") < position("Untracked files:"));
+        assert!(position("Untracked files:") < position("new file:   file2.rs"));
+        assert!(out.ends_with("\n2 staged changes, 2 unstaged changes, 1 untracked file\n"), "{}", out);
+
+        let out = format_status(&report, None, false).unwrap();
+        assert!(out.contains("Untracked files not listed"));
+        assert!(!out.contains("file2.rs"));
+    }
+
+    #[test]
+    fn test_format_status_before_first_commit() {
+        let _test_dir = TestDir::with_repo();
+        let report = StatusReport {
+            branch: Some("master".to_string()),
+            untracked: vec!["file2.rs".to_string()],
+            ..StatusReport::default()
+        };
+
+        let out = format_status(&report, None, true).unwrap();
+        assert!(out.starts_with("On branch master\n\nNo commits yet\n\n"), "{}", out);
+        assert!(out.contains("new file:   file2.rs"));
+        assert!(out.ends_with("\n0 staged changes, 0 unstaged changes, 1 untracked file\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_status_report_scans_whole_repository() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("top.txt", "top").unwrap();
+        fs::create_dir_all("docs/guide").unwrap();
+        fs::write("docs/guide/intro.md", "intro").unwrap();
+        fs::create_dir_all(SOURCE_PATH).unwrap();
+        fs::write(Path::new(SOURCE_PATH).join("main.rs"), "fn main() {}").unwrap();
+
+        let report = status_report(&[]).unwrap();
+        assert_eq!(report.untracked, vec!["docs/guide/intro.md", "src/main.rs", "top.txt"]);
+        assert!(report.untracked.iter().all(|path| !path.starts_with(DIRECTORY_PATH)));
+    }
+
+    #[test]
+    fn test_status_report_nested_unchanged_file() {
+        let _test_dir = TestDir::with_repo();
+        fs::create_dir_all("dir/sub").unwrap();
+        fs::write("dir/sub/file2.txt", "nested").unwrap();
+        fs::write("top.txt", "top").unwrap();
+        crate::staging::add_file_to_staging("dir/sub/file2.txt").unwrap();
+        crate::staging::add_file_to_staging("top.txt").unwrap();
+        crate::commit::commit("first", "Tester <tester@example.com>").unwrap();
+
+        // The committed tree and the index agree on every path, however deep
+        let tree = convert_tree_entry_to_hashmap(get_current_tree_from_commit_hash().unwrap()).unwrap();
+        assert_eq!(tree.get("dir/sub/file2.txt"), load_index().unwrap().iter().find(|e| e.path == "dir/sub/file2.txt").map(|e| (e.blob_hash.clone(), e.mode)).as_ref());
+        let report = status_report(&[]).unwrap();
+        assert!(report.staged.is_empty(), "{:?}", report.staged);
+        assert!(report.is_clean(), "{:?}", report);
+
+        fs::write("dir/sub/file2.txt", "changed").unwrap();
+        crate::staging::add_file_to_staging("dir/sub/file2.txt").unwrap();
+        let report = status_report(&[]).unwrap();
+        assert_eq!(report.staged, HashMap::from([("dir/sub/file2.txt".to_string(), "modified".to_string())]));
+    }
+
+    #[test]
+    fn test_status_reports_deleted_files() {
+        let _test_dir = TestDir::with_repo();
+        fs::create_dir_all("dir").unwrap();
+        for path in ["gone.txt", "kept.txt", "dir/nested.txt"] {
+            fs::write(path, path).unwrap();
+            crate::staging::add_file_to_staging(path).unwrap();
+        }
+        crate::commit::commit("first", "Tester <tester@example.com>").unwrap();
+        fs::remove_file("gone.txt").unwrap();
+        fs::remove_dir_all("dir").unwrap();
+
+        assert_eq!(deleted_files(&[]).unwrap(), vec!["dir/nested.txt", "gone.txt"]);
+        assert_eq!(deleted_files(&["dir".to_string()]).unwrap(), vec!["dir/nested.txt"]);
+
+        // Deletions are unstaged changes, with or without the untracked-file walk
+        let expected = HashMap::from([
+            ("gone.txt".to_string(), "deleted".to_string()),
+            ("dir/nested.txt".to_string(), "deleted".to_string()),
+        ]);
+        assert_eq!(status_report_with(&[], true).unwrap().unstaged, expected);
+        assert_eq!(status_report_with(&[], false).unwrap().unstaged, expected);
+        let report = status_report(&["gone.txt".to_string()]).unwrap();
+        assert_eq!(report.unstaged, HashMap::from([("gone.txt".to_string(), "deleted".to_string())]));
+        assert!(report.staged.is_empty());
+
+        let out = format_status(&status_report(&[]).unwrap(), None, true).unwrap();
+        let unstaged_section = &out[out.find("Changes not staged for commit:").unwrap()..out.find("Untracked files:").unwrap()];
+        assert!(unstaged_section.contains("deleted:   gone.txt"), "{}", out);
+        assert!(unstaged_section.contains("deleted:   dir/nested.txt"), "{}", out);
     }
 }
\ No newline at end of file
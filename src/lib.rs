@@ -0,0 +1,41 @@
+// Library surface for rit. The CLI in main.rs is a thin wrapper over these
+// modules so other Rust tools (GUIs, scripts) can embed rit directly.
+
+pub mod archive;
+pub mod attributes;
+pub mod blame;
+pub mod database;
+pub mod date;
+pub mod index;
+pub mod tree;
+pub mod commit;
+pub mod compression;
+pub mod branches;
+pub mod checkout;
+pub mod repo;
+pub mod staging;
+pub mod constants;
+pub mod config;
+pub mod graph;
+pub mod hash;
+pub mod diff;
+pub mod export;
+pub mod gc;
+pub mod fsck;
+pub mod hooks;
+pub mod ignore;
+pub mod lock;
+pub mod merge;
+pub mod packed_refs;
+pub mod pretty;
+pub mod refs;
+pub mod reflog;
+pub mod reset;
+pub mod tag;
+pub mod text;
+
+mod base85;
+mod parallel;
+
+#[cfg(test)]
+mod test_utils;
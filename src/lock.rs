@@ -0,0 +1,93 @@
+// Lock files guard repository files against concurrent writers.
+// Like git, a writer creates `<file>.lock` exclusively, writes the new contents
+// into it and renames it over the original, so readers never see a partial file.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long to wait for another process to release a lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct LockFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<File>,
+}
+
+impl LockFile {
+    // Takes the lock for the given file, waiting while another writer holds it
+    pub fn acquire(path: &Path) -> io::Result<LockFile> {
+        let mut lock_name = path.as_os_str().to_owned();
+        lock_name.push(".lock");
+        let lock_path = PathBuf::from(lock_name);
+
+        let start = Instant::now();
+        let mut wait = Duration::from_millis(1);
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+                Ok(file) => {
+                    return Ok(LockFile {
+                        path: path.to_path_buf(),
+                        lock_path,
+                        file: Some(file),
+                    })
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        return Err(io::Error::new(
+                            io::ErrorKind::WouldBlock,
+                            format!("Unable to lock {}: {} exists. Another rit process may be running.", path.display(), lock_path.display()),
+                        ));
+                    }
+                    thread::sleep(wait);
+                    wait = (wait * 2).min(Duration::from_millis(50));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Writes the new contents and atomically replaces the locked file, releasing the lock
+    pub fn commit(mut self, data: &[u8]) -> io::Result<()> {
+        let mut file = self.file.take().expect("lock file is open until commit");
+        file.write_all(data)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&self.lock_path, &self.path)
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        // Release a lock that was never committed
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn test_lock_is_exclusive_and_released() {
+        let _test_dir = TestDir::new();
+        let path = Path::new("file");
+
+        let lock = LockFile::acquire(path).unwrap();
+        assert!(Path::new("file.lock").exists());
+        drop(lock);
+        assert!(!Path::new("file.lock").exists());
+        assert!(!path.exists());
+
+        let lock = LockFile::acquire(path).unwrap();
+        lock.commit(b"contents").unwrap();
+        assert!(!Path::new("file.lock").exists());
+        assert_eq!(fs::read(path).unwrap(), b"contents");
+    }
+}
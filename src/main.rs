@@ -1,22 +1,15 @@
-mod utility;
-mod database;
 mod args;
-mod constants;
-mod index;
-mod staging;
-mod repo;
-mod compression;
-mod hash;
-mod commit;
-mod tree;
-mod branches;
 
-
-use repo::{rit_init, rit_remove, check_repo_initialized};
-use args::{RitArgs, Commands};
+use rit::repo::{default_branch_name, rit_init_with, rit_reinit, InitOptions, rit_remove, check_repo_initialized};
+use rit::{archive, blame, branches, checkout, commit, config, constants, database, date, diff, export, fsck, gc, ignore, merge, packed_refs, pretty, refs, reflog, reset, staging, tag, text, tree};
+use args::{RitArgs, Commands, ReflogAction};
 use clap::Parser;
-use std::io;
-use crate::index::get_status;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rit::compression::CompressionCodec;
+use rit::hash::HashAlgo;
+use rit::index::{self, get_status_with, load_index, porcelain_v2_with, short_status, status_report_with};
 
 // 100644 for normal files.
 // 100755 for executable files.
@@ -36,8 +29,21 @@ fn main() -> io::Result<()> {
     
     // calls commands
     match args.command {
-        Commands::Init => {
-            rit_init()?;
+        Commands::Init(init_args) => {
+            let branch = default_branch_name(init_args.initial_branch.as_deref());
+            if init_args.reinit {
+                rit_reinit(&branch)?;
+            } else {
+                let mut options = InitOptions::default();
+                if let Some(format) = &init_args.object_format {
+                    options.object_format = HashAlgo::from_name(format)?;
+                }
+                if let Some(codec) = &init_args.compression {
+                    options.compression = CompressionCodec::from_name(codec)?;
+                }
+                options.compression_level = init_args.compression_level;
+                rit_init_with(&branch, &options)?;
+            }
         },
         Commands::Remove => {
             check_repo_initialized()?;
@@ -50,45 +56,549 @@ fn main() -> io::Result<()> {
         }
         Commands::CatFile(cat_args) => {
             check_repo_initialized()?;
-            let (_, _, data) = database::get_data(&cat_args.key)?;
-            println!("{}", String::from_utf8_lossy(&data));
+            let key = refs::resolve_ref(&cat_args.key)?;
+            if cat_args.pretty {
+                print!("{}", pretty::pretty_object(&key)?);
+                return Ok(());
+            }
+            let (object_type, object_size, data) = database::get_data(&key)?;
+            if cat_args.show_type {
+                println!("{}", object_type);
+            } else if cat_args.show_size {
+                println!("{}", object_size);
+            } else {
+                println!("{}", text::display_text(&data));
+            }
         },
         Commands::Blob(hash_args) => {
             check_repo_initialized()?;
-            let (object_type, _, data) = database::get_data(&hash_args.key)?;
-            if (object_type != constants::BLOB) {
+            let (object_type, _, data) = database::get_data(&refs::resolve_ref(&hash_args.key)?)?;
+            if object_type != constants::BLOB {
                 return Err(io::Error::new(io::ErrorKind::InvalidInput, "Object is not a blob"));
             }
-            println!("{}", String::from_utf8_lossy(&data));
+            println!("{}", text::display_text(&data));
         },
         Commands::Add(add_args) => {
             check_repo_initialized()?;
-            staging::add_file_to_staging(&add_args.file)?;
+            staging::add_file_to_staging_with(&add_args.file, add_args.force)?;
+        },
+        Commands::Rm(rm_args) => {
+            check_repo_initialized()?;
+            staging::remove_file_from_staging(&rm_args.file, rm_args.cached)?;
+            println!("rm '{}'", rm_args.file);
+        },
+        Commands::LsFiles(ls_args) => {
+            check_repo_initialized()?;
+            let terminator = if ls_args.nul_terminated { '\0' } else { '\n' };
+            let mut entries = load_index()?;
+            entries.sort();
+            for entry in entries {
+                // `<mode> <hash> <stage>\t<path>` as git prints it; rit has no merge stages
+                if ls_args.stage {
+                    print!("{:06o} {} 0\t{}{}", entry.mode, entry.blob_hash, entry.path, terminator);
+                } else {
+                    print!("{}{}", entry.path, terminator);
+                }
+            }
+        },
+        Commands::WriteTree => {
+            check_repo_initialized()?;
+            println!("{}", tree::create_tree(&load_index()?)?);
+        },
+        Commands::ReadTree(read_args) => {
+            check_repo_initialized()?;
+            let tree_hash = diff::tree_of(&refs::resolve_ref(&read_args.tree)?)?;
+            let entries = tree::flatten_tree(&tree_hash)?;
+            index::save_index_locked(index::lock_index()?, &entries)?;
         },
         Commands::LsTree(hash_args) => {
             check_repo_initialized()?;
-            let entries = tree::read_tree(&hash_args.key)?;
+            // A commit lists its root tree
+            let entries = tree::read_tree(&diff::tree_of(&refs::resolve_ref(&hash_args.key)?)?)?;
+            let terminator = if hash_args.nul_terminated { '\0' } else { '\n' };
             for entry in entries {
+                if hash_args.name_only {
+                    print!("{}{}", entry.name, terminator);
+                    continue;
+                }
                 // Print each entry in the format: "<mode> <type> <hash>\t<name>"
-                println!(
-                    "{:06o} {}\t{}\t{}",
+                print!(
+                    "{:06o} {}\t{}\t{}{}",
                     entry.mode,
                     entry.object_type,
                     entry.hash,
-                    entry.name
+                    entry.name,
+                    terminator
                 );
             }
         },
         Commands::Commit(commit_args) => {
             check_repo_initialized()?;
-            let commit_hash = commit::commit(&commit_args.message, &commit_args.committer)?;
-            println!("{}", commit_hash);
+            if commit_args.dry_run {
+                let preview = commit::commit_dry_run()?;
+                println!("tree {}", preview.tree);
+                match &preview.parent {
+                    Some(parent) => println!("parent {}", parent),
+                    None => println!("(root commit)"),
+                }
+                if preview.changes.is_empty() {
+                    println!("nothing to commit");
+                } else {
+                    println!("Changes to be committed:");
+                    for change in &preview.changes {
+                        println!("\t{}:   {}", change.status.label(), change.path);
+                    }
+                }
+                return Ok(());
+            }
+            // Collect the patch before committing, while the index still differs from HEAD
+            let mut patch = String::new();
+            if commit_args.verbose {
+                for change in diff::index_changes()? {
+                    patch.push_str(&diff::path_patch(&change)?);
+                }
+            }
+            let timezone = match &commit_args.committer_tz {
+                Some(tz) => Some(date::parse_offset(tz)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid timezone '{}': expected +HHMM", tz)))?),
+                None => None,
+            };
+            let options = commit::CommitOptions {
+                timezone,
+                allow_invalid_identity: commit_args.allow_invalid_identity,
+                allow_empty: commit_args.allow_empty,
+                author: commit_args.author.clone(),
+            };
+            let commit_hash = commit::commit_with_options(&commit_args.message, commit_args.committer.as_deref().unwrap_or(""), &options)?;
+            if !commit_args.quiet {
+                println!("{}", commit_hash);
+            }
+            print!("{}", patch);
+            // Keep the store tidy once it grows past gc.auto. The commit is already made, so a
+            // failed gc is only worth a warning; quiet commits leave housekeeping for later
+            if !commit_args.quiet {
+                match gc::gc_auto() {
+                    Ok(Some(stats)) => eprintln!("Auto gc pruned {} unreachable objects", stats.pruned.len()),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("warning: auto gc failed: {}", e),
+                }
+            }
+        },
+        Commands::Log(log_args) => {
+            check_repo_initialized()?;
+            for (hash, commit) in commit::log_with(log_args.reverse, log_args.topo_order)? {
+                if (log_args.merges && !commit.is_merge()) || (log_args.no_merges && commit.is_merge()) {
+                    continue;
+                }
+                println!("commit {}", hash);
+                if commit.is_merge() {
+                    let parents: Vec<String> = commit.parents().iter().map(|parent| parent[..8.min(parent.len())].to_string()).collect();
+                    println!("Merge:     {}", parents.join(" "));
+                }
+                if commit.author != commit.committer {
+                    println!("Author:    {}", commit.author);
+                }
+                println!("Committer: {}", commit.committer);
+                match log_args.date.as_deref() {
+                    Some("raw") => println!("Date:      {} {}", commit.timestamp, date::format_offset(commit.timezone)),
+                    _ => println!("Date:      {}", date::format_iso(commit.timestamp, commit.timezone)),
+                }
+                println!("\n    {}\n", commit.message);
+                if log_args.patch {
+                    println!("{}", diff::commit_patch(&hash)?);
+                }
+            }
+        },
+        Commands::Show(show_args) => {
+            check_repo_initialized()?;
+            let hash = refs::resolve_commit(&show_args.commit)?;
+            let commit = commit::read_commit(&hash)?;
+            println!("commit {}", hash);
+            println!("tree {}", commit.tree);
+            for parent in commit.parents() {
+                println!("parent {}", parent);
+            }
+            if commit.author != commit.committer {
+                println!("Author:    {}", commit.author);
+            }
+            println!("Committer: {}", commit.committer);
+            println!("Date:      {}", date::format_iso(commit.timestamp, commit.timezone));
+            println!();
+            for line in commit.message.lines() {
+                println!("    {}", line);
+            }
+            println!();
+            // The root commit is compared with the empty tree, so all its files show as added
+            for change in diff::commit_changed_paths(&hash)? {
+                if show_args.name_status {
+                    println!("{}\t{}", change.status.code(), change.path);
+                } else {
+                    print!("{}", diff::path_patch(&change)?);
+                }
+            }
+        },
+        Commands::Status(status_args) => {
+            check_repo_initialized()?;
+            let show_untracked = status_args.untracked_files != "no";
+            let report = if status_args.porcelain.is_some() || status_args.nul_terminated {
+                let report = status_report_with(&status_args.pathspecs, show_untracked)?;
+                let terminator = if status_args.nul_terminated { '\0' } else { '\n' };
+                print!("{}", porcelain_v2_with(&report, status_args.branch, terminator));
+                report
+            } else if status_args.short {
+                let report = status_report_with(&status_args.pathspecs, show_untracked)?;
+                print!("{}", short_status(&report, status_args.branch));
+                report
+            } else {
+                get_status_with(status_args.compare.as_deref(), &status_args.pathspecs, show_untracked)?
+            };
+            if status_args.exit_code && !report.is_clean() {
+                std::process::exit(1);
+            }
+        },
+        Commands::Gc(gc_args) => {
+            check_repo_initialized()?;
+            let show_stat = gc_args.stat || (!gc_args.no_stat && io::stdout().is_terminal());
+            if gc_args.prune_reflog {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                let expired = reflog::expire(
+                    now,
+                    gc_args.expire * reflog::SECONDS_PER_DAY,
+                    gc_args.expire_unreachable * reflog::SECONDS_PER_DAY,
+                )?;
+                if show_stat {
+                    println!("Reflog entries expired: {}", expired);
+                }
+            }
+            if gc_args.dry_run {
+                let stats = gc::gc_with(true)?;
+                for key in &stats.pruned {
+                    println!("Would remove {}", key);
+                }
+                if show_stat {
+                    stats.print();
+                }
+                return Ok(());
+            }
+            let stats = if gc_args.auto { gc::gc_auto()? } else { Some(gc::gc()?) };
+            if let (Some(stats), true) = (stats, show_stat) {
+                stats.print();
+            }
+        },
+        Commands::Reset(reset_args) => {
+            check_repo_initialized()?;
+            if (reset_args.hard || reset_args.mixed) && (reset_args.args.len() > 1 || !reset_args.paths.is_empty()) {
+                let mode = if reset_args.hard { "hard" } else { "mixed" };
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot do a {} reset with paths", mode)));
+            }
+            // Without paths, reset moves the branch: `--mixed` unless `--hard` is given
+            if reset_args.paths.is_empty() && reset_args.args.len() <= 1 {
+                let target = reset_args.args.first().map(String::as_str).unwrap_or("HEAD");
+                if reset_args.hard {
+                    let commit_hash = reset::reset_hard(target)?;
+                    let summary = commit::read_commit(&commit_hash)?.message.lines().next().unwrap_or("").to_string();
+                    println!("HEAD is now at {} {}", &commit_hash[..8.min(commit_hash.len())], summary);
+                    return Ok(());
+                }
+                if reset_args.mixed || refs::resolve_ref(target).is_ok() {
+                    reset::reset_mixed(target)?;
+                    print_unstaged_after_reset()?;
+                    return Ok(());
+                }
+                // Before the first commit there is nothing to move, so a bare reset unstages everything
+                if reset_args.args.is_empty() {
+                    reset::reset_paths(None, &[".".to_string()])?;
+                    print_unstaged_after_reset()?;
+                    return Ok(());
+                }
+            }
+            // `<commit> -- <paths>` is explicit; otherwise a leading revision is the commit
+            let (target, paths) = if !reset_args.paths.is_empty() {
+                if reset_args.args.len() > 1 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "Only one commit can be given before `--`"));
+                }
+                (reset_args.args.first().cloned(), reset_args.paths)
+            } else {
+                match reset_args.args.split_first() {
+                    Some((first, rest)) if refs::resolve_ref(first).is_ok() => (Some(first.clone()), rest.to_vec()),
+                    _ => (None, reset_args.args),
+                }
+            };
+            reset::reset_paths(target.as_deref(), &paths)?;
+            print_unstaged_after_reset()?;
+        },
+        Commands::Fsck(fsck_args) => {
+            check_repo_initialized()?;
+            let report = fsck::fsck()?;
+            for object in &report.corrupt {
+                eprintln!("error: {}", object.error);
+                println!("corrupt {}", object.hash);
+            }
+            for object in &report.missing {
+                println!("missing {} (referenced by {})", object.hash, object.referenced_by);
+            }
+            for object in &report.dangling {
+                println!("dangling {} {}", object.object_type, object.hash);
+            }
+            if fsck_args.lost_found {
+                fsck::write_lost_found(&report)?;
+            }
+            if report.has_problems() {
+                std::process::exit(1);
+            }
+        },
+        Commands::DiffTree(diff_args) => {
+            check_repo_initialized()?;
+            let old_tree = diff::tree_of(&refs::resolve_ref(&diff_args.old)?)?;
+            let new_tree = diff::tree_of(&refs::resolve_ref(&diff_args.new)?)?;
+            let changes = diff::changed_paths(&old_tree, &new_tree)?;
+            for change in diff::detect_renames(changes, diff_args.find_renames)? {
+                match (&change.status, &change.old) {
+                    (diff::ChangeStatus::Renamed(_), Some(old)) => {
+                        println!("{}\t{}\t{}", change.status.code(), old.path, change.path)
+                    }
+                    _ => println!("{}\t{}", change.status.code(), change.path),
+                }
+            }
+        },
+        Commands::Diff(diff_args) => {
+            check_repo_initialized()?;
+            let changes = if diff_args.cached { diff::index_changes()? } else { diff::worktree_changes()? };
+            if diff_args.check {
+                let mut found = false;
+                for change in &changes {
+                    for error in diff::check_whitespace(change, !diff_args.cached)? {
+                        found = true;
+                        println!("{}:{}: {}", error.path, error.line, error.problem);
+                        println!("+{}", error.content);
+                    }
+                }
+                // Like git, whitespace errors exit with status 2
+                if found {
+                    std::process::exit(2);
+                }
+            } else if diff_args.raw {
+                for change in &changes {
+                    println!("{}", diff::raw_line(change));
+                }
+            } else {
+                for change in &changes {
+                    print!("{}", diff::path_patch_with(change, !diff_args.cached, diff_args.binary)?);
+                }
+            }
+        },
+        Commands::CheckIgnore(check_args) => {
+            check_repo_initialized()?;
+            let rules = ignore::IgnoreRules::load()?;
+            let mut any_ignored = false;
+            for path in &check_args.paths {
+                let is_dir = Path::new(path).is_dir();
+                match rules.matching(path, is_dir) {
+                    Some(pattern) if !pattern.negated => {
+                        any_ignored = true;
+                        if check_args.verbose {
+                            println!("{}:{}:{}\t{}", pattern.source.display(), pattern.line, pattern.pattern, path);
+                        } else {
+                            println!("{}", path);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // Like git, exit 1 when none of the paths are ignored
+            if !any_ignored {
+                std::process::exit(1);
+            }
         },
-        Commands::Status => {
+        Commands::Branch(branch_args) => {
+            check_repo_initialized()?;
+            let current = branches::get_current_branch_name();
+            if let Some(upstream) = &branch_args.set_upstream_to {
+                let branch = branch_args.name.clone().or(current)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "HEAD is not on a branch"))?;
+                branches::set_upstream(&branch, upstream)?;
+                println!("branch '{}' set up to track '{}'.", branch, upstream);
+            } else if let Some(name) = &branch_args.name {
+                let head = branches::get_current_branch_commit_hash()?
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Cannot create a branch before the first commit"))?;
+                if branches::get_commit_hash(name)?.is_some() {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("A branch named '{}' already exists", name)));
+                }
+                branches::create_banch(name, &head)?;
+            } else {
+                for branch in branches::list_branches()? {
+                    let marker = if current.as_deref() == Some(branch.as_str()) { "*" } else { " " };
+                    println!("{} {}", marker, branch);
+                }
+            }
+        },
+        Commands::Reflog(reflog_args) => {
+            check_repo_initialized()?;
+            match reflog_args.action.unwrap_or(ReflogAction::Show { reference: constants::HEAD_FILE.to_string() }) {
+                ReflogAction::Show { reference } => {
+                    let entries = reflog::read_reflog(&reflog::ref_log_name(&reference))?;
+                    for (index, entry) in entries.iter().rev().enumerate() {
+                        println!("{} {}@{{{}}}: {}", entry.new, reference, index, entry.message);
+                    }
+                },
+                ReflogAction::Expire { refs, .. } => {
+                    let ref_names: Vec<String> = refs.iter().map(|name| reflog::ref_log_name(name)).collect();
+                    reflog::expire_all(&ref_names)?;
+                },
+                ReflogAction::Delete { entries } => {
+                    reflog::delete_entries(&entries)?;
+                },
+            }
+        },
+        Commands::ImportArchive(import_args) => {
+            check_repo_initialized()?;
+            // Settle who commits before staging anything, so a missing identity changes nothing
+            let committer = match &import_args.message {
+                Some(_) => Some(commit::resolve_committer(import_args.committer.as_deref().unwrap_or(""))?),
+                None => None,
+            };
+            let file = std::fs::File::open(&import_args.archive)?;
+            let entries = archive::import_tar(io::BufReader::new(file))?;
+            println!("Imported {} files from {}", entries.len(), import_args.archive);
+            if let (Some(message), Some(committer)) = (&import_args.message, &committer) {
+                println!("{}", commit::commit(message, committer)?);
+            }
+        },
+        Commands::Blame(blame_args) => {
+            check_repo_initialized()?;
+            let (rev, path) = match blame_args.args.as_slice() {
+                [rev, path] => (rev.as_str(), path.as_str()),
+                [path] => (constants::HEAD_FILE, path.as_str()),
+                _ => unreachable!("clap requires one or two arguments"),
+            };
+            let lines = blame::blame(rev, path)?;
+            if blame_args.porcelain {
+                print!("{}", blame::porcelain(&lines, path)?);
+            } else {
+                print!("{}", blame::human(&lines)?);
+            }
+        },
+        Commands::Checkout(checkout_args) => {
+            check_repo_initialized()?;
+            if let Some(branch) = &checkout_args.new_branch {
+                checkout::switch_branch(branch, true, checkout_args.force)?;
+                println!("Switched to a new branch '{}'", branch);
+            } else if let Some(commit) = &checkout_args.commit {
+                if checkout_args.paths.is_empty() && refs::validate_ref_name(commit).is_ok() && branches::get_commit_hash(commit)?.is_some() {
+                    // A branch name switches to the branch rather than detaching HEAD at its tip
+                    checkout::switch_branch(commit, false, checkout_args.force)?;
+                    println!("Switched to branch '{}'", commit);
+                } else if checkout_args.paths.is_empty() {
+                    let commit_hash = checkout::checkout_commit(commit, checkout_args.force)?;
+                    println!("HEAD is now detached at {}", commit_hash);
+                } else {
+                    let restored = checkout::checkout_paths(commit, &checkout_args.paths, checkout_args.index, checkout_args.force)?;
+                    println!("Updated {} path{} from {}", restored.len(), if restored.len() == 1 { "" } else { "s" }, commit);
+                }
+            }
+        }
+        Commands::Config(config_args) => {
+            check_repo_initialized()?;
+            if config_args.list {
+                for (key, value) in config::Config::load()?.entries() {
+                    println!("{}={}", key, value);
+                }
+            } else if let Some(key) = &config_args.key {
+                if config_args.unset {
+                    let mut config = config::Config::load()?;
+                    // Like git, unsetting a missing option exits with status 5
+                    if !config.unset(key)? {
+                        std::process::exit(5);
+                    }
+                    config.save()?;
+                } else if let Some(value) = &config_args.value {
+                    config::set_value(key, value)?;
+                } else {
+                    // A missing option prints nothing and exits 1, so scripts can test for it
+                    match config::get_value(key)? {
+                        Some(value) => println!("{}", value),
+                        None => std::process::exit(1),
+                    }
+                }
+            }
+        }
+        Commands::Switch(switch_args) => {
+            check_repo_initialized()?;
+            checkout::switch_branch(&switch_args.branch, switch_args.create, switch_args.force)?;
+            if switch_args.create {
+                println!("Switched to a new branch '{}'", switch_args.branch);
+            } else {
+                println!("Switched to branch '{}'", switch_args.branch);
+            }
+        }
+        Commands::PackRefs(pack_args) => {
+            check_repo_initialized()?;
+            packed_refs::pack_refs(!pack_args.no_prune)?;
+        }
+        Commands::Tag(tag_args) => {
             check_repo_initialized()?;
-            get_status();
+            match (&tag_args.name, &tag_args.message) {
+                (None, _) => {
+                    for name in tag::list_tags()? {
+                        println!("{}", name);
+                    }
+                }
+                (Some(name), None) => {
+                    tag::create_lightweight_tag(name, &tag_args.commit, tag_args.force)?;
+                }
+                (Some(name), Some(message)) => {
+                    let tagger = commit::resolve_committer(tag_args.tagger.as_deref().unwrap_or(""))?;
+                    commit::validate_identity(&tagger, false)?;
+                    tag::create_annotated_tag_with(name, &tag_args.commit, &tagger, message, tag_args.force)?;
+                }
+            }
+        }
+        Commands::VerifyTag(verify_args) => {
+            check_repo_initialized()?;
+            let tag = tag::verify_tag(&verify_args.name)?;
+            println!("object {}\ntype {}\ntag {}", tag.object, tag.object_type, tag.name);
+        }
+        Commands::ExportGit(export_args) => {
+            check_repo_initialized()?;
+            let summary = export::export_git(std::path::Path::new(&export_args.path))?;
+            println!(
+                "Exported {} objects, {} branches and {} tags to {}",
+                summary.objects, summary.branches, summary.tags, export_args.path
+            );
+        }
+        Commands::Merge(merge_args) => {
+            check_repo_initialized()?;
+            if merge_args.abort {
+                merge::abort()?;
+                return Ok(());
+            }
+            let branch = merge_args.branch.as_deref().expect("clap requires a branch without --abort");
+            match merge::start_merge_with(branch, merge_args.committer.as_deref().unwrap_or(""))? {
+                merge::MergeOutcome::UpToDate => println!("Already up to date."),
+                merge::MergeOutcome::FastForward(commit_hash) => println!("Fast-forward to {}", commit_hash),
+                merge::MergeOutcome::MergeCommit(commit_hash) => println!("Merge made by commit {}", commit_hash),
+                merge::MergeOutcome::Merged { conflicts } => {
+                    for path in &conflicts {
+                        println!("CONFLICT (content): Merge conflict in {}", path);
+                    }
+                    println!("Automatic merge failed; fix conflicts, or run `rit merge --abort` to abandon the merge.");
+                    std::process::exit(1);
+                }
+            }
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Lists the tracked files that differ from the index, as git does after a mixed reset
+fn print_unstaged_after_reset() -> io::Result<()> {
+    let changes = diff::worktree_changes()?;
+    if !changes.is_empty() {
+        println!("Unstaged changes after reset:");
+        for change in changes {
+            println!("{}\t{}", change.status.code(), change.path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,486 @@
+// Merges another branch into the current one. A clean merge is committed at once with
+// both tips as parents; one that cannot finish on its own stops with conflict markers in
+// the working tree and its state recorded in `.rit/MERGE_HEAD`, `.rit/MERGE_MSG` and
+// `.rit/ORIG_HEAD`, until it is aborted.
+
+use crate::attributes::is_binary_path;
+use crate::branches;
+use crate::checkout;
+use crate::commit::{self, read_commit, CommitOptions};
+use crate::constants::{merge_head_path, merge_msg_path, orig_head_path, BLOB};
+use crate::database;
+use crate::diff::{blob_text, diff_lines, index_changes, worktree_changes, LineOp};
+use crate::graph::merge_base;
+use crate::index::{self, load_index, IndexEntry};
+use crate::refs::resolve_commit;
+use crate::tree::flatten_tree;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+
+const CONFLICTS_HEADER: &str = "# Conflicts:";
+
+#[derive(Debug, PartialEq)]
+pub enum MergeOutcome {
+    // The other branch is already contained in the current one
+    UpToDate,
+    // The current branch was moved forward to the given commit
+    FastForward(String),
+    // Both sides were combined cleanly and committed as the given merge commit
+    MergeCommit(String),
+    // Both sides were combined in the index and working tree, stopping before the commit
+    // because of the conflicted paths
+    Merged { conflicts: Vec<String> },
+}
+
+// The recorded state of a merge in progress
+#[derive(Debug, PartialEq)]
+pub struct MergeState {
+    pub merge_head: String,
+    pub orig_head: String,
+    pub message: String,
+}
+
+impl MergeState {
+    // The paths the merge left conflicted, as listed in its message
+    pub fn conflicts(&self) -> Vec<String> {
+        self.message
+            .lines()
+            .skip_while(|line| *line != CONFLICTS_HEADER)
+            .filter_map(|line| line.strip_prefix("#\t"))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+pub fn in_progress() -> bool {
+    merge_head_path().exists()
+}
+
+// Reads the state of the merge in progress, if there is one
+pub fn read_state() -> io::Result<Option<MergeState>> {
+    if !in_progress() {
+        return Ok(None);
+    }
+    Ok(Some(MergeState {
+        merge_head: fs::read_to_string(merge_head_path())?.trim().to_string(),
+        orig_head: fs::read_to_string(orig_head_path())?.trim().to_string(),
+        message: fs::read_to_string(merge_msg_path())?,
+    }))
+}
+
+fn write_state(state: &MergeState) -> io::Result<()> {
+    fs::write(orig_head_path(), format!("{}\n", state.orig_head))?;
+    fs::write(merge_msg_path(), &state.message)?;
+    // MERGE_HEAD goes last since its presence is what marks the merge as in progress
+    fs::write(merge_head_path(), format!("{}\n", state.merge_head))
+}
+
+pub(crate) fn clear_state() -> io::Result<()> {
+    for path in [merge_head_path(), merge_msg_path()] {
+        match fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+// A run of base lines [start, end) that one side replaced with `lines`
+struct Hunk<'a> {
+    start: usize,
+    end: usize,
+    lines: Vec<&'a str>,
+}
+
+fn hunks<'a>(base: &[&'a str], side: &[&'a str]) -> Vec<Hunk<'a>> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut base_index = 0;
+    let mut in_hunk = false;
+    for op in diff_lines(base, side) {
+        match op {
+            LineOp::Equal(_) => {
+                base_index += 1;
+                in_hunk = false;
+                continue;
+            }
+            _ if !in_hunk => {
+                hunks.push(Hunk { start: base_index, end: base_index, lines: Vec::new() });
+                in_hunk = true;
+            }
+            _ => {}
+        }
+        let hunk = hunks.last_mut().expect("a hunk was just opened");
+        match op {
+            LineOp::Delete(_) => {
+                base_index += 1;
+                hunk.end = base_index;
+            }
+            LineOp::Insert(line) => hunk.lines.push(line),
+            LineOp::Equal(_) => unreachable!(),
+        }
+    }
+    hunks
+}
+
+// One side's version of the base lines [start, end), given that side's hunks within it
+fn apply_hunks<'a>(base: &[&'a str], start: usize, end: usize, hunks: &[&Hunk<'a>]) -> Vec<&'a str> {
+    let mut lines = Vec::new();
+    let mut index = start;
+    for hunk in hunks {
+        lines.extend_from_slice(&base[index..hunk.start]);
+        lines.extend_from_slice(&hunk.lines);
+        index = hunk.end;
+    }
+    lines.extend_from_slice(&base[index..end]);
+    lines
+}
+
+fn push_conflict_side(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+// Merges two texts descended from `base` line by line. Changes to separate regions combine;
+// regions both sides changed differently are wrapped in conflict markers. Returns the merged
+// text and whether it has conflicts.
+pub fn merge_texts(base: &str, ours: &str, theirs: &str, our_label: &str, their_label: &str) -> (String, bool) {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let our_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let their_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    // Hunks from both sides, ordered by where they start in the base (ours first on ties)
+    let mut all: Vec<(bool, Hunk)> = hunks(&base_lines, &our_lines).into_iter().map(|h| (true, h)).collect();
+    all.extend(hunks(&base_lines, &their_lines).into_iter().map(|h| (false, h)));
+    all.sort_by_key(|(is_ours, hunk)| (hunk.start, !*is_ours));
+
+    let mut out = String::new();
+    let mut conflicted = false;
+    let mut base_index = 0;
+    let mut next = 0;
+    while next < all.len() {
+        // Gather every hunk that overlaps or touches the region so far
+        let start = all[next].1.start;
+        let mut end = all[next].1.end;
+        let mut last = next + 1;
+        while last < all.len() && all[last].1.start <= end {
+            end = end.max(all[last].1.end);
+            last += 1;
+        }
+        let cluster = &all[next..last];
+        next = last;
+
+        out.extend(base_lines[base_index..start].iter().copied());
+        base_index = end;
+
+        let ours_hunks: Vec<&Hunk> = cluster.iter().filter(|(is_ours, _)| *is_ours).map(|(_, h)| h).collect();
+        let theirs_hunks: Vec<&Hunk> = cluster.iter().filter(|(is_ours, _)| !*is_ours).map(|(_, h)| h).collect();
+        let our_version = apply_hunks(&base_lines, start, end, &ours_hunks);
+        let their_version = apply_hunks(&base_lines, start, end, &theirs_hunks);
+
+        if theirs_hunks.is_empty() || our_version == their_version {
+            out.extend(our_version);
+        } else if ours_hunks.is_empty() {
+            out.extend(their_version);
+        } else {
+            conflicted = true;
+            out.push_str(&format!("<<<<<<< {}\n", our_label));
+            push_conflict_side(&mut out, &our_version);
+            out.push_str("=======\n");
+            push_conflict_side(&mut out, &their_version);
+            out.push_str(&format!(">>>>>>> {}\n", their_label));
+        }
+    }
+    out.extend(base_lines[base_index..].iter().copied());
+
+    (out, conflicted)
+}
+
+fn same(a: Option<&IndexEntry>, b: Option<&IndexEntry>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.blob_hash == b.blob_hash && a.mode == b.mode,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn entries_by_path(commit_hash: &str) -> io::Result<HashMap<String, IndexEntry>> {
+    Ok(flatten_tree(&read_commit(commit_hash)?.tree)?.into_iter().map(|e| (e.path.clone(), e)).collect())
+}
+
+// Starts merging `branch` into the current branch as the configured user
+pub fn start_merge(branch: &str) -> io::Result<MergeOutcome> {
+    start_merge_with(branch, "")
+}
+
+// Merges `branch` into the current branch. A fast-forward completes at once, and a merge
+// without conflicts is committed by `committer` (the configured user when empty). Any other
+// merge is applied to the index and working tree and stops before committing, with
+// conflicted paths holding both sides between conflict markers.
+pub fn start_merge_with(branch: &str, committer: &str) -> io::Result<MergeOutcome> {
+    if in_progress() {
+        return Err(io::Error::other("You have not concluded your merge (MERGE_HEAD exists); run `rit merge --abort` to abandon it"));
+    }
+    let head = branches::get_current_branch_commit_hash()?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Cannot merge: the current branch has no commits yet"))?;
+    let theirs = resolve_commit(branch)?;
+    if !index_changes()?.is_empty() || !worktree_changes()?.is_empty() {
+        return Err(io::Error::other("Your local changes would be overwritten by merge; commit them before merging"));
+    }
+
+    let base = merge_base(&head, &theirs)?;
+    if base.as_deref() == Some(theirs.as_str()) {
+        return Ok(MergeOutcome::UpToDate);
+    }
+
+    let lock = index::lock_index()?;
+    let our_entries = flatten_tree(&read_commit(&head)?.tree)?;
+
+    if base.as_deref() == Some(head.as_str()) {
+        let their_entries = flatten_tree(&read_commit(&theirs)?.tree)?;
+        checkout::refuse_untracked_overwrites(&our_entries, &their_entries, "merge")?;
+        checkout::switch_entries(&our_entries, &their_entries)?;
+        index::save_index_locked(lock, &their_entries)?;
+        branches::update_current_branch(&theirs)?;
+        return Ok(MergeOutcome::FastForward(theirs));
+    }
+
+    let base_map = match &base {
+        Some(base) => entries_by_path(base)?,
+        None => HashMap::new(),
+    };
+    let our_map: HashMap<String, IndexEntry> = our_entries.iter().map(|e| (e.path.clone(), e.clone())).collect();
+    let their_map = entries_by_path(&theirs)?;
+    let paths: BTreeSet<&String> = base_map.keys().chain(our_map.keys()).chain(their_map.keys()).collect();
+
+    let mut merged = Vec::new();
+    let mut conflict_files = Vec::new();
+    let mut conflicts = Vec::new();
+    for path in paths {
+        let (b, o, t) = (base_map.get(path), our_map.get(path), their_map.get(path));
+        if same(o, t) || same(t, b) {
+            merged.extend(o.cloned());
+        } else if same(o, b) {
+            merged.extend(t.cloned());
+        } else {
+            // Both sides changed the path; only text changed on both sides can be combined,
+            // unless the attributes mark it binary
+            let texts = match (o, t) {
+                (Some(o), Some(t)) if !is_binary_path(path)? => {
+                    let base_text = match b {
+                        Some(b) => blob_text(&b.blob_hash)?,
+                        None => Some(String::new()),
+                    };
+                    match (base_text, blob_text(&o.blob_hash)?, blob_text(&t.blob_hash)?) {
+                        (Some(base_text), Some(our_text), Some(their_text)) => Some((o, t, base_text, our_text, their_text)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            let (o, t, base_text, our_text, their_text) = match texts {
+                Some(texts) => texts,
+                None => {
+                    // Keep our version and let the user decide
+                    merged.extend(o.cloned());
+                    conflicts.push(path.clone());
+                    continue;
+                }
+            };
+
+            let (text, conflicted) = merge_texts(&base_text, &our_text, &their_text, "HEAD", branch);
+            let mode = if b.is_some_and(|b| b.mode == o.mode) { t.mode } else { o.mode };
+            if conflicted {
+                // The index keeps our version; the marked-up file goes only to the working tree
+                merged.push(o.clone());
+                conflict_files.push(IndexEntry { mode, blob_hash: database::store_data(text.as_bytes(), BLOB)?, path: path.clone() });
+                conflicts.push(path.clone());
+            } else {
+                merged.push(IndexEntry { mode, blob_hash: database::store_data(text.as_bytes(), BLOB)?, path: path.clone() });
+            }
+        }
+    }
+
+    checkout::refuse_untracked_overwrites(&our_entries, &merged, "merge")?;
+    checkout::switch_entries(&our_entries, &merged)?;
+    for marked in &conflict_files {
+        checkout::write_entry(marked)?;
+    }
+    index::save_index_locked(lock, &merged)?;
+
+    let mut message = format!("Merge branch '{}'\n", branch);
+    if !conflicts.is_empty() {
+        message.push_str(&format!("\n{}\n", CONFLICTS_HEADER));
+        for path in &conflicts {
+            message.push_str(&format!("#\t{}\n", path));
+        }
+    }
+    let clean = conflicts.is_empty();
+    write_state(&MergeState { merge_head: theirs, orig_head: head, message: message.clone() })?;
+    if !clean {
+        return Ok(MergeOutcome::Merged { conflicts });
+    }
+
+    // Concluding the merge commits the merged tree with the other tip as second parent; if
+    // that fails the merge stays in progress, to be committed or aborted by hand
+    let commit_hash = commit::commit_with_options(message.trim_end(), committer, &CommitOptions::default())?;
+    Ok(MergeOutcome::MergeCommit(commit_hash))
+}
+
+// Abandons the merge in progress, restoring the index and working tree to the commit
+// the merge started from and removing the merge state
+pub fn abort() -> io::Result<()> {
+    let state = read_state()?.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "There is no merge to abort (MERGE_HEAD missing)"))?;
+
+    let lock = index::lock_index()?;
+    let orig_entries = flatten_tree(&read_commit(&state.orig_head)?.tree)?;
+    checkout::switch_entries(&load_index()?, &orig_entries)?;
+    index::save_index_locked(lock, &orig_entries)?;
+
+    clear_state()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::commit;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::{commit_file, TestDir};
+
+    // Points HEAD at a branch and brings the index and working tree to its commit
+    fn switch_to(branch: &str) {
+        let from = load_index().unwrap();
+        fs::write(crate::constants::head_file_path(), format!("ref: refs/heads/{}\n", branch)).unwrap();
+        let to = flatten_tree(&read_commit(&resolve_commit(branch).unwrap()).unwrap().tree).unwrap();
+        checkout::switch_entries(&from, &to).unwrap();
+        index::save_index(&to).unwrap();
+    }
+
+    #[test]
+    fn test_merge_texts() {
+        let base = "one\ntwo\nthree\nfour\n";
+        // Changes to separate lines combine cleanly
+        let (merged, conflicted) = merge_texts(base, "ONE\ntwo\nthree\nfour\n", "one\ntwo\nthree\nFOUR\n", "HEAD", "topic");
+        assert_eq!(merged, "ONE\ntwo\nthree\nFOUR\n");
+        assert!(!conflicted);
+
+        // The same change on both sides is not a conflict
+        let (merged, conflicted) = merge_texts(base, "one\n2\nthree\nfour\n", "one\n2\nthree\nfour\n", "HEAD", "topic");
+        assert_eq!(merged, "one\n2\nthree\nfour\n");
+        assert!(!conflicted);
+
+        let (merged, conflicted) = merge_texts(base, "one\nours\nthree\nfour\n", "one\ntheirs\nthree\nfour\n", "HEAD", "topic");
+        assert_eq!(merged, "one\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\nthree\nfour\n");
+        assert!(conflicted);
+    }
+
+    #[test]
+    fn test_conflicting_merge_and_abort() {
+        let _test_dir = TestDir::with_repo();
+        commit_file("shared.txt", "line one\nline two\n", "base");
+        let base = commit_file("keep.txt", "unchanged\n", "keep");
+        branches::create_banch("topic", &base).unwrap();
+
+        let ours = commit_file("shared.txt", "line one\nours\n", "ours");
+        switch_to("topic");
+        fs::write("added.txt", "from topic\n").unwrap();
+        add_file_to_staging("added.txt").unwrap();
+        let theirs = commit_file("shared.txt", "line one\ntheirs\n", "theirs");
+        switch_to("master");
+
+        let outcome = start_merge("topic").unwrap();
+        assert_eq!(outcome, MergeOutcome::Merged { conflicts: vec!["shared.txt".to_string()] });
+        assert_eq!(fs::read_to_string("shared.txt").unwrap(), "line one\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\n");
+        assert_eq!(fs::read_to_string("added.txt").unwrap(), "from topic\n");
+
+        let state = read_state().unwrap().unwrap();
+        assert_eq!(state.merge_head, theirs);
+        assert_eq!(state.orig_head, ours);
+        assert_eq!(state.conflicts(), vec!["shared.txt"]);
+        assert!(start_merge("topic").is_err());
+
+        abort().unwrap();
+        assert!(!in_progress());
+        assert!(!merge_msg_path().exists());
+        assert_eq!(fs::read_to_string("shared.txt").unwrap(), "line one\nours\n");
+        assert!(!std::path::Path::new("added.txt").exists());
+        assert!(index_changes().unwrap().is_empty());
+        assert!(worktree_changes().unwrap().is_empty());
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), Some(ours));
+        assert!(abort().is_err());
+    }
+
+    #[test]
+    fn test_clean_merge_commits_with_two_parents() {
+        let _test_dir = TestDir::with_repo();
+        commit_file("shared.txt", "one\ntwo\nthree\n", "base");
+        let base = commit_file("keep.txt", "unchanged\n", "keep");
+        branches::create_banch("topic", &base).unwrap();
+
+        let ours = commit_file("shared.txt", "ONE\ntwo\nthree\n", "ours");
+        switch_to("topic");
+        commit_file("added.txt", "from topic\n", "add");
+        let theirs = commit_file("shared.txt", "one\ntwo\nTHREE\n", "theirs");
+        switch_to("master");
+
+        let merge_commit = match start_merge_with("topic", "Merger <merger@example.com>").unwrap() {
+            MergeOutcome::MergeCommit(hash) => hash,
+            other => panic!("expected a merge commit, got {:?}", other),
+        };
+        let merged = read_commit(&merge_commit).unwrap();
+        assert_eq!(merged.parents(), vec![ours, theirs.clone()]);
+        assert_eq!(merged.message, "Merge branch 'topic'");
+        assert_eq!(merged.committer, "Merger <merger@example.com>");
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), Some(merge_commit));
+
+        // Each side's change is kept, and the merge is over
+        assert_eq!(fs::read_to_string("shared.txt").unwrap(), "ONE\ntwo\nTHREE\n");
+        assert_eq!(fs::read_to_string("added.txt").unwrap(), "from topic\n");
+        assert!(!in_progress());
+        assert!(index_changes().unwrap().is_empty());
+        assert!(worktree_changes().unwrap().is_empty());
+        assert_eq!(start_merge("topic").unwrap(), MergeOutcome::UpToDate);
+    }
+
+    #[test]
+    fn test_clean_merge_without_committer_stays_in_progress() {
+        let _test_dir = TestDir::with_repo();
+        let base = commit_file("a.txt", "a\n", "base");
+        branches::create_banch("topic", &base).unwrap();
+        let ours = commit_file("b.txt", "b\n", "ours");
+        switch_to("topic");
+        let theirs = commit_file("c.txt", "c\n", "theirs");
+        switch_to("master");
+
+        // No committer is configured, so the merge cannot be committed yet
+        assert!(start_merge("topic").is_err());
+        assert!(in_progress());
+
+        // Committing by hand concludes it
+        let merge_commit = commit("Merge topic", "Tester <tester@example.com>").unwrap();
+        assert_eq!(read_commit(&merge_commit).unwrap().parents(), vec![ours, theirs]);
+        assert!(!in_progress());
+    }
+
+    #[test]
+    fn test_fast_forward_merge() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_file("file.txt", "one\n", "first");
+        branches::create_banch("topic", &first).unwrap();
+        switch_to("topic");
+        let ahead = commit_file("file.txt", "two\n", "second");
+        switch_to("master");
+
+        assert_eq!(start_merge("topic").unwrap(), MergeOutcome::FastForward(ahead.clone()));
+        assert_eq!(fs::read_to_string("file.txt").unwrap(), "two\n");
+        assert_eq!(branches::get_current_branch_commit_hash().unwrap(), Some(ahead));
+        assert!(!in_progress());
+        assert_eq!(start_merge("topic").unwrap(), MergeOutcome::UpToDate);
+    }
+}
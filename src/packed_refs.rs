@@ -0,0 +1,177 @@
+// The packed-refs file: every branch and tag in one file of `<hash> <refname>` lines, as
+// written by `pack-refs`. A loose ref file always takes precedence over a packed entry, so
+// updating a packed branch simply writes a loose file again.
+
+use crate::constants::{directory_path, heads_dir_path, packed_refs_path, tags_dir_path, HEADS_DIR, REFS_DIR, TAGS_DIR};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "# pack-refs with: sorted";
+
+// Reads every packed ref, keyed by full name such as `refs/heads/master`
+pub fn read_packed_refs() -> io::Result<BTreeMap<String, String>> {
+    let contents = match fs::read_to_string(packed_refs_path()) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut refs = BTreeMap::new();
+    for line in contents.lines() {
+        // Comments, and the peeled lines git writes after annotated tags
+        if line.is_empty() || line.starts_with('#') || line.starts_with('^') {
+            continue;
+        }
+        let (hash, name) = line
+            .split_once(' ')
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Malformed packed-refs line: {:?}", line)))?;
+        refs.insert(name.to_string(), hash.to_string());
+    }
+    Ok(refs)
+}
+
+// Looks up one packed ref by its full name
+pub fn packed_ref(full_name: &str) -> io::Result<Option<String>> {
+    Ok(read_packed_refs()?.remove(full_name))
+}
+
+// The short names of packed refs of one kind (`heads` or `tags`)
+pub fn packed_names(kind: &str) -> io::Result<Vec<String>> {
+    let prefix = format!("{}/{}/", REFS_DIR, kind);
+    Ok(read_packed_refs()?.keys().filter_map(|name| name.strip_prefix(&prefix)).map(str::to_string).collect())
+}
+
+fn write_packed_refs(refs: &BTreeMap<String, String>) -> io::Result<()> {
+    let mut contents = format!("{}\n", HEADER);
+    for (name, hash) in refs {
+        contents.push_str(&format!("{} {}\n", hash, name));
+    }
+    fs::write(packed_refs_path(), contents)
+}
+
+// Collects the loose ref files under a directory, with names relative to it
+fn loose_refs(dir: &Path, prefix: &str, refs: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            loose_refs(&entry.path(), &format!("{}/", name), refs)?;
+        } else {
+            refs.push((name, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+// Writes every loose branch and tag into packed-refs, merged with what is already packed,
+// and removes the loose files when `prune` is set. Returns how many refs were packed.
+pub fn pack_refs(prune: bool) -> io::Result<usize> {
+    let mut refs = read_packed_refs()?;
+    let mut loose = Vec::new();
+    loose_refs(&heads_dir_path(), &format!("{}/{}/", REFS_DIR, HEADS_DIR), &mut loose)?;
+    loose_refs(&tags_dir_path(), &format!("{}/{}/", REFS_DIR, TAGS_DIR), &mut loose)?;
+
+    for (name, path) in &loose {
+        refs.insert(name.clone(), fs::read_to_string(path)?.trim().to_string());
+    }
+    write_packed_refs(&refs)?;
+
+    if prune {
+        for (_, path) in &loose {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(loose.len())
+}
+
+// Writes each packed ref back out as a loose file, unless a loose file already overrides
+// it, and removes packed-refs. Returns how many loose files were written.
+pub fn unpack_refs() -> io::Result<usize> {
+    let mut written = 0;
+    for (name, hash) in read_packed_refs()? {
+        let path = directory_path().join(&name);
+        if path.exists() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &hash)?;
+        written += 1;
+    }
+    match fs::remove_file(packed_refs_path()) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(written),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branches;
+    use crate::commit::commit;
+    use crate::refs::resolve_ref;
+    use crate::staging::add_file_to_staging;
+    use crate::tag;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn test_resolve_packed_only_ref() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let commit_hash = commit("first", "Tester <tester@example.com>").unwrap();
+        branches::create_banch("feature", &commit_hash).unwrap();
+        let tag_key = tag::create_annotated_tag("v1", "HEAD", "Tester <tester@example.com>", "release").unwrap();
+
+        assert_eq!(pack_refs(true).unwrap(), 3);
+        assert!(!heads_dir_path().join("feature").exists());
+        assert_eq!(
+            fs::read_to_string(packed_refs_path()).unwrap(),
+            format!(
+                "{}\n{} refs/heads/feature\n{} refs/heads/master\n{} refs/tags/v1\n",
+                HEADER, commit_hash, commit_hash, tag_key
+            )
+        );
+
+        // Packed refs still resolve and list
+        assert_eq!(resolve_ref("feature").unwrap(), commit_hash);
+        assert_eq!(resolve_ref("HEAD").unwrap(), commit_hash);
+        assert_eq!(branches::list_branches().unwrap(), vec!["feature", "master"]);
+        assert_eq!(tag::tag_ref("v1").unwrap(), Some(tag_key.clone()));
+        assert_eq!(tag::list_tags().unwrap(), vec!["v1"]);
+
+        // A new commit writes a loose ref, which wins over the packed entry
+        fs::write("file.txt", "changed").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let second = commit("second", "Tester <tester@example.com>").unwrap();
+        assert_eq!(resolve_ref("master").unwrap(), second);
+        assert_eq!(branches::list_branches().unwrap(), vec!["feature", "master"]);
+    }
+
+    #[test]
+    fn test_pack_and_unpack_round_trip() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let commit_hash = commit("first", "Tester <tester@example.com>").unwrap();
+        branches::create_banch("topic", &commit_hash).unwrap();
+
+        // Packing without pruning keeps the loose files
+        assert_eq!(pack_refs(false).unwrap(), 2);
+        assert!(heads_dir_path().join("topic").exists());
+        assert_eq!(packed_ref("refs/heads/topic").unwrap(), Some(commit_hash.clone()));
+
+        pack_refs(true).unwrap();
+        assert_eq!(unpack_refs().unwrap(), 2);
+        assert!(!packed_refs_path().exists());
+        assert_eq!(fs::read_to_string(heads_dir_path().join("topic")).unwrap(), commit_hash);
+        assert_eq!(resolve_ref("topic").unwrap(), commit_hash);
+        assert_eq!(branches::list_branches().unwrap(), vec!["master", "topic"]);
+    }
+}
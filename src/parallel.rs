@@ -0,0 +1,49 @@
+// A small scoped thread pool for work that is independent per item, such as
+// checking every object in the store.
+
+use std::thread;
+
+// The number of worker threads to use by default
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+// Applies `f` to every item on up to `threads` threads, returning the results in input order
+pub fn map_ordered<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let chunk_size = items.len().div_ceil(threads);
+    let f = &f;
+    thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("worker thread panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_ordered_keeps_input_order() {
+        let items: Vec<u32> = (0..100).collect();
+        let serial = map_ordered(&items, 1, |n| n * 2);
+        assert_eq!(map_ordered(&items, 7, |n| n * 2), serial);
+        assert_eq!(map_ordered(&items, 500, |n| n * 2), serial);
+        assert!(map_ordered(&Vec::<u32>::new(), 4, |n| *n).is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+// Human-readable rendering of any object, for `cat-file -p`. Blobs print as text, trees
+// list their entries like ls-tree, and commits and tags show their parsed fields with
+// dates formatted in the offset they were recorded in.
+
+use crate::commit::Commit;
+use crate::constants::{BLOB, COMMIT, TAG, TREE};
+use crate::database;
+use crate::date;
+use crate::tag::Tag;
+use crate::text::display_text;
+use crate::tree::read_tree;
+use std::io;
+
+// Renders the object stored under `key`
+pub fn pretty_object(key: &str) -> io::Result<String> {
+    let (object_type, _, data) = database::get_data(key)?;
+    match object_type.as_str() {
+        BLOB => Ok(display_text(&data)),
+        TREE => pretty_tree(key),
+        COMMIT => Ok(pretty_commit(&Commit::deserialize(&data)?)),
+        TAG => Ok(pretty_tag(&Tag::deserialize(&data)?)),
+        other => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unknown object type '{}'", other))),
+    }
+}
+
+fn pretty_tree(key: &str) -> io::Result<String> {
+    let mut out = String::new();
+    for entry in read_tree(key)? {
+        out.push_str(&format!("{:06o} {}\t{}\t{}\n", entry.mode, entry.object_type, entry.hash, entry.name));
+    }
+    Ok(out)
+}
+
+pub fn pretty_commit(commit: &Commit) -> String {
+    let mut out = format!("tree      {}\n", commit.tree);
+    for parent in commit.parents() {
+        out.push_str(&format!("parent    {}\n", parent));
+    }
+    if commit.author != commit.committer {
+        out.push_str(&format!("author    {}\n", commit.author));
+    }
+    out.push_str(&format!("committer {}\n", commit.committer));
+    out.push_str(&format!("date      {}\n", date::format_iso(commit.timestamp, commit.timezone)));
+    out.push_str(&format!("\n{}\n", commit.message));
+    out
+}
+
+pub fn pretty_tag(tag: &Tag) -> String {
+    format!(
+        "object    {}\ntype      {}\ntag       {}\ntagger    {}\ndate      {}\n\n{}\n",
+        tag.object,
+        tag.object_type,
+        tag.name,
+        tag.tagger,
+        date::format_iso(tag.timestamp, tag.timezone),
+        tag.message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_commit() {
+        let commit = Commit {
+            tree: "t".repeat(64),
+            parent: Some("p".repeat(64)),
+            merge_parents: Vec::new(),
+            author: "Tester <tester@example.com>".to_string(),
+            committer: "Tester <tester@example.com>".to_string(),
+            message: "Add things\n\nIn detail.".to_string(),
+            timestamp: 1_700_000_000,
+            timezone: 90,
+        };
+        assert_eq!(
+            pretty_commit(&commit),
+            format!(
+                "tree      {}\nparent    {}\ncommitter Tester <tester@example.com>\ndate      2023-11-14 23:43:20 +0130\n\nAdd things\n\nIn detail.\n",
+                "t".repeat(64),
+                "p".repeat(64)
+            )
+        );
+    }
+
+    #[test]
+    fn test_pretty_tag() {
+        let tag = Tag {
+            object: "c".repeat(64),
+            object_type: COMMIT.to_string(),
+            name: "v1.0".to_string(),
+            tagger: "Tagger <tagger@example.com>".to_string(),
+            timestamp: 1_700_000_000,
+            timezone: -300,
+            message: "First release".to_string(),
+        };
+        assert_eq!(
+            pretty_tag(&tag),
+            format!(
+                "object    {}\ntype      commit\ntag       v1.0\ntagger    Tagger <tagger@example.com>\ndate      2023-11-14 17:13:20 -0500\n\nFirst release\n",
+                "c".repeat(64)
+            )
+        );
+    }
+}
@@ -0,0 +1,378 @@
+// Reflogs record every position a ref has pointed at, so lost commits can be found again.
+// Each line is `<old> <new> <identity> <timestamp> <tz>\t<message>`, oldest first.
+
+use crate::branches;
+use crate::commit::parse_identity;
+use crate::constants::{logs_dir_path, HEAD_FILE, HEADS_DIR, REFS_DIR};
+use crate::database::ZERO_HASH;
+use crate::graph;
+use crate::lock::LockFile;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+// Defaults for gc --prune-reflog, matching git's gc.reflogExpire settings
+pub const DEFAULT_EXPIRE_DAYS: u64 = 90;
+pub const DEFAULT_EXPIRE_UNREACHABLE_DAYS: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflogEntry {
+    pub old: String,
+    pub new: String,
+    pub committer: String,
+    pub timestamp: u64,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    fn serialize(&self) -> String {
+        format!("{} {} {} {} +0000\t{}\n", self.old, self.new, self.committer, self.timestamp, self.message)
+    }
+
+    fn deserialize(line: &str) -> io::Result<Self> {
+        let malformed = || io::Error::new(io::ErrorKind::InvalidData, format!("Malformed reflog line: {}", line));
+
+        let (header, message) = line.split_once('\t').unwrap_or((line, ""));
+        let mut parts = header.splitn(3, ' ');
+        let old = parts.next().ok_or_else(malformed)?.to_string();
+        let new = parts.next().ok_or_else(malformed)?.to_string();
+        let (committer, timestamp, _) = parse_identity(parts.next().ok_or_else(malformed)?)?;
+
+        Ok(ReflogEntry { old, new, committer, timestamp, message: message.to_string() })
+    }
+}
+
+// Returns the log file for a ref name such as `HEAD` or `refs/heads/master`
+fn reflog_path(ref_name: &str) -> PathBuf {
+    logs_dir_path().join(ref_name)
+}
+
+// Returns the full ref name of a branch
+pub fn branch_ref_name(branch: &str) -> String {
+    format!("{}/{}/{}", REFS_DIR, HEADS_DIR, branch)
+}
+
+// Appends one entry to the log of a ref, creating the log if needed
+pub fn append(ref_name: &str, entry: &ReflogEntry) -> io::Result<()> {
+    let path = reflog_path(ref_name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(entry.serialize().as_bytes())
+}
+
+// Records a branch moving from `old` to `new` in both the branch log and HEAD's log
+pub fn record_branch_update(branch: &str, old: Option<&str>, new: &str, committer: &str, timestamp: u64, message: &str) -> io::Result<()> {
+    let entry = ReflogEntry {
+        old: old.unwrap_or(ZERO_HASH).to_string(),
+        new: new.to_string(),
+        committer: committer.to_string(),
+        timestamp,
+        message: message.to_string(),
+    };
+    append(&branch_ref_name(branch), &entry)?;
+    append(HEAD_FILE, &entry)
+}
+
+// Reads every entry of a ref's log, oldest first; a missing log is empty
+pub fn read_reflog(ref_name: &str) -> io::Result<Vec<ReflogEntry>> {
+    let contents = match fs::read_to_string(reflog_path(ref_name)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    contents.lines().filter(|line| !line.is_empty()).map(ReflogEntry::deserialize).collect()
+}
+
+// Atomically replaces the log of a ref with the given entries
+pub fn write_reflog(ref_name: &str, entries: &[ReflogEntry]) -> io::Result<()> {
+    let path = reflog_path(ref_name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let data: String = entries.iter().map(ReflogEntry::serialize).collect();
+    LockFile::acquire(&path)?.commit(data.as_bytes())
+}
+
+// Lists the refs that have a log: HEAD and every branch
+pub fn list_reflogs() -> io::Result<Vec<String>> {
+    let mut ref_names = Vec::new();
+    if reflog_path(HEAD_FILE).exists() {
+        ref_names.push(HEAD_FILE.to_string());
+    }
+
+    let heads_dir = logs_dir_path().join(REFS_DIR).join(HEADS_DIR);
+    if heads_dir.exists() {
+        let mut branches: Vec<String> = branches::ref_names_under(&heads_dir)?.iter().map(|name| branch_ref_name(name)).collect();
+        branches.sort();
+        ref_names.extend(branches);
+    }
+
+    Ok(ref_names)
+}
+
+// Returns the commit a logged ref currently points at
+fn current_tip(ref_name: &str) -> io::Result<Option<String>> {
+    if ref_name == HEAD_FILE {
+        return branches::get_current_branch_commit_hash();
+    }
+    match ref_name.strip_prefix(&format!("{}/{}/", REFS_DIR, HEADS_DIR)) {
+        Some(branch) => branches::get_commit_hash(branch),
+        None => Ok(None),
+    }
+}
+
+// Every commit recorded in any reflog; gc keeps these alive until their entries expire
+pub fn reflog_commits() -> io::Result<HashSet<String>> {
+    let mut commits = HashSet::new();
+    for ref_name in list_reflogs()? {
+        for entry in read_reflog(&ref_name)? {
+            commits.extend([entry.old, entry.new].into_iter().filter(|hash| hash != ZERO_HASH));
+        }
+    }
+    Ok(commits)
+}
+
+// Drops entries older than `expire` seconds, and entries older than `expire_unreachable`
+// seconds whose commit the ref can no longer reach. Returns how many entries were removed.
+pub fn expire(now: u64, expire: u64, expire_unreachable: u64) -> io::Result<usize> {
+    let mut removed = 0;
+
+    for ref_name in list_reflogs()? {
+        let entries = read_reflog(&ref_name)?;
+        let reachable = match current_tip(&ref_name)? {
+            Some(tip) => graph::ancestors(&tip)?,
+            None => HashSet::new(),
+        };
+
+        let kept: Vec<ReflogEntry> = entries
+            .iter()
+            .filter(|entry| {
+                let age = now.saturating_sub(entry.timestamp);
+                if age > expire {
+                    return false;
+                }
+                age <= expire_unreachable || reachable.contains(&entry.new)
+            })
+            .cloned()
+            .collect();
+
+        if kept.len() != entries.len() {
+            removed += entries.len() - kept.len();
+            write_reflog(&ref_name, &kept)?;
+        }
+    }
+
+    Ok(removed)
+}
+
+// Returns the log name for `HEAD`, a branch name or a full ref name
+pub fn ref_log_name(name: &str) -> String {
+    if name == HEAD_FILE || name.starts_with(&format!("{}/", REFS_DIR)) {
+        name.to_string()
+    } else {
+        branch_ref_name(name)
+    }
+}
+
+// Splits `<ref>@{<n>}` into the log name and the entry index, where 0 is the newest entry
+pub fn parse_entry_spec(spec: &str) -> io::Result<(String, usize)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid reflog entry '{}': expected <ref>@{{<n>}}", spec));
+
+    let (name, index) = spec.strip_suffix('}').and_then(|rest| rest.rsplit_once("@{")).ok_or_else(invalid)?;
+    let index = index.parse::<usize>().map_err(|_| invalid())?;
+    let name = if name.is_empty() { HEAD_FILE } else { name };
+    Ok((ref_log_name(name), index))
+}
+
+// Resolves `<ref>@{<n>}` to the commit the ref pointed at n moves ago
+pub fn resolve_entry(spec: &str) -> io::Result<String> {
+    let (ref_name, index) = parse_entry_spec(spec)?;
+    let entries = read_reflog(&ref_name)?;
+    if index >= entries.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Log for '{}' only has {} entries", ref_name, entries.len()),
+        ));
+    }
+    Ok(entries[entries.len() - 1 - index].new.clone())
+}
+
+// Removes the n-th newest entry from a ref's log. The newest entry records where the
+// ref points now, so it is kept while the ref still points there.
+pub fn delete_entry(ref_name: &str, index: usize) -> io::Result<ReflogEntry> {
+    let mut entries = read_reflog(ref_name)?;
+    if index >= entries.len() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{}@{{{}}} does not exist", ref_name, index)));
+    }
+    let position = entries.len() - 1 - index;
+    if index == 0 && current_tip(ref_name)?.as_deref() == Some(entries[position].new.as_str()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}@{{0}} records the current value of {} and cannot be deleted", ref_name, ref_name),
+        ));
+    }
+
+    let removed = entries.remove(position);
+    write_reflog(ref_name, &entries)?;
+    Ok(removed)
+}
+
+// Removes every entry named by a `<ref>@{<n>}` spec, each once however often it is given.
+// Indexes refer to the logs as they were before any deletion. Returns how many were removed.
+pub fn delete_entries(specs: &[String]) -> io::Result<usize> {
+    // Per ref, delete the oldest entries first so the indexes of newer ones stay valid
+    let mut entries = BTreeSet::new();
+    for spec in specs {
+        let (ref_name, index) = parse_entry_spec(spec)?;
+        entries.insert((ref_name, Reverse(index)));
+    }
+    for (ref_name, Reverse(index)) in &entries {
+        delete_entry(ref_name, *index)?;
+    }
+    Ok(entries.len())
+}
+
+// Clears the logs of the given refs, or of every ref when none are given. Returns how
+// many entries were removed.
+pub fn expire_all(ref_names: &[String]) -> io::Result<usize> {
+    let ref_names = if ref_names.is_empty() { list_reflogs()? } else { ref_names.to_vec() };
+    let mut removed = 0;
+    for ref_name in ref_names {
+        let entries = read_reflog(&ref_name)?;
+        if !entries.is_empty() {
+            removed += entries.len();
+            write_reflog(&ref_name, &[])?;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::head_file_path;
+    use crate::test_utils::{commit_file, TestDir};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn entry(new: &str, timestamp: u64) -> ReflogEntry {
+        ReflogEntry {
+            old: ZERO_HASH.to_string(),
+            new: new.to_string(),
+            committer: "Tester <tester@example.com>".to_string(),
+            timestamp,
+            message: "seeded".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_commit_records_reflog_entries() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_file("file.txt", "one", "first");
+        let second = commit_file("file.txt", "two", "second");
+
+        let log = read_reflog(&branch_ref_name("master")).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].old, ZERO_HASH);
+        assert_eq!(log[0].new, first);
+        assert_eq!(log[1].old, first);
+        assert_eq!(log[1].new, second);
+        assert_eq!(log[1].message, "commit: second");
+        assert_eq!(read_reflog(HEAD_FILE).unwrap(), log);
+    }
+
+    #[test]
+    fn test_expire_drops_old_and_unreachable_entries() {
+        let _test_dir = TestDir::with_repo();
+        let reachable = commit_file("file.txt", "one", "reachable");
+
+        // A commit only a deleted branch pointed at
+        branches::create_banch("doomed", &reachable).unwrap();
+        fs::write(head_file_path(), "ref: refs/heads/doomed\n").unwrap();
+        let unreachable = commit_file("file.txt", "two", "unreachable");
+        fs::write(head_file_path(), "ref: refs/heads/master\n").unwrap();
+        fs::remove_file(crate::constants::heads_dir_path().join("doomed")).unwrap();
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let days_ago = |days: u64| now - days * SECONDS_PER_DAY;
+        let ref_name = branch_ref_name("master");
+        write_reflog(&ref_name, &[
+            entry(&reachable, days_ago(100)),
+            entry(&reachable, days_ago(40)),
+            entry(&unreachable, days_ago(40)),
+            entry(&unreachable, days_ago(10)),
+        ]).unwrap();
+        fs::remove_file(reflog_path(HEAD_FILE)).unwrap();
+        fs::remove_file(reflog_path(&branch_ref_name("doomed"))).unwrap();
+
+        let removed = expire(now, DEFAULT_EXPIRE_DAYS * SECONDS_PER_DAY, DEFAULT_EXPIRE_UNREACHABLE_DAYS * SECONDS_PER_DAY).unwrap();
+        assert_eq!(removed, 2);
+        assert_eq!(read_reflog(&ref_name).unwrap(), vec![
+            entry(&reachable, days_ago(40)),
+            entry(&unreachable, days_ago(10)),
+        ]);
+
+        // Once the remaining entry expires the commit is truly dead and gc can prune it
+        assert!(reflog_commits().unwrap().contains(&unreachable));
+        expire(now, DEFAULT_EXPIRE_DAYS * SECONDS_PER_DAY, 0).unwrap();
+        assert!(!reflog_commits().unwrap().contains(&unreachable));
+        assert!(crate::gc::gc().unwrap().pruned.contains(&unreachable));
+    }
+
+    #[test]
+    fn test_delete_entry() {
+        let _test_dir = TestDir::with_repo();
+        let first = commit_file("file.txt", "one", "first");
+        let second = commit_file("file.txt", "two", "second");
+        commit_file("file.txt", "three", "third");
+
+        let (ref_name, index) = parse_entry_spec("master@{1}").unwrap();
+        assert_eq!(ref_name, branch_ref_name("master"));
+        let removed = delete_entry(&ref_name, index).unwrap();
+        assert_eq!(removed.new, second);
+
+        let log = read_reflog(&ref_name).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].new, first);
+        assert_eq!(log[1].message, "commit: third");
+
+        // The entry for where master points now is still needed
+        assert!(delete_entry(&ref_name, 0).is_err());
+        assert!(delete_entry(&ref_name, 5).is_err());
+        assert!(parse_entry_spec("master").is_err());
+        assert_eq!(read_reflog(HEAD_FILE).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_delete_entries_once_each() {
+        let _test_dir = TestDir::with_repo();
+        let hashes: Vec<String> = ["one", "two", "three", "four"].iter().map(|c| commit_file("file.txt", c, c)).collect();
+
+        // A repeated spec, split up by a spec for another ref, still deletes one entry
+        let specs: Vec<String> = ["HEAD@{1}", "master@{1}", "HEAD@{1}", "HEAD@{2}"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(delete_entries(&specs).unwrap(), 3);
+
+        let head: Vec<String> = read_reflog(HEAD_FILE).unwrap().into_iter().map(|e| e.new).collect();
+        assert_eq!(head, vec![hashes[0].clone(), hashes[3].clone()]);
+        let master: Vec<String> = read_reflog(&branch_ref_name("master")).unwrap().into_iter().map(|e| e.new).collect();
+        assert_eq!(master, vec![hashes[0].clone(), hashes[1].clone(), hashes[3].clone()]);
+    }
+
+    #[test]
+    fn test_expire_all_for_branch() {
+        let _test_dir = TestDir::with_repo();
+        commit_file("file.txt", "one", "first");
+        commit_file("file.txt", "two", "second");
+
+        assert_eq!(expire_all(&[branch_ref_name("master")]).unwrap(), 2);
+        assert!(read_reflog(&branch_ref_name("master")).unwrap().is_empty());
+        assert_eq!(read_reflog(HEAD_FILE).unwrap().len(), 2);
+
+        assert_eq!(expire_all(&[]).unwrap(), 2);
+        assert!(reflog_commits().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+// Resolves user-supplied names (HEAD, branches, tags, full or abbreviated object keys) to object keys.
+
+use crate::branches;
+use crate::commit::read_commit;
+use crate::database;
+use crate::reflog;
+use crate::constants::{COMMIT, TAG};
+use crate::tag::{self, Tag};
+use std::io;
+
+// Resolves a revision name to the object key it points at. A trailing `~n` walks n
+// first parents back, each `^` walks one, and `@{n}` looks the name up in its reflog.
+pub fn resolve_ref(name: &str) -> io::Result<String> {
+    if let Some((base, steps)) = split_ancestry(name) {
+        let mut hash = resolve_commit(base)?;
+        for _ in 0..steps {
+            hash = read_commit(&hash)?.parent.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("Revision '{}' goes past the root commit", name))
+            })?;
+        }
+        return Ok(hash);
+    }
+
+    // `HEAD@{n}` and `<branch>@{n}` name where the ref pointed n moves ago
+    if name.ends_with('}') && name.contains("@{") {
+        return reflog::resolve_entry(name);
+    }
+
+    if name == "HEAD" {
+        return branches::get_current_branch_commit_hash()?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HEAD does not point to a commit yet"));
+    }
+
+    // Illegal names can never be refs, and must not be joined onto the refs directory
+    if validate_ref_name(name).is_ok() {
+        if let Some(commit_hash) = branches::get_commit_hash(name)? {
+            return Ok(commit_hash);
+        }
+        if let Some(key) = tag::tag_ref(name)? {
+            return Ok(key);
+        }
+    }
+
+    if database::object_exists(name) {
+        return Ok(name.to_string());
+    }
+
+    // An abbreviated object key, such as the first few characters `log` prints
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit()) {
+        match database::resolve_prefix(name) {
+            Ok(key) => return Ok(key),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown revision '{}'", name)))
+}
+
+// Resolves a revision name to a commit, following annotated tags to the commit they tag
+pub fn resolve_commit(name: &str) -> io::Result<String> {
+    peel_to_commit(&resolve_ref(name)?)
+}
+
+// Follows annotated tags from an object until reaching a commit
+pub fn peel_to_commit(key: &str) -> io::Result<String> {
+    let mut key = key.to_string();
+    loop {
+        let (object_type, _, data) = database::get_data(&key)?;
+        match object_type.as_str() {
+            COMMIT => return Ok(key),
+            TAG => key = Tag::deserialize(&data)?.object,
+            other => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Object {} is a {}, not a commit", key, other)));
+            }
+        }
+    }
+}
+
+// Splits `base~n`, `base~` and `base^^` into the base and how many parents to walk
+fn split_ancestry(name: &str) -> Option<(&str, usize)> {
+    if let Some((base, count)) = name.rsplit_once('~') {
+        let steps = if count.is_empty() { 1 } else { count.parse().ok()? };
+        return Some((base, steps));
+    }
+    let base = name.trim_end_matches('^');
+    if base.len() < name.len() {
+        return Some((base, name.len() - base.len()));
+    }
+    None
+}
+
+// Rejects names git's check-ref-format would reject, so refs stay valid file paths
+pub fn validate_ref_name(name: &str) -> io::Result<()> {
+    let invalid = |reason: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is not a valid ref name: {}", name, reason)));
+
+    if name.is_empty() {
+        return invalid("it is empty");
+    }
+    if name == "@" {
+        return invalid("it is '@'");
+    }
+    if name.starts_with('-') {
+        return invalid("it starts with '-'");
+    }
+    if name.ends_with('/') || name.ends_with('.') {
+        return invalid("it ends with '/' or '.'");
+    }
+    if name.contains("..") {
+        return invalid("it contains '..'");
+    }
+    if name.contains("@{") {
+        return invalid("it contains '@{'");
+    }
+    if let Some(c) = name.chars().find(|c| c.is_control() || *c == ' ' || "~^:?*[\\".contains(*c)) {
+        return invalid(&format!("it contains {:?}", c));
+    }
+    for component in name.split('/') {
+        if component.is_empty() {
+            return invalid("it contains an empty path component");
+        }
+        if component.starts_with('.') {
+            return invalid("a path component starts with '.'");
+        }
+        if component.ends_with(".lock") {
+            return invalid("a path component ends with '.lock'");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::commit;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_resolve_ref() {
+        let _test_dir = TestDir::with_repo();
+        assert!(resolve_ref("HEAD").is_err());
+
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let commit_hash = commit("message", "Tester <tester@example.com>").unwrap();
+
+        assert_eq!(resolve_ref("HEAD").unwrap(), commit_hash);
+        assert_eq!(resolve_ref("master").unwrap(), commit_hash);
+        assert_eq!(resolve_ref(&commit_hash).unwrap(), commit_hash);
+        assert!(resolve_ref("no-such-branch").is_err());
+    }
+
+    #[test]
+    fn test_resolve_ancestry() {
+        let _test_dir = TestDir::with_repo();
+        let mut hashes = Vec::new();
+        for content in ["one", "two", "three"] {
+            fs::write("file.txt", content).unwrap();
+            add_file_to_staging("file.txt").unwrap();
+            hashes.push(commit(content, "Tester <tester@example.com>").unwrap());
+        }
+
+        assert_eq!(resolve_ref("HEAD~0").unwrap(), hashes[2]);
+        assert_eq!(resolve_ref("HEAD~").unwrap(), hashes[1]);
+        assert_eq!(resolve_ref("master~2").unwrap(), hashes[0]);
+        assert_eq!(resolve_ref("HEAD^^").unwrap(), hashes[0]);
+        assert_eq!(resolve_ref(&format!("{}^", hashes[1])).unwrap(), hashes[0]);
+        assert!(resolve_ref("HEAD~3").is_err());
+        assert!(resolve_ref("HEAD~x").is_err());
+    }
+
+    #[test]
+    fn test_validate_ref_name() {
+        for valid in ["master", "feature/login", "release-1.0", "v2"] {
+            assert!(validate_ref_name(valid).is_ok(), "{} should be valid", valid);
+        }
+        for invalid in ["", "has space", "a..b", "-flag", ".hidden", "dir/.hidden", "tab\tname",
+                        "ends.", "ends/", "a//b", "topic.lock", "what?", "HEAD@{1}", "@", "x~1", "a:b"] {
+            assert!(validate_ref_name(invalid).is_err(), "{:?} should be invalid", invalid);
+        }
+    }
+
+    #[test]
+    fn test_create_branch_rejects_invalid_name() {
+        let _test_dir = TestDir::with_repo();
+        let error = branches::create_banch("../escape", "hash").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let error = crate::repo::rit_init_with_branch("bad name").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
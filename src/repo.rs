@@ -1,8 +1,12 @@
 use crate::index;
-use crate::utility;
-use crate::constants::DIRECTORY_PATH;
+use crate::config::{self, Config};
+use crate::constants::{description_path, head_file_path, heads_dir_path, info_exclude_path, objects_dir_path, refs_dir_path, DIRECTORY_PATH, DEFAULT_BRANCH, DEFAULT_BRANCH_ENV, DEFAULT_DESCRIPTION, BASE_REPOSITORY_FORMAT_VERSION, REPOSITORY_FORMAT_VERSION};
 use crate::database;
 use crate::branches;
+use crate::compression::{self, CompressionCodec, CODEC_KEY, LEVEL_KEY};
+use crate::hash::{HashAlgo, OBJECT_FORMAT_KEY};
+use crate::hooks;
+use crate::refs::validate_ref_name;
 use std::io::{Error, ErrorKind, Result};
 use std::path::Path;
 use std::fs;
@@ -29,8 +33,45 @@ pub fn rit_remove() -> Result<()> {
     Ok(())
 }
 
+// Picks the initial branch: an explicit name, then the environment, then master
+pub fn default_branch_name(explicit: Option<&str>) -> String {
+    explicit
+        .map(|name| name.to_string())
+        .or_else(|| std::env::var(DEFAULT_BRANCH_ENV).ok())
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_BRANCH.to_string())
+}
+
 // initialize .rit folder
 pub fn rit_init() -> Result<()> {
+    rit_init_with_branch(&default_branch_name(None))
+}
+
+// Choices fixed when a repository is created
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    // The hash objects are addressed by; every key in the repository depends on it
+    pub object_format: HashAlgo,
+    // How new objects are compressed; this one can be changed later in the config
+    pub compression: CompressionCodec,
+    // The zstd level, when not the default; it too can be changed later
+    pub compression_level: Option<i32>,
+}
+
+// initialize .rit folder with HEAD pointing at the given branch
+pub fn rit_init_with_branch(default_branch: &str) -> Result<()> {
+    rit_init_with(default_branch, &InitOptions::default())
+}
+
+pub fn rit_init_with(default_branch: &str, options: &InitOptions) -> Result<()> {
+    if default_branch.trim().is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "Branch name cannot be empty."));
+    }
+    validate_ref_name(default_branch)?;
+    if let Some(level) = options.compression_level {
+        compression::validate_level(level.into())?;
+    }
+
     // Get the path
     let path = Path::new(DIRECTORY_PATH);
     
@@ -43,19 +84,260 @@ pub fn rit_init() -> Result<()> {
     fs::create_dir_all(path)?;
 
     // Create the repository structure
-    // utility::init_file_structure()?;
     database::create_object_database()?;
     index::create_index()?;
-    branches::init_branches()?;
+    branches::init_branches(default_branch)?;
+    hooks::init_hooks()?;
+    fs::write(description_path(), DEFAULT_DESCRIPTION)?;
+    if let Some(info_dir) = info_exclude_path().parent() {
+        fs::create_dir_all(info_dir)?;
+    }
+    fs::write(info_exclude_path(), "")?;
+    // Anything but the defaults needs a rit that knows about it, so older ones must refuse the repository
+    let uses_extensions = options.object_format != HashAlgo::Sha256 || options.compression != CompressionCodec::Zstd;
+    let version = if uses_extensions { REPOSITORY_FORMAT_VERSION } else { BASE_REPOSITORY_FORMAT_VERSION };
+    config::set_value("core.repositoryformatversion", &version.to_string())?;
+    // SHA-256 repositories leave the key out, so they read the same as ones made before it existed
+    if options.object_format != HashAlgo::Sha256 {
+        config::set_value(OBJECT_FORMAT_KEY, options.object_format.name())?;
+    }
+    if options.compression != CompressionCodec::Zstd {
+        config::set_value(CODEC_KEY, options.compression.name())?;
+    }
+    if let Some(level) = options.compression_level {
+        config::set_value(LEVEL_KEY, &level.to_string())?;
+    }
 
     println!("Repository initialized at {}.", DIRECTORY_PATH);
     Ok(())
 }
 
+// Re-creates whatever is missing from an existing repository, leaving everything
+// present untouched. HEAD, if missing, points at the given branch again.
+pub fn rit_reinit(default_branch: &str) -> Result<()> {
+    if !Path::new(DIRECTORY_PATH).exists() {
+        return Err(Error::new(ErrorKind::NotFound, "Repository not initialized. Please run `rit init` first."));
+    }
+    validate_ref_name(default_branch)?;
+
+    database::create_object_database()?;
+    fs::create_dir_all(heads_dir_path())?;
+    if !head_file_path().exists() {
+        branches::init_branches(default_branch)?;
+    }
+    index::create_index()?;
+    if !crate::constants::hooks_dir_path().exists() {
+        hooks::init_hooks()?;
+    }
+    if !description_path().exists() {
+        fs::write(description_path(), DEFAULT_DESCRIPTION)?;
+    }
+    if !info_exclude_path().exists() {
+        if let Some(info_dir) = info_exclude_path().parent() {
+            fs::create_dir_all(info_dir)?;
+        }
+        fs::write(info_exclude_path(), "")?;
+    }
+    if config::get_value("core.repositoryformatversion")?.is_none() {
+        let config = Config::load()?;
+        let uses_extensions = config.get(OBJECT_FORMAT_KEY).is_some() || config.get(CODEC_KEY).is_some_and(|codec| codec != CompressionCodec::Zstd.name());
+        let version = if uses_extensions { REPOSITORY_FORMAT_VERSION } else { BASE_REPOSITORY_FORMAT_VERSION };
+        config::set_value("core.repositoryformatversion", &version.to_string())?;
+    }
+
+    println!("Reinitialized existing repository at {}.", DIRECTORY_PATH);
+    Ok(())
+}
+
 // Helper function to check if the repository is initialized
 pub fn check_repo_initialized() -> Result<()> {
     if !Path::new(DIRECTORY_PATH).exists() {
         return Err(Error::new(ErrorKind::NotFound, "Repository not initialized. Please run `rit init` first."));
     }
+    check_integrity()?;
+    check_format_version()
+}
+
+// Verifies the parts every command relies on are present, so a damaged repository is
+// reported up front instead of through confusing errors later
+pub fn check_integrity() -> Result<()> {
+    let components = [
+        ("the objects directory", objects_dir_path(), true),
+        ("the refs directory", refs_dir_path(), true),
+        ("HEAD", head_file_path(), false),
+    ];
+    for (name, path, is_dir) in components {
+        let present = if is_dir { path.is_dir() } else { path.is_file() };
+        if !present {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Repository is missing {} ({}); run `rit init --reinit` to repair it", name, path.display()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The `extensions` settings this binary knows how to honour
+const KNOWN_EXTENSIONS: &[&str] = &[OBJECT_FORMAT_KEY];
+
+// Refuses repositories written in a newer format than this binary understands, or that
+// need an extension it does not know. Repositories created before the version was
+// recorded are format 0.
+pub fn check_format_version() -> Result<()> {
+    let config = Config::load()?;
+    for (key, _) in config.entries() {
+        if key.starts_with("extensions.") && !KNOWN_EXTENSIONS.contains(&key.as_str()) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Repository needs the unknown extension '{}'. Please upgrade rit.", key),
+            ));
+        }
+    }
+    let version = config.get_int("core.repositoryformatversion")?.unwrap_or(0);
+    if version > REPOSITORY_FORMAT_VERSION {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "Repository format version {} is newer than this rit supports ({}). Please upgrade rit.",
+                version, REPOSITORY_FORMAT_VERSION
+            ),
+        ));
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::commit;
+    use crate::constants::{head_file_path, heads_dir_path};
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn test_init_with_custom_default_branch() {
+        let _test_dir = TestDir::new();
+        rit_init_with_branch("main").unwrap();
+
+        assert_eq!(fs::read_to_string(head_file_path()).unwrap(), "ref: refs/heads/main\n");
+
+        fs::write("file.txt", "content").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let commit_hash = commit("first", "Tester <tester@example.com>").unwrap();
+
+        assert_eq!(fs::read_to_string(heads_dir_path().join("main")).unwrap(), commit_hash);
+        assert!(!heads_dir_path().join("master").exists());
+    }
+
+    #[test]
+    fn test_init_with_blake3_object_format() {
+        let _test_dir = TestDir::new();
+        rit_init_with("master", &InitOptions { object_format: HashAlgo::Blake3, ..InitOptions::default() }).unwrap();
+        assert_eq!(config::get_value(OBJECT_FORMAT_KEY).unwrap().as_deref(), Some("blake3"));
+        assert_eq!(config::get_value("core.repositoryformatversion").unwrap().as_deref(), Some("1"));
+
+        fs::create_dir_all("docs").unwrap();
+        fs::write("docs/file.txt", "content").unwrap();
+        add_file_to_staging("docs/file.txt").unwrap();
+        let commit_hash = commit("first", "Tester <tester@example.com>").unwrap();
+
+        // Keys are BLAKE3 digests, and trees written with them read back
+        let blob_hash = database::store_data(b"content", crate::constants::BLOB).unwrap();
+        assert_eq!(blob_hash, hex::encode(crate::hash::blake3(b"blob 7\0content")));
+        let tree = crate::commit::read_commit(&commit_hash).unwrap().tree;
+        let entries = crate::tree::flatten_tree(&tree).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "docs/file.txt");
+        assert_eq!(entries[0].blob_hash, blob_hash);
+    }
+
+    #[test]
+    fn test_init_with_lz4_compression_reads_older_objects() {
+        let _test_dir = TestDir::new();
+        rit_init_with("master", &InitOptions { compression: CompressionCodec::Lz4, ..InitOptions::default() }).unwrap();
+        assert_eq!(config::get_value(CODEC_KEY).unwrap().as_deref(), Some("lz4"));
+        assert_eq!(config::get_value("core.repositoryformatversion").unwrap().as_deref(), Some("1"));
+
+        let lz4_key = database::store_data(b"stored with lz4", crate::constants::BLOB).unwrap();
+        assert_eq!(fs::read(database::get_object_path(&lz4_key)).unwrap()[0], 0x04);
+
+        // Switching codecs only affects new objects
+        config::set_value(CODEC_KEY, "none").unwrap();
+        let plain_key = database::store_data(b"stored plainly", crate::constants::BLOB).unwrap();
+        assert!(fs::read(database::get_object_path(&plain_key)).unwrap().ends_with(b"stored plainly"));
+        config::set_value(CODEC_KEY, "zstd").unwrap();
+        assert_eq!(database::get_data(&lz4_key).unwrap().2, b"stored with lz4");
+        assert_eq!(database::get_data(&plain_key).unwrap().2, b"stored plainly");
+    }
+
+    #[test]
+    fn test_init_with_compression_level() {
+        let _test_dir = TestDir::new();
+        assert!(rit_init_with("master", &InitOptions { compression_level: Some(23), ..InitOptions::default() }).is_err());
+        assert!(!Path::new(DIRECTORY_PATH).exists());
+
+        rit_init_with("master", &InitOptions { compression_level: Some(19), ..InitOptions::default() }).unwrap();
+        assert_eq!(compression::current_level().unwrap(), 19);
+        let key = database::store_data(b"archived", crate::constants::BLOB).unwrap();
+        assert_eq!(database::get_data(&key).unwrap().2, b"archived");
+
+        // An out-of-range level in the config is reported rather than passed to zstd
+        config::set_value(LEVEL_KEY, "40").unwrap();
+        assert!(database::store_data(b"other", crate::constants::BLOB).is_err());
+    }
+
+    #[test]
+    fn test_newer_format_version_is_refused() {
+        let _test_dir = TestDir::with_repo();
+        assert_eq!(config::get_value("core.repositoryformatversion").unwrap().as_deref(), Some("0"));
+        check_repo_initialized().unwrap();
+
+        config::set_value("core.repositoryformatversion", "2").unwrap();
+        let error = check_repo_initialized().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Unsupported);
+        assert!(error.to_string().contains("upgrade rit"));
+
+        // Format 1 is understood, but not an extension this rit has never heard of
+        config::set_value("core.repositoryformatversion", "1").unwrap();
+        check_repo_initialized().unwrap();
+        config::set_value("extensions.worktreeconfig", "true").unwrap();
+        let error = check_repo_initialized().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Unsupported);
+        assert!(error.to_string().contains("extensions.worktreeconfig"), "{}", error);
+
+        // Repositories from before the version was written are still accepted
+        let mut config = Config::load().unwrap();
+        config.unset("extensions.worktreeconfig").unwrap();
+        config.unset("core.repositoryformatversion").unwrap();
+        config.save().unwrap();
+        check_repo_initialized().unwrap();
+    }
+
+    #[test]
+    fn test_missing_objects_dir_is_reported_and_repaired() {
+        let _test_dir = TestDir::with_repo();
+        fs::remove_dir_all(objects_dir_path()).unwrap();
+
+        let error = check_repo_initialized().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        assert!(error.to_string().contains("missing the objects directory"));
+        assert!(error.to_string().contains("rit init --reinit"));
+
+        fs::write(head_file_path(), "ref: refs/heads/keep\n").unwrap();
+        rit_reinit("master").unwrap();
+        check_repo_initialized().unwrap();
+        // Existing files are left alone
+        assert_eq!(fs::read_to_string(head_file_path()).unwrap(), "ref: refs/heads/keep\n");
+
+        fs::remove_file(head_file_path()).unwrap();
+        assert!(check_repo_initialized().unwrap_err().to_string().contains("missing HEAD"));
+        rit_reinit("master").unwrap();
+        assert_eq!(fs::read_to_string(head_file_path()).unwrap(), "ref: refs/heads/master\n");
+    }
+
+    #[test]
+    fn test_default_branch_name_prefers_explicit_name() {
+        assert_eq!(default_branch_name(Some("trunk")), "trunk");
+    }
+}
@@ -0,0 +1,239 @@
+// Resets index entries to the version recorded in a commit.
+
+use crate::branches;
+use crate::checkout;
+use crate::commit::read_commit;
+use crate::index::{self, load_index, matches_pathspec, IndexEntry};
+use crate::reflog;
+use crate::refs::{resolve_commit, resolve_ref};
+use crate::tree::flatten_tree;
+use std::collections::HashMap;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Resets the index entries matching the pathspecs to their version in the target commit
+// The branch pointer and the working tree are left untouched
+pub fn reset_paths(target: Option<&str>, pathspecs: &[String]) -> io::Result<()> {
+    if pathspecs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "No paths given to reset"));
+    }
+
+    // Entries in the target commit, or nothing when HEAD has no commits yet
+    let target_entries = match target {
+        Some(name) => flatten_tree(&read_commit(&resolve_commit(name)?)?.tree)?,
+        None => match resolve_ref("HEAD") {
+            Ok(commit_hash) => flatten_tree(&read_commit(&commit_hash)?.tree)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        },
+    };
+
+    let matches = |path: &str| pathspecs.iter().any(|pathspec| matches_pathspec(path, pathspec));
+
+    // Keep index entries outside the pathspecs, then take the rest from the target
+    let lock = index::lock_index()?;
+    let mut index: HashMap<String, IndexEntry> = load_index()?
+        .into_iter()
+        .filter(|entry| !matches(&entry.path))
+        .map(|entry| (entry.path.clone(), entry))
+        .collect();
+
+    for entry in target_entries {
+        if matches(&entry.path) {
+            index.insert(entry.path.clone(), entry);
+        }
+    }
+
+    let entries: Vec<IndexEntry> = index.into_values().collect();
+    index::save_index_locked(lock, &entries)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetMode {
+    // Move the branch and reset the index, keeping the working tree (git's default)
+    Mixed,
+    // Move the branch and make both the index and the working tree match the target
+    Hard,
+}
+
+// Moves the current branch to the target commit and makes the index match it, leaving
+// the working tree alone so earlier changes show up as unstaged. Returns the new tip.
+pub fn reset_mixed(target: &str) -> io::Result<String> {
+    reset_commit(target, ResetMode::Mixed)
+}
+
+// Moves the current branch to the target commit and makes the index and working tree
+// match it, discarding local changes to tracked files. Returns the new tip.
+pub fn reset_hard(target: &str) -> io::Result<String> {
+    reset_commit(target, ResetMode::Hard)
+}
+
+pub fn reset_commit(target: &str, mode: ResetMode) -> io::Result<String> {
+    let commit_hash = resolve_commit(target)?;
+    let branch = branches::get_current_branch_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "HEAD is not on a branch"))?;
+    let old = branches::get_current_branch_commit_hash()?;
+    let commit = read_commit(&commit_hash)?;
+
+    let lock = index::lock_index()?;
+    let entries = flatten_tree(&commit.tree)?;
+    if mode == ResetMode::Hard {
+        checkout::switch_entries(&load_index()?, &entries)?;
+    }
+    index::save_index_locked(lock, &entries)?;
+    branches::update_current_branch(&commit_hash)?;
+
+    // Record the move under the identity of the latest HEAD entry, so it can be undone with `@{1}`
+    let identity = reflog::read_reflog("HEAD")?.pop().map(|entry| entry.committer).unwrap_or(commit.committer);
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    reflog::record_branch_update(&branch, old.as_deref(), &commit_hash, &identity, timestamp, &format!("reset: moving to {}", target))?;
+
+    Ok(commit_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branches::get_current_branch_commit_hash;
+    use crate::commit::commit;
+    use crate::database::store_data;
+    use crate::constants::BLOB;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::TestDir;
+    use std::fs;
+
+    fn index_hash(path: &str) -> Option<String> {
+        load_index().unwrap().into_iter().find(|e| e.path == path).map(|e| e.blob_hash)
+    }
+
+    #[test]
+    fn test_reset_single_path_to_older_commit() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("a.txt", "a v1").unwrap();
+        fs::write("b.txt", "b v1").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        add_file_to_staging("b.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+
+        fs::write("a.txt", "a v2").unwrap();
+        fs::write("b.txt", "b v2").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        add_file_to_staging("b.txt").unwrap();
+        let second = commit("second", "Tester <tester@example.com>").unwrap();
+
+        let b_before = index_hash("b.txt");
+        reset_paths(Some(&first), &["a.txt".to_string()]).unwrap();
+
+        // Only a.txt's entry went back to the first commit
+        assert_eq!(index_hash("a.txt"), Some(store_data(b"a v1", BLOB).unwrap()));
+        assert_eq!(index_hash("b.txt"), b_before);
+
+        // Branch and working tree are untouched
+        assert_eq!(get_current_branch_commit_hash().unwrap(), Some(second));
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v2");
+    }
+
+    #[test]
+    fn test_reset_path_defaults_to_head() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("a.txt", "a v1").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        commit("first", "Tester <tester@example.com>").unwrap();
+        let committed = index_hash("a.txt");
+
+        // Stage a change and a new file, then unstage both
+        fs::write("a.txt", "a v2").unwrap();
+        fs::write("new.txt", "new").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        add_file_to_staging("new.txt").unwrap();
+
+        reset_paths(None, &["a.txt".to_string(), "new.txt".to_string()]).unwrap();
+        assert_eq!(index_hash("a.txt"), committed);
+        assert_eq!(index_hash("new.txt"), None);
+    }
+
+    #[test]
+    fn test_reset_path_waits_for_index_lock() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("a.txt", "a v1").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        commit("first", "Tester <tester@example.com>").unwrap();
+        let committed = index_hash("a.txt");
+        fs::write("a.txt", "a v2").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        let staged = index_hash("a.txt");
+
+        // The staged entry survives until whoever holds the lock lets go
+        let lock = crate::index::lock_index().unwrap();
+        let resetter = std::thread::spawn(|| reset_paths(None, &["a.txt".to_string()]));
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(index_hash("a.txt"), staged);
+        drop(lock);
+
+        resetter.join().unwrap().unwrap();
+        assert_eq!(index_hash("a.txt"), committed);
+    }
+
+    #[test]
+    fn test_reset_hard_to_reflog_entry() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("a.txt", "a v1").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+        fs::write("a.txt", "a v2").unwrap();
+        fs::write("b.txt", "b v2").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        add_file_to_staging("b.txt").unwrap();
+        let second = commit("second", "Tester <tester@example.com>").unwrap();
+
+        // A mistaken hard reset drops the second commit and its files
+        fs::write("a.txt", "uncommitted").unwrap();
+        assert_eq!(reset_hard("HEAD~1").unwrap(), first);
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v1");
+        assert!(!std::path::Path::new("b.txt").exists());
+        assert_eq!(index_hash("b.txt"), None);
+
+        // The reflog still knows where the branch was
+        assert_eq!(resolve_ref("HEAD@{1}").unwrap(), second);
+        assert_eq!(resolve_ref("master@{0}").unwrap(), first);
+        assert_eq!(reset_hard("HEAD@{1}").unwrap(), second);
+        assert_eq!(get_current_branch_commit_hash().unwrap(), Some(second.clone()));
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v2");
+        assert_eq!(fs::read_to_string("b.txt").unwrap(), "b v2");
+        assert_eq!(index_hash("b.txt"), Some(store_data(b"b v2", BLOB).unwrap()));
+
+        assert_eq!(resolve_ref("HEAD@{2}~1").unwrap(), first);
+        assert!(resolve_ref("HEAD@{9}").is_err());
+    }
+
+    #[test]
+    fn test_reset_mixed_keeps_working_tree() {
+        let _test_dir = TestDir::with_repo();
+
+        fs::write("a.txt", "a v1").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+        fs::write("a.txt", "a v2").unwrap();
+        fs::write("b.txt", "b v2").unwrap();
+        add_file_to_staging("a.txt").unwrap();
+        add_file_to_staging("b.txt").unwrap();
+        commit("second", "Tester <tester@example.com>").unwrap();
+
+        assert_eq!(reset_mixed("HEAD~1").unwrap(), first);
+
+        // The branch moved and the index is the first commit's tree
+        assert_eq!(get_current_branch_commit_hash().unwrap(), Some(first.clone()));
+        assert_eq!(load_index().unwrap(), flatten_tree(&read_commit(&first).unwrap().tree).unwrap());
+        assert_eq!(index_hash("b.txt"), None);
+
+        // The working tree still has the second commit's files, now as unstaged changes
+        assert_eq!(fs::read_to_string("a.txt").unwrap(), "a v2");
+        assert_eq!(fs::read_to_string("b.txt").unwrap(), "b v2");
+        let unstaged: Vec<String> = crate::diff::worktree_changes().unwrap().into_iter().map(|change| change.path).collect();
+        assert_eq!(unstaged, vec!["a.txt"]);
+    }
+}
@@ -1,25 +1,87 @@
 use crate::index;
 use crate::database;
-use crate::commit;
 use crate::index::IndexEntry;
+use crate::constants::{BLOB, DIRECTORY_PATH};
+use crate::ignore::IgnoreRules;
 use std::io::{Error, ErrorKind};
+use std::path::Path;
 
 
 pub fn add_file_to_staging(file_path: &str) -> Result<(), Error> {
-    // Ensure the file exists
-    if !std::path::Path::new(file_path).exists() {
+    add_file_to_staging_with(file_path, false)
+}
+
+// Stages a file, or every file beneath a directory. Untracked files the ignore rules match
+// are refused (or, under a directory, skipped) unless `force` is set, and nothing inside
+// the `.rit` directory can ever be added.
+pub fn add_file_to_staging_with(file_path: &str, force: bool) -> Result<(), Error> {
+    // Ensure the file exists; a symbolic link counts even if what it points to does not
+    if Path::new(file_path).symlink_metadata().is_err() {
         return Err(Error::new(ErrorKind::NotFound, "File not found"));
     }
+
+    let path: &str = &index::normalize_path(file_path)?;
+    if path == DIRECTORY_PATH || path.starts_with(&format!("{}/", DIRECTORY_PATH)) {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("'{}' is inside the repository directory", file_path)));
+    }
+    let disk_path = Path::new(if path.is_empty() { "." } else { path });
+    if disk_path.is_dir() && !disk_path.is_symlink() {
+        return add_directory_to_staging(path, force);
+    }
+    if !force && IgnoreRules::load()?.is_ignored(path, false) && !index::load_index()?.iter().any(|entry| entry.path == path) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("The following path is ignored by one of your ignore files:\n{}\nUse -f if you really want to add it.", file_path),
+        ));
+    }
     
-    // Store the file in the object database
-    let blob_hash = database::store_file(file_path)?;
+    let data = database::read_working_file(Path::new(path))?;
+
+    // Store the blob while holding the index lock, so a concurrent gc cannot prune it before
+    // the index refers to it. store_data re-creates it if an earlier gc already removed it.
+    let lock = index::lock_index()?;
+    let blob_hash = database::store_data(&data, BLOB)?;
     
     // Check if the file is already in the latest commit
 
     // Add the file to the index
-    index::add_to_index(file_path, &blob_hash)
+    index::bulk_add_to_index_locked(lock, &[(path, &blob_hash)])
+}
+
+fn add_directory_to_staging(directory: &str, force: bool) -> Result<(), Error> {
+    let files = index::working_files_under(Path::new(directory), force)?;
+
+    // As for a single file, store the blobs while holding the lock so gc cannot prune them
+    let lock = index::lock_index()?;
+    let mut staged = Vec::with_capacity(files.len());
+    for file in &files {
+        staged.push((file.path.as_str(), database::store_file(&file.path)?));
+    }
+    let entries: Vec<(&str, &str)> = staged.iter().map(|(path, hash)| (*path, hash.as_str())).collect();
+    index::bulk_add_to_index_locked(lock, &entries)
 }
 
 pub fn get_staged_entries() -> std::io::Result<Vec<IndexEntry>> {
     index::load_index()
-}
\ No newline at end of file
+}
+
+// Unstages a tracked file and, unless `cached` is set, deletes it from the working tree.
+// A file that was already deleted by hand is still removed from the index.
+pub fn remove_file_from_staging(file_path: &str, cached: bool) -> Result<(), Error> {
+    let path: &str = &index::normalize_path(file_path)?;
+    let lock = index::lock_index()?;
+    let mut entries = index::load_index()?;
+    if !entries.iter().any(|entry| entry.path == path) {
+        return Err(Error::new(ErrorKind::NotFound, format!("pathspec '{}' did not match any tracked files", file_path)));
+    }
+
+    if !cached {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    entries.retain(|entry| entry.path != path);
+    index::save_index_locked(lock, &entries)
+}
@@ -0,0 +1,290 @@
+// Annotated tags: objects naming another object together with a tagger and a message,
+// referred to from `refs/tags/<name>`. The object uses git's layout:
+// `object <key>\ntype <type>\ntag <name>\ntagger <identity> <time> <tz>\n\n<message>`.
+
+use crate::branches;
+use crate::commit::parse_identity;
+use crate::constants::{tags_dir_path, REFS_DIR, TAG, TAGS_DIR};
+use crate::database;
+use crate::date;
+use crate::index;
+use crate::packed_refs;
+use crate::refs::{resolve_ref, validate_ref_name};
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+    // The tagged object and its type
+    pub object: String,
+    pub object_type: String,
+    pub name: String,
+    pub tagger: String,
+    pub timestamp: u64,
+    // The tagger's UTC offset in minutes
+    pub timezone: i32,
+    pub message: String,
+}
+
+impl Tag {
+    pub fn serialize(&self) -> Vec<u8> {
+        format!(
+            "object {}\ntype {}\ntag {}\ntagger {} {} {}\n\n{}",
+            self.object,
+            self.object_type,
+            self.name,
+            self.tagger,
+            self.timestamp,
+            date::format_offset(self.timezone),
+            self.message
+        )
+        .into_bytes()
+    }
+
+    // Parses a tag object, requiring every header
+    pub fn deserialize(data: &[u8]) -> io::Result<Self> {
+        let malformed = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Malformed tag object: {}", reason));
+
+        let text = std::str::from_utf8(data).map_err(|_| malformed("not UTF-8"))?;
+        let (headers, message) = text.split_once("\n\n").unwrap_or((text, ""));
+        let (mut object, mut object_type, mut name, mut tagger) = (None, None, None, None);
+        for line in headers.lines() {
+            let (key, value) = line.split_once(' ').ok_or_else(|| malformed(&format!("bad header line {:?}", line)))?;
+            match key {
+                "object" => object = Some(value.to_string()),
+                "type" => object_type = Some(value.to_string()),
+                "tag" => name = Some(value.to_string()),
+                "tagger" => tagger = Some(parse_identity(value)?),
+                _ => {}
+            }
+        }
+
+        let (tagger, timestamp, timezone) = tagger.ok_or_else(|| malformed("missing tagger"))?;
+        Ok(Tag {
+            object: object.ok_or_else(|| malformed("missing object"))?,
+            object_type: object_type.ok_or_else(|| malformed("missing type"))?,
+            name: name.ok_or_else(|| malformed("missing tag name"))?,
+            tagger,
+            timestamp,
+            timezone,
+            message: message.trim_end().to_string(),
+        })
+    }
+}
+
+// Reads the object a tag ref points at, or nothing when there is no such tag
+pub fn tag_ref(name: &str) -> io::Result<Option<String>> {
+    validate_ref_name(name)?;
+    match fs::read_to_string(tags_dir_path().join(name)) {
+        Ok(key) => Ok(Some(key.trim().to_string())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => packed_refs::packed_ref(&format!("{}/{}/{}", REFS_DIR, TAGS_DIR, name)),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_tag_ref(name: &str, key: &str) -> io::Result<()> {
+    let path = tags_dir_path().join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{}\n", key))
+}
+
+// Lists every tag name under refs/tags, including names like release/v2, sorted
+pub fn list_tags() -> io::Result<Vec<String>> {
+    let mut names = packed_refs::packed_names(TAGS_DIR)?;
+    let tags_dir = tags_dir_path();
+    if tags_dir.exists() {
+        names.extend(branches::ref_names_under(&tags_dir)?);
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+// Refuses to overwrite an existing tag unless forced
+fn check_new_tag(name: &str, force: bool) -> io::Result<()> {
+    if tag_ref(name)?.is_some() && !force {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Tag '{}' already exists (use --force to replace it)", name)));
+    }
+    Ok(())
+}
+
+// Points refs/tags/<name> straight at the commit the revision names, without a tag object
+pub fn create_lightweight_tag(name: &str, rev: &str, force: bool) -> io::Result<String> {
+    check_new_tag(name, force)?;
+    let commit_hash = resolve_ref(rev)?;
+    write_tag_ref(name, &commit_hash)?;
+    Ok(commit_hash)
+}
+
+// Creates an annotated tag object for the revision and points refs/tags/<name> at it
+pub fn create_annotated_tag(name: &str, rev: &str, tagger: &str, message: &str) -> io::Result<String> {
+    create_annotated_tag_with(name, rev, tagger, message, false)
+}
+
+// Like create_annotated_tag; `force` replaces a tag of the same name
+pub fn create_annotated_tag_with(name: &str, rev: &str, tagger: &str, message: &str, force: bool) -> io::Result<String> {
+    check_new_tag(name, force)?;
+    let object = resolve_ref(rev)?;
+    let (object_type, _, _) = database::get_data(&object)?;
+
+    let tag = Tag {
+        object,
+        object_type,
+        name: name.to_string(),
+        tagger: tagger.to_string(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        timezone: date::local_offset()?,
+        message: message.to_string(),
+    };
+    // Hold the index lock until the ref names the new object, so gc cannot prune it in between
+    let _lock = index::lock_index()?;
+    let key = database::store_data(&tag.serialize(), TAG)?;
+    write_tag_ref(name, &key)?;
+    Ok(key)
+}
+
+// Checks an annotated tag, given by tag name or object key: the object must be a well-formed
+// tag whose target exists with the declared type. rit does not sign tags, so there is no
+// signature to check.
+pub fn verify_tag(name: &str) -> io::Result<Tag> {
+    let key = match tag_ref(name).ok().flatten() {
+        Some(key) => key,
+        None if database::object_exists(name) => name.to_string(),
+        None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("Tag '{}' not found", name))),
+    };
+
+    let (object_type, _, data) = database::read_verified(&key)?;
+    if object_type != TAG {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is a {}, not an annotated tag", name, object_type),
+        ));
+    }
+    let tag = Tag::deserialize(&data)?;
+
+    if !database::object_exists(&tag.object) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Tag '{}' points to missing object {}", tag.name, tag.object),
+        ));
+    }
+    let (target_type, _, _) = database::get_data(&tag.object)?;
+    if target_type != tag.object_type {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Tag '{}' declares a {} but {} is a {}", tag.name, tag.object_type, tag.object, target_type),
+        ));
+    }
+
+    Ok(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::commit;
+    use crate::constants::COMMIT;
+    use crate::staging::add_file_to_staging;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn test_annotated_tag_waits_for_index_lock() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "release").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        commit("release", "Tester <tester@example.com>").unwrap();
+
+        // While gc or another writer holds the lock, neither the object nor the ref appears
+        let lock = index::lock_index().unwrap();
+        let objects = database::list_objects().unwrap();
+        let tagger = std::thread::spawn(|| create_annotated_tag("v1", "HEAD", "Tagger <tagger@example.com>", "first"));
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(database::list_objects().unwrap(), objects);
+        assert_eq!(tag_ref("v1").unwrap(), None);
+        drop(lock);
+
+        let key = tagger.join().unwrap().unwrap();
+        assert_eq!(tag_ref("v1").unwrap(), Some(key));
+    }
+
+    #[test]
+    fn test_annotated_tag_verifies() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "release").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let commit_hash = commit("release", "Tester <tester@example.com>").unwrap();
+
+        let key = create_annotated_tag("v1.0", "HEAD", "Tagger <tagger@example.com>", "First release").unwrap();
+        assert_eq!(tag_ref("v1.0").unwrap(), Some(key.clone()));
+        assert_eq!(list_tags().unwrap(), vec!["v1.0"]);
+
+        let tag = verify_tag("v1.0").unwrap();
+        assert_eq!(tag.object, commit_hash);
+        assert_eq!(tag.object_type, COMMIT);
+        assert_eq!(tag.tagger, "Tagger <tagger@example.com>");
+        assert_eq!(tag.message, "First release");
+        assert_eq!(verify_tag(&key).unwrap(), tag);
+        assert_eq!(Tag::deserialize(&tag.serialize()).unwrap(), tag);
+
+        assert!(create_annotated_tag("v1.0", "HEAD", "Tagger <tagger@example.com>", "again").is_err());
+        // A commit is not a tag object
+        assert!(verify_tag(&commit_hash).is_err());
+    }
+
+    #[test]
+    fn test_lightweight_tag() {
+        let _test_dir = TestDir::with_repo();
+        fs::write("file.txt", "one").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let first = commit("first", "Tester <tester@example.com>").unwrap();
+
+        assert_eq!(create_lightweight_tag("v1", "HEAD", false).unwrap(), first);
+        assert_eq!(tag_ref("v1").unwrap(), Some(first.clone()));
+
+        fs::write("file.txt", "two").unwrap();
+        add_file_to_staging("file.txt").unwrap();
+        let second = commit("second", "Tester <tester@example.com>").unwrap();
+
+        // An existing tag is only moved when forced, whether lightweight or annotated
+        let error = create_lightweight_tag("v1", "HEAD", false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert!(create_annotated_tag("v1", "HEAD", "Tagger <tagger@example.com>", "again").is_err());
+        assert_eq!(tag_ref("v1").unwrap(), Some(first));
+        assert_eq!(create_lightweight_tag("v1", "HEAD", true).unwrap(), second);
+        let key = create_annotated_tag_with("v1", "HEAD", "Tagger <tagger@example.com>", "again", true).unwrap();
+        assert_eq!(verify_tag("v1").unwrap().object, second);
+        assert_eq!(tag_ref("v1").unwrap(), Some(key));
+
+        assert!(create_lightweight_tag("bad..name", "HEAD", false).is_err());
+        assert_eq!(list_tags().unwrap(), vec!["v1"]);
+    }
+
+    #[test]
+    fn test_verify_tag_with_missing_target() {
+        let _test_dir = TestDir::with_repo();
+        let tag = Tag {
+            object: "ab".repeat(32),
+            object_type: COMMIT.to_string(),
+            name: "broken".to_string(),
+            tagger: "Tagger <tagger@example.com>".to_string(),
+            timestamp: 1_700_000_000,
+            timezone: 0,
+            message: "points nowhere".to_string(),
+        };
+        let key = database::store_data(&tag.serialize(), TAG).unwrap();
+        write_tag_ref("broken", &key).unwrap();
+
+        let error = verify_tag("broken").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("missing object"));
+
+        // A target of the wrong type is caught too
+        let blob = database::store_data(b"not a commit", crate::constants::BLOB).unwrap();
+        let mislabeled = Tag { object: blob, name: "mislabeled".to_string(), ..tag };
+        write_tag_ref("mislabeled", &database::store_data(&mislabeled.serialize(), TAG).unwrap()).unwrap();
+        assert_eq!(verify_tag("mislabeled").unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,68 @@
+// Shared helpers for tests that need a scratch repository.
+// Every module resolves `.rit` relative to the current directory, so tests that
+// change directory must hold CWD_LOCK to avoid stepping on each other.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tempfile::TempDir;
+
+static CWD_LOCK: Mutex<()> = Mutex::new(());
+
+pub struct TestDir {
+    _temp_dir: TempDir,
+    original_dir: PathBuf,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TestDir {
+    // Switch into a fresh temporary directory
+    pub fn new() -> TestDir {
+        let guard = CWD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let temp_dir = TempDir::new().unwrap();
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(temp_dir.path()).unwrap();
+
+        TestDir {
+            _temp_dir: temp_dir,
+            original_dir,
+            _guard: guard,
+        }
+    }
+
+    // Switch into a fresh temporary directory with an initialized repository
+    pub fn with_repo() -> TestDir {
+        let dir = TestDir::new();
+        crate::repo::rit_init().unwrap();
+        dir
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        // Restore the original directory before the temp dir is removed
+        let _ = env::set_current_dir(&self.original_dir);
+    }
+}
+
+// Writes and stages each file, creating parent directories as needed
+pub fn stage_files(files: &[(&str, &str)]) {
+    for (path, content) in files {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, content).unwrap();
+        crate::staging::add_file_to_staging(path).unwrap();
+    }
+}
+
+// Stages the files and commits them as the test committer, returning the new commit
+pub fn commit_files(files: &[(&str, &str)], message: &str) -> String {
+    stage_files(files);
+    crate::commit::commit(message, "Tester <tester@example.com>").unwrap()
+}
+
+pub fn commit_file(path: &str, content: &str, message: &str) -> String {
+    commit_files(&[(path, content)], message)
+}
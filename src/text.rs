@@ -0,0 +1,76 @@
+// Decodes blob contents for display. Stored bytes are never changed; only what
+// is printed is transcoded.
+
+// Git only looks this far into a file when guessing whether it is binary
+const BINARY_SNIFF_LEN: usize = 8000;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+// Treats content with a NUL byte near the start as binary, like git
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(BINARY_SNIFF_LEN).any(|&b| b == 0)
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Option<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| from_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16(&units).ok()
+}
+
+// Decodes UTF-8 text, honouring a UTF-8 or UTF-16 byte order mark.
+// Returns None for binary or undecodable content.
+pub fn decode_text(bytes: &[u8]) -> Option<String> {
+    if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        return String::from_utf8(rest.to_vec()).ok();
+    }
+    // UTF-16 text is full of NUL bytes, so check its BOM before the binary guard
+    if let Some(rest) = bytes.strip_prefix(UTF16_LE_BOM) {
+        return decode_utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(UTF16_BE_BOM) {
+        return decode_utf16(rest, u16::from_be_bytes);
+    }
+    if is_binary(bytes) {
+        return None;
+    }
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+// Text for printing a blob, or a short notice when it cannot be shown as text
+pub fn display_text(bytes: &[u8]) -> String {
+    decode_text(bytes).unwrap_or_else(|| format!("Binary content ({} bytes) not shown", bytes.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_bom_is_stripped() {
+        let bytes = [UTF8_BOM, "héllo\n".as_bytes()].concat();
+        assert_eq!(decode_text(&bytes), Some("héllo\n".to_string()));
+    }
+
+    #[test]
+    fn test_utf16_is_transcoded() {
+        let text = "héllo wörld\n";
+        let le: Vec<u8> = UTF16_LE_BOM.iter().copied().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        let be: Vec<u8> = UTF16_BE_BOM.iter().copied().chain(text.encode_utf16().flat_map(u16::to_be_bytes)).collect();
+        assert_eq!(decode_text(&le), Some(text.to_string()));
+        assert_eq!(decode_text(&be), Some(text.to_string()));
+    }
+
+    #[test]
+    fn test_binary_and_invalid_content_fall_back() {
+        assert_eq!(decode_text(b"plain"), Some("plain".to_string()));
+        assert_eq!(decode_text(b"\x00\x01\x02"), None);
+        assert_eq!(decode_text(b"\xC3\x28"), None);
+        // An odd number of bytes cannot be UTF-16
+        assert_eq!(decode_text(&[0xFF, 0xFE, 0x41]), None);
+        assert_eq!(display_text(b"\x00\x01"), "Binary content (2 bytes) not shown");
+    }
+}
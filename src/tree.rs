@@ -3,11 +3,10 @@
 use crate::database;
 use crate::index::{IndexEntry};
 use std::collections::{BTreeMap, HashMap};
-use hex;
-use json::iterators::Entries;
 use std::io;
 use std::path::{Path, PathBuf};
 use crate::constants::{BLOB, TREE};
+use crate::hash::HashAlgo;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct TreeEntry {
@@ -21,23 +20,31 @@ pub struct TreeEntry {
 pub fn create_tree(index_entries: &[IndexEntry]) -> io::Result<String> {
     // Start recursive processing from the root directory
     let root_path: PathBuf = PathBuf::new();
-    let tree_hash: String = recursive_tree(&root_path, index_entries)?;
+    let tree_hash: String = recursive_tree(&root_path, index_entries, &mut |tree| database::store_data(tree, TREE))?;
 
     Ok(tree_hash)
 }
 
+// Computes the hash create_tree would return without storing any tree objects
+pub fn hash_tree(index_entries: &[IndexEntry]) -> io::Result<String> {
+    recursive_tree(&PathBuf::new(), index_entries, &mut |tree| database::hash_object(tree, TREE))
+}
+
 
 fn recursive_tree(
     cur_dir: &PathBuf,
-    entries: &[IndexEntry]
+    entries: &[IndexEntry],
+    // Stores (or just hashes) one serialized tree, returning its key
+    write_tree: &mut dyn FnMut(&[u8]) -> io::Result<String>
 ) -> io::Result<String> {
-    let mut tree_entries: HashMap<String, TreeEntry> = HashMap::new();
-    let mut sub_tree_entries: HashMap<String, Vec<IndexEntry>> = HashMap::new();
+    // Ordered maps, so subtrees are always written in the same order
+    let mut tree_entries: BTreeMap<String, TreeEntry> = BTreeMap::new();
+    let mut sub_tree_entries: BTreeMap<String, Vec<IndexEntry>> = BTreeMap::new();
 
     // Filter entries that belong to the current directory
     for entry in entries {
         let entry_path = Path::new(&entry.path);
-        let relative_path = entry_path.strip_prefix(&cur_dir).unwrap();
+        let relative_path = entry_path.strip_prefix(cur_dir).unwrap();
 
         let mut components = relative_path.components();
 
@@ -50,7 +57,7 @@ fn recursive_tree(
                     name.clone(),
                     TreeEntry {
                         mode: entry.mode,
-                        object_type: "blob".to_string(),
+                        object_type: BLOB.to_string(),
                         hash: entry.blob_hash.clone(),
                         name,
                     },
@@ -59,7 +66,7 @@ fn recursive_tree(
                 // Entry belongs to a subdirectory
                 sub_tree_entries
                     .entry(name.clone())
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(entry.clone());
             }
         }
@@ -68,34 +75,35 @@ fn recursive_tree(
     // Process subdirectories recursively
     for (sub_dir_name, sub_entries) in sub_tree_entries {
         let sub_dir_path = cur_dir.join(&sub_dir_name);
-        let sub_tree_hash = recursive_tree(&sub_dir_path, &sub_entries)?;
+        let sub_tree_hash = recursive_tree(&sub_dir_path, &sub_entries, write_tree)?;
         tree_entries.insert(
             sub_dir_name.clone(),
             TreeEntry {
                 mode: 0o040000, // Default mode for a tree
-                object_type: "tree".to_string(),
+                object_type: TREE.to_string(),
                 hash: sub_tree_hash,
                 name: sub_dir_name,
             },
         );
     }
 
-    // Sort 
-    let mut entries = tree_entries.values().cloned().collect::<Vec<TreeEntry>>();
-    entries.sort_by(|a, b| a.name.cmp(&b.name));
-    
+    // Already sorted by name
+    let entries = tree_entries.into_values().collect::<Vec<TreeEntry>>();
+
     // Serialize the tree entries
     let serialized_tree = serialize_tree_entries(&entries)?;
     
     // Store the tree in the database and return its hash
-    let hash = database::store_data(&serialized_tree, TREE)?;
-
-    Ok(hash)
+    write_tree(&serialized_tree)
 }
 
 pub fn read_tree(tree_hash: &str) -> io::Result<Vec<TreeEntry>> {
-    // Get the data for the tree object
-    let (_, _, data) = database::get_data(tree_hash)?;
+    // Get the data for the tree object; the empty tree is well known and need not be stored
+    let data = match database::get_data(tree_hash) {
+        Ok((_, _, data)) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && tree_hash == database::empty_tree_hash()? => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
 
     // Deserialize the tree entries
     let entries = deserialize_tree_entries(&data)?;
@@ -104,6 +112,7 @@ pub fn read_tree(tree_hash: &str) -> io::Result<Vec<TreeEntry>> {
 }
 
 fn serialize_tree_entries(entries: &[TreeEntry]) -> io::Result<Vec<u8>> {
+    let digest_len = HashAlgo::current()?.digest_len();
     let mut data = Vec::new();
 
     for entry in entries {
@@ -114,42 +123,39 @@ fn serialize_tree_entries(entries: &[TreeEntry]) -> io::Result<Vec<u8>> {
         data.push(0); // Null byte
 
         match hex::decode(&entry.hash){
-            Ok(hash_byte) if hash_byte.len() == 32 => data.extend(hash_byte),
-            Ok(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid sha256 hash length")),
+            Ok(hash_byte) if hash_byte.len() == digest_len => data.extend(hash_byte),
+            Ok(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid hash length for {}", entry.name))),
             Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e))
         }
     }
     Ok(data)
 }
 
-fn deserialize_tree_entries(data: &[u8]) -> io::Result<Vec<TreeEntry>> {
+// Parses a tree object, failing on truncated or corrupt data rather than panicking
+pub(crate) fn deserialize_tree_entries(data: &[u8]) -> io::Result<Vec<TreeEntry>> {
+    let digest_len = HashAlgo::current()?.digest_len();
+    let corrupt = |offset: usize, reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Corrupt tree object at byte {}: {}", offset, reason));
     let mut entries: Vec<TreeEntry> = Vec::new();
     let mut i: usize = 0;
 
     while i < data.len() {
-        // Read the mode
-        let mut mode: Vec<u8> = Vec::new();
-        while data[i] != b' ' {
-            mode.push(data[i]);
-            i += 1;
-        }
-        let mode_str = String::from_utf8(mode).expect("Invalid UTF-8");
-        let mode = mode_str.parse::<u32>().expect("Invalid mode");
-        i += 1; // Skip the space
-
-        // Read the name
-        let mut name = Vec::new();
-        while data[i] != 0 {
-            name.push(data[i]);
-            i += 1;
-        }
-        let name = String::from_utf8(name).expect("Invalid UTF-8");
-        i += 1; // Skip the null byte
-
-        // Read the hash (32 bytes for SHA-256)
-        let hash_bytes = &data[i..i + 32];
+        // Read the mode, which ends at a space
+        let space = data[i..].iter().position(|&b| b == b' ').ok_or_else(|| corrupt(i, "missing space after the mode"))?;
+        let mode = std::str::from_utf8(&data[i..i + space])
+            .ok()
+            .and_then(|mode| mode.parse::<u32>().ok())
+            .ok_or_else(|| corrupt(i, "invalid mode"))?;
+        i += space + 1;
+
+        // Read the name, which ends at a null byte
+        let nul = data[i..].iter().position(|&b| b == 0).ok_or_else(|| corrupt(i, "missing null byte after the name"))?;
+        let name = String::from_utf8(data[i..i + nul].to_vec()).map_err(|_| corrupt(i, "name is not valid UTF-8"))?;
+        i += nul + 1;
+
+        // Read the hash, whose length depends on the repository's hash algorithm
+        let hash_bytes = data.get(i..i + digest_len).ok_or_else(|| corrupt(i, "hash is truncated"))?;
         let hash = hex::encode(hash_bytes);
-        i += 32;
+        i += digest_len;
 
         // Determine the object type based on the mode
         let object_type = if mode == 0o040000 {
@@ -170,16 +176,45 @@ fn deserialize_tree_entries(data: &[u8]) -> io::Result<Vec<TreeEntry>> {
     Ok(entries)
 }
 
-pub fn convert_tree_entry_to_hashmap(entries: Vec<TreeEntry>) -> HashMap<String, String> {
+// Recursively lists every blob in a tree as an index entry keyed by its full path
+pub fn flatten_tree(tree_hash: &str) -> io::Result<Vec<IndexEntry>> {
+    let mut entries = Vec::new();
+    flatten_tree_into(tree_hash, &PathBuf::new(), &mut entries)?;
+    Ok(entries)
+}
+
+fn flatten_tree_into(tree_hash: &str, prefix: &Path, entries: &mut Vec<IndexEntry>) -> io::Result<()> {
+    for entry in read_tree(tree_hash)? {
+        let path = prefix.join(&entry.name);
+        if entry.object_type == TREE {
+            flatten_tree_into(&entry.hash, &path, entries)?;
+        } else {
+            entries.push(IndexEntry {
+                mode: entry.mode,
+                blob_hash: entry.hash,
+                path: path.to_string_lossy().to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Maps the full path of every blob beneath the given top-level entries to its hash, so
+// the keys line up with the paths in the index
+pub fn convert_tree_entry_to_hashmap(entries: Vec<TreeEntry>) -> io::Result<HashMap<String, (String, u32)>> {
     let mut result = HashMap::new();
 
     for entry in entries {
-        if entry.object_type == "blob" {
-            result.insert(entry.name, entry.hash);
+        if entry.object_type == TREE {
+            for nested in flatten_tree(&entry.hash)? {
+                result.insert(format!("{}/{}", entry.name, nested.path), (nested.blob_hash, nested.mode));
+            }
+        } else {
+            result.insert(entry.name, (entry.hash, entry.mode));
         }
     }
 
-    result
+    Ok(result)
 }
 
 
@@ -187,6 +222,7 @@ pub fn convert_tree_entry_to_hashmap(entries: Vec<TreeEntry>) -> HashMap<String,
 mod tests {
     use super::*;
     use crate::database;
+    use crate::test_utils::TestDir;
     use std::io;
 
     #[test]
@@ -215,8 +251,97 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_deserialize_corrupt_tree_is_error() -> io::Result<()> {
+        let entries = vec![TreeEntry {
+            mode: 0o100644,
+            object_type: "blob".to_string(),
+            hash: "06bf71aad1d68e12dca830259bf0bea1cd724468fb44e02a6b4fe425fcb11bb0".to_string(),
+            name: "file1.txt".to_string(),
+        }];
+        let serialized = serialize_tree_entries(&entries)?;
+
+        // Cut off mid-hash, as an interrupted write would leave it
+        let truncated = &serialized[..serialized.len() - 10];
+        let err = deserialize_tree_entries(truncated).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("hash is truncated"), "{}", err);
+
+        assert!(deserialize_tree_entries(b"100644").unwrap_err().to_string().contains("missing space"));
+        assert!(deserialize_tree_entries(b"100644 file1.txt").unwrap_err().to_string().contains("missing null byte"));
+        assert!(deserialize_tree_entries(b"10x644 file1.txt\0").unwrap_err().to_string().contains("invalid mode"));
+        for len in 0..serialized.len() {
+            assert_eq!(deserialize_tree_entries(&serialized[..len]).is_err(), len != 0, "{}", len);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten_tree() -> io::Result<()> {
+        let _test_dir = TestDir::new();
+
+        let file1_hash = database::store_data(b"content of file1.txt", BLOB)?;
+        let file2_hash = database::store_data(b"content of file2.txt", BLOB)?;
+        let index_entries = vec![
+            IndexEntry {
+                mode: 0o100644,
+                blob_hash: file1_hash,
+                path: "file1.txt".to_string(),
+            },
+            IndexEntry {
+                mode: 0o100644,
+                blob_hash: file2_hash,
+                path: "dir/subdir/file2.txt".to_string(),
+            },
+        ];
+
+        let tree_hash = create_tree(&index_entries)?;
+        let mut flattened = flatten_tree(&tree_hash)?;
+        flattened.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(flattened, vec![index_entries[1].clone(), index_entries[0].clone()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_writes_are_deterministic() -> io::Result<()> {
+        let hash = "06bf71aad1d68e12dca830259bf0bea1cd724468fb44e02a6b4fe425fcb11bb0".to_string();
+        let paths = ["z/file.txt", "a/b/file.txt", "m/file.txt", "a/file.txt", "top.txt", "a/c/file.txt"];
+        let entries: Vec<IndexEntry> = paths
+            .iter()
+            .map(|path| IndexEntry { mode: 0o100644, blob_hash: hash.clone(), path: path.to_string() })
+            .collect();
+
+        let build = |entries: &[IndexEntry]| -> io::Result<(String, Vec<String>)> {
+            let mut writes = Vec::new();
+            let root = recursive_tree(&PathBuf::new(), entries, &mut |tree| {
+                let key = database::hash_object(tree, TREE)?;
+                writes.push(key.clone());
+                Ok(key)
+            })?;
+            Ok((root, writes))
+        };
+
+        let (first_root, first_writes) = build(&entries)?;
+        for _ in 0..5 {
+            assert_eq!(build(&entries)?, (first_root.clone(), first_writes.clone()));
+        }
+
+        // Input order does not matter either
+        let mut reversed = entries.clone();
+        reversed.reverse();
+        assert_eq!(build(&reversed)?, (first_root.clone(), first_writes.clone()));
+
+        // Subtrees are written depth-first in name order, the root last
+        assert_eq!(first_writes.len(), 6);
+        assert_eq!(first_writes.last(), Some(&first_root));
+        Ok(())
+    }
+
     #[test]
     fn test_write_and_read_tree() -> io::Result<()> {
+        let _test_dir = TestDir::new();
+
         // Prepare mock index entries
         let file1_hash = database::store_data(b"content of file1.txt", BLOB)?;
         let file2_hash = database::store_data(b"content of file2.txt", BLOB)?;
@@ -254,7 +379,7 @@ mod tests {
         // Check root entries
         assert_eq!(root_entries.len(), 2);
 
-        let expected_root_entries = vec![
+        let expected_root_entries = [
             TreeEntry {
                 mode: 0o040000,
                 object_type: "tree".to_string(),
@@ -285,7 +410,7 @@ mod tests {
         // Check 'dir' entries
         assert_eq!(dir_entries.len(), 2);
 
-        let expected_dir_entries = vec![
+        let expected_dir_entries = [
             TreeEntry {
                 mode: 0o040000,
                 object_type: "tree".to_string(),
@@ -367,7 +492,7 @@ mod tests {
         // Check updated root entries
         assert_eq!(updated_root_entries.len(), 3);
 
-        let expected_updated_root_entries = vec![
+        let expected_updated_root_entries = [
             TreeEntry {
                 mode: 0o040000,
                 object_type: "tree".to_string(),
@@ -410,7 +535,7 @@ mod tests {
         // Check updated 'dir' entries
         assert_eq!(updated_dir_entries.len(), 3);
 
-        let expected_updated_dir_entries = vec![
+        let expected_updated_dir_entries = [
             TreeEntry {
                 mode: 0o040000,
                 object_type: "tree".to_string(),
@@ -0,0 +1,61 @@
+// Runs `rit add` on directories through the CLI to check what gets staged, and `rit ls-files` to list it.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+fn staged_paths(dir: &std::path::Path) -> String {
+    String::from_utf8_lossy(&rit(dir, &["status", "--porcelain=v2"]).stdout)
+        .lines()
+        .filter(|line| line.starts_with("1 A"))
+        .map(|line| line.rsplit(' ').next().unwrap().to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[test]
+fn test_add_directory_stages_files_beneath_it() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::create_dir_all(dir.join("src/nested")).unwrap();
+    fs::write(dir.join("src/lib.rs"), "lib\n").unwrap();
+    fs::write(dir.join("src/nested/mod.rs"), "mod\n").unwrap();
+    fs::write(dir.join("src/debug.log"), "noise\n").unwrap();
+    fs::write(dir.join("top.txt"), "top\n").unwrap();
+    fs::write(dir.join(".ritignore"), "*.log\n").unwrap();
+
+    assert!(rit(dir, &["add", "src"]).status.success());
+    assert_eq!(staged_paths(dir), "src/lib.rs,src/nested/mod.rs");
+
+    // `add .` stages the rest of the tree, still leaving ignored files and .rit alone
+    assert!(rit(dir, &["add", "."]).status.success());
+    assert_eq!(staged_paths(dir), ".ritignore,src/lib.rs,src/nested/mod.rs,top.txt");
+
+    assert!(rit(dir, &["add", "-f", "src"]).status.success());
+    assert_eq!(staged_paths(dir), ".ritignore,src/debug.log,src/lib.rs,src/nested/mod.rs,top.txt");
+}
+
+#[test]
+fn test_ls_files_lists_the_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::create_dir_all(dir.join("docs")).unwrap();
+    fs::write(dir.join("docs/guide.md"), "guide\n").unwrap();
+    fs::write(dir.join("b.txt"), "b\n").unwrap();
+    fs::write(dir.join("untracked.txt"), "not staged\n").unwrap();
+    rit(dir, &["add", "docs/guide.md"]);
+    rit(dir, &["add", "b.txt"]);
+
+    assert_eq!(String::from_utf8_lossy(&rit(dir, &["ls-files"]).stdout), "b.txt\ndocs/guide.md\n");
+    assert_eq!(String::from_utf8_lossy(&rit(dir, &["ls-files", "-z"]).stdout), "b.txt\0docs/guide.md\0");
+
+    let blob = String::from_utf8_lossy(&rit(dir, &["hash-object", "b.txt"]).stdout).trim().to_string();
+    let staged = String::from_utf8_lossy(&rit(dir, &["ls-files", "--stage"]).stdout).to_string();
+    assert!(staged.starts_with(&format!("100644 {} 0\tb.txt\n", blob)), "{}", staged);
+    assert_eq!(staged.lines().count(), 2);
+}
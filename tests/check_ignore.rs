@@ -0,0 +1,24 @@
+// Runs `rit check-ignore` through the CLI against a `.ritignore`.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+#[test]
+fn test_check_ignore_reports_matching_pattern() {
+    let temp_dir = TempDir::new().unwrap();
+    assert!(rit(temp_dir.path(), &["init"]).status.success());
+    fs::write(temp_dir.path().join(".ritignore"), "# logs\n*.log\n").unwrap();
+
+    let output = rit(temp_dir.path(), &["check-ignore", "-v", "app.log", "main.rs"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), ".ritignore:2:*.log\tapp.log\n");
+
+    // Nothing ignored: no output and a non-zero exit
+    let output = rit(temp_dir.path(), &["check-ignore", "main.rs"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,112 @@
+// Runs `rit commit` through the CLI to check what --quiet and --verbose print where the committer comes from and how an author is recorded.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::{rit, rit_ok, rit_stdout};
+
+fn repo_with_staged_file() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    rit_ok(temp_dir.path(), &["init"]);
+    fs::write(temp_dir.path().join("file.txt"), "first line\n").unwrap();
+    rit_ok(temp_dir.path(), &["add", "file.txt"]);
+    temp_dir
+}
+
+#[test]
+fn test_commit_quiet_prints_nothing() {
+    let repo = repo_with_staged_file();
+    let output = rit_ok(repo.path(), &["commit", "--quiet", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_commit_verbose_shows_diff() {
+    let repo = repo_with_staged_file();
+    let output = rit_ok(repo.path(), &["commit", "--verbose", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("diff --rit a/file.txt b/file.txt\n"));
+    assert!(stdout.contains("+first line\n"));
+}
+
+#[test]
+fn test_commit_uses_configured_committer() {
+    let repo = repo_with_staged_file();
+    let output = rit(repo.path(), &["commit", "-m", "first"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rit config user.name"));
+
+    rit_ok(repo.path(), &["config", "user.name", "Configured"]);
+    rit_ok(repo.path(), &["config", "user.email", "configured@example.com"]);
+    let commit_hash = String::from_utf8_lossy(&rit_ok(repo.path(), &["commit", "-m", "first"]).stdout).trim().to_string();
+    let output = rit_ok(repo.path(), &["cat-file", "-p", &commit_hash]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\ncommitter Configured <configured@example.com>\n"));
+}
+
+#[test]
+fn test_commit_with_author() {
+    let repo = repo_with_staged_file();
+    rit_ok(repo.path(), &["commit", "-m", "first", "-c", "Tester <tester@example.com>", "--author", "Author <author@example.com>"]);
+    let stdout = String::from_utf8_lossy(&rit_ok(repo.path(), &["log"]).stdout).to_string();
+    assert!(stdout.contains("\nAuthor:    Author <author@example.com>\nCommitter: Tester <tester@example.com>\n"), "{}", stdout);
+}
+
+// A tar archive holding one regular file
+fn tar_with_file(name: &str, content: &[u8]) -> Vec<u8> {
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", content.len()).as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    let mut archive = header.to_vec();
+    archive.extend_from_slice(content);
+    archive.resize(archive.len().div_ceil(512) * 512 + 1024, 0);
+    archive
+}
+
+#[test]
+fn test_import_archive_uses_configured_committer() {
+    let repo = TempDir::new().unwrap();
+    rit_ok(repo.path(), &["init"]);
+    fs::write(repo.path().join("import.tar"), tar_with_file("hello.txt", b"hello\n")).unwrap();
+
+    // Without --committer or a configured user, nothing is imported
+    let output = rit(repo.path(), &["import-archive", "import.tar", "-m", "import"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rit config user.name"));
+    assert!(rit_ok(repo.path(), &["ls-files"]).stdout.is_empty());
+
+    rit_ok(repo.path(), &["config", "user.name", "Configured"]);
+    rit_ok(repo.path(), &["config", "user.email", "configured@example.com"]);
+    let output = rit_ok(repo.path(), &["import-archive", "import.tar", "-m", "import"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let commit_hash = stdout.lines().nth(1).unwrap();
+    let output = rit_ok(repo.path(), &["cat-file", "-p", commit_hash]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\ncommitter Configured <configured@example.com>\n"));
+}
+
+#[test]
+fn test_commit_survives_failing_auto_gc() {
+    let repo = repo_with_staged_file();
+    rit_ok(repo.path(), &["config", "gc.auto", "lots"]);
+
+    // The commit is made and reported; the broken gc only earns a warning
+    let output = rit_ok(repo.path(), &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("warning: auto gc failed"));
+    let commit_hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    assert!(rit_stdout(repo.path(), &["log"]).contains(&commit_hash));
+
+    // Quiet commits do not try at all
+    fs::write(repo.path().join("file.txt"), "second line\n").unwrap();
+    rit_ok(repo.path(), &["add", "file.txt"]);
+    let output = rit_ok(repo.path(), &["commit", "--quiet", "-m", "second", "-c", "Tester <tester@example.com>"]);
+    assert!(output.stderr.is_empty());
+}
@@ -0,0 +1,26 @@
+// Helpers shared by the integration tests, which drive the rit binary in a scratch directory.
+#![allow(dead_code)]
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+// Runs rit in the directory and returns whatever it did
+pub fn rit(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rit"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+// Runs rit in the directory, failing the test unless it succeeds
+pub fn rit_ok(dir: &Path, args: &[&str]) -> Output {
+    let output = rit(dir, args);
+    assert!(output.status.success(), "rit {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+// Runs rit in the directory, failing the test unless it succeeds, and returns its stdout
+pub fn rit_stdout(dir: &Path, args: &[&str]) -> String {
+    String::from_utf8_lossy(&rit_ok(dir, args).stdout).to_string()
+}
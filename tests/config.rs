@@ -0,0 +1,30 @@
+// Runs `rit config` through the CLI to check options are set, read back and removed.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+#[test]
+fn test_config_get_set_unset() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+
+    assert_eq!(rit(dir, &["config", "user.name"]).status.code(), Some(1));
+    assert!(rit(dir, &["config", "user.name", "Tester"]).status.success());
+    assert!(rit(dir, &["config", "user.email", "tester@example.com"]).status.success());
+    assert!(fs::read_to_string(dir.join(".rit/config")).unwrap().contains("[user]\n\tname = Tester\n"));
+
+    let output = rit(dir, &["config", "user.name"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Tester\n");
+    let output = rit(dir, &["config", "--list"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "core.repositoryformatversion=0\nuser.name=Tester\nuser.email=tester@example.com\n");
+
+    assert!(rit(dir, &["config", "--unset", "user.email"]).status.success());
+    assert_eq!(rit(dir, &["config", "--unset", "user.email"]).status.code(), Some(5));
+    assert_eq!(rit(dir, &["config", "user.email"]).status.code(), Some(1));
+    assert!(!rit(dir, &["config", "nodot", "value"]).status.success());
+}
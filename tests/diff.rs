@@ -0,0 +1,37 @@
+// Runs plain `rit diff` through the CLI to check the patches it prints.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+#[test]
+fn test_diff_prints_working_tree_patches() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("notes.txt"), "one\ntwo\nthree\n").unwrap();
+    fs::write(dir.join("data.bin"), [0u8, 159, 146, 150]).unwrap();
+    rit(dir, &["add", "notes.txt"]);
+    rit(dir, &["add", "data.bin"]);
+
+    // Nothing has changed since staging
+    let output = rit(dir, &["diff"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+
+    fs::write(dir.join("notes.txt"), "one\n2\nthree\n").unwrap();
+    fs::write(dir.join("data.bin"), [1u8, 159, 146, 150]).unwrap();
+    let stdout = String::from_utf8_lossy(&rit(dir, &["diff"]).stdout).to_string();
+    assert!(stdout.contains("diff --rit a/notes.txt b/notes.txt\n--- a/notes.txt\n+++ b/notes.txt\n"), "{}", stdout);
+    assert!(stdout.contains("\n one\n-two\n+2\n three\n"), "{}", stdout);
+    assert!(stdout.contains("Binary files a/data.bin and b/data.bin differ\n"), "{}", stdout);
+
+    // Staged changes show with --cached, and no longer in the working tree diff
+    rit(dir, &["add", "notes.txt"]);
+    assert!(!String::from_utf8_lossy(&rit(dir, &["diff"]).stdout).contains("notes.txt"));
+    let stdout = String::from_utf8_lossy(&rit(dir, &["diff", "--cached"]).stdout).to_string();
+    assert!(stdout.contains("--- /dev/null\n+++ b/notes.txt\n"), "{}", stdout);
+}
@@ -0,0 +1,49 @@
+// Runs `rit diff --check` through the CLI to check whitespace reports and the exit status.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+#[test]
+fn test_diff_check_reports_trailing_whitespace() {
+    let temp_dir = TempDir::new().unwrap();
+    rit(temp_dir.path(), &["init"]);
+    fs::write(temp_dir.path().join("notes.txt"), "clean\n").unwrap();
+    rit(temp_dir.path(), &["add", "notes.txt"]);
+    rit(temp_dir.path(), &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+
+    // Nothing added yet, so the check passes
+    let output = rit(temp_dir.path(), &["diff", "--check"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    fs::write(temp_dir.path().join("notes.txt"), "clean\ntrailing   \n").unwrap();
+    let output = rit(temp_dir.path(), &["diff", "--check"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "notes.txt:2: trailing whitespace\n+trailing   \n");
+
+    // Once staged, the problem shows up in the cached comparison instead
+    rit(temp_dir.path(), &["add", "notes.txt"]);
+    assert!(rit(temp_dir.path(), &["diff", "--check"]).status.success());
+    assert_eq!(rit(temp_dir.path(), &["diff", "--cached", "--check"]).status.code(), Some(2));
+}
+
+#[test]
+fn test_diff_check_skips_binary_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    rit(temp_dir.path(), &["init"]);
+    fs::write(temp_dir.path().join("data.dat"), "one\n").unwrap();
+    rit(temp_dir.path(), &["add", "data.dat"]);
+    rit(temp_dir.path(), &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    fs::write(temp_dir.path().join("data.dat"), "two   \n").unwrap();
+    assert_eq!(rit(temp_dir.path(), &["diff", "--check"]).status.code(), Some(2));
+
+    // Once the attributes mark the file binary its lines are not checked
+    fs::write(temp_dir.path().join(".ritattributes"), "*.dat binary\n").unwrap();
+    let output = rit(temp_dir.path(), &["diff", "--check"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
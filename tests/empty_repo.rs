@@ -0,0 +1,57 @@
+// Runs each command against a freshly initialized repository with no commits.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+fn init_repo() -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    assert!(rit(temp_dir.path(), &["init"]).status.success());
+    temp_dir
+}
+
+#[test]
+fn test_status_without_commits() {
+    let repo = init_repo();
+    let output = rit(repo.path(), &["status"]);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No commits yet"));
+}
+
+#[test]
+fn test_log_without_commits() {
+    let repo = init_repo();
+    let output = rit(repo.path(), &["log"]);
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_gc_without_commits() {
+    let repo = init_repo();
+    assert!(rit(repo.path(), &["gc"]).status.success());
+}
+
+#[test]
+fn test_first_commit_has_no_parent() {
+    let repo = init_repo();
+    fs::write(repo.path().join("file.txt"), "content").unwrap();
+    assert!(rit(repo.path(), &["add", "file.txt"]).status.success());
+
+    let output = rit(repo.path(), &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    assert!(output.status.success());
+    let commit_hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let output = rit(repo.path(), &["cat-file", &commit_hash]);
+    let commit = String::from_utf8_lossy(&output.stdout);
+    assert!(commit.starts_with("tree "));
+    assert!(!commit.contains("parent "));
+
+    let output = rit(repo.path(), &["log"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&commit_hash));
+}
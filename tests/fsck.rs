@@ -0,0 +1,37 @@
+// Runs `rit fsck` through the CLI to check what it reports and its exit status.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+#[test]
+fn test_fsck_exit_status() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("file.txt"), "content\n").unwrap();
+    rit(dir, &["add", "file.txt"]);
+    rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    let output = rit(dir, &["fsck"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    // Damage the blob: it is reported as corrupt
+    let blob = String::from_utf8_lossy(&rit(dir, &["ls-tree", "HEAD"]).stdout).split('\t').nth(1).unwrap().to_string();
+    let object_path = dir.join(".rit/objects").join(&blob[..2]).join(&blob[2..]);
+    fs::write(&object_path, b"garbage").unwrap();
+    let output = rit(dir, &["fsck"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("corrupt {}\n", blob)));
+
+    // Remove it: the tree and the index now refer to a missing object
+    fs::remove_file(&object_path).unwrap();
+    let output = rit(dir, &["fsck"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("missing {} (referenced by tree ", blob)), "{}", stdout);
+    assert!(stdout.contains(&format!("missing {} (referenced by index entry file.txt)\n", blob)), "{}", stdout);
+}
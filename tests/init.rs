@@ -0,0 +1,90 @@
+// Runs `rit init` through the CLI to check how the initial branch is chosen.
+
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn test_init_default_branch_from_environment() {
+    let temp_dir = TempDir::new().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rit"))
+        .arg("init")
+        .env("RIT_DEFAULT_BRANCH", "main")
+        .current_dir(temp_dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let head = fs::read_to_string(temp_dir.path().join(".rit/HEAD")).unwrap();
+    assert_eq!(head, "ref: refs/heads/main\n");
+}
+
+#[test]
+fn test_init_initial_branch_flag_overrides_environment() {
+    let temp_dir = TempDir::new().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rit"))
+        .args(["init", "--initial-branch", "trunk"])
+        .env("RIT_DEFAULT_BRANCH", "main")
+        .current_dir(temp_dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let head = fs::read_to_string(temp_dir.path().join(".rit/HEAD")).unwrap();
+    assert_eq!(head, "ref: refs/heads/trunk\n");
+}
+
+#[test]
+fn test_init_falls_back_to_master() {
+    let temp_dir = TempDir::new().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rit"))
+        .arg("init")
+        .env_remove("RIT_DEFAULT_BRANCH")
+        .current_dir(temp_dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let head = fs::read_to_string(temp_dir.path().join(".rit/HEAD")).unwrap();
+    assert_eq!(head, "ref: refs/heads/master\n");
+}
+
+#[test]
+fn test_init_writes_description_and_exclude() {
+    let temp_dir = TempDir::new().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rit"))
+        .arg("init")
+        .current_dir(temp_dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let description = fs::read_to_string(temp_dir.path().join(".rit/description")).unwrap();
+    assert_eq!(description, "Unnamed repository; edit this file 'description' to name the repository.\n");
+    let exclude = fs::read_to_string(temp_dir.path().join(".rit/info/exclude")).unwrap();
+    assert_eq!(exclude, "");
+}
+
+#[test]
+fn test_commands_refuse_newer_repository_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rit"))
+        .arg("init")
+        .current_dir(temp_dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let config_path = temp_dir.path().join(".rit/config");
+    let config = fs::read_to_string(&config_path).unwrap();
+    assert!(config.contains("repositoryformatversion = 0"));
+    fs::write(&config_path, config.replace("repositoryformatversion = 0", "repositoryformatversion = 2")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rit"))
+        .arg("status")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Repository format version 2 is newer"));
+}
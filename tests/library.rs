@@ -0,0 +1,61 @@
+// Drives rit purely through its library API, the way an embedding tool would.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tempfile::TempDir;
+
+use rit::{commit, repo, staging};
+
+// The library resolves `.rit` against the current directory, which is shared by every
+// test in this binary; one test at a time moves into its repository and moves back on
+// drop, even when an assertion fails.
+static CWD_LOCK: Mutex<()> = Mutex::new(());
+
+struct CwdGuard {
+    original_dir: PathBuf,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl CwdGuard {
+    fn enter(dir: &Path) -> CwdGuard {
+        let lock = CWD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(dir).unwrap();
+        CwdGuard { original_dir, _lock: lock }
+    }
+}
+
+impl Drop for CwdGuard {
+    fn drop(&mut self) {
+        let _ = env::set_current_dir(&self.original_dir);
+    }
+}
+
+#[test]
+fn test_init_add_commit_log() {
+    let temp_dir = TempDir::new().unwrap();
+    let _cwd = CwdGuard::enter(temp_dir.path());
+
+    repo::rit_init().unwrap();
+
+    // First commit
+    fs::write("hello.txt", "hello").unwrap();
+    staging::add_file_to_staging("hello.txt").unwrap();
+    let first = commit::commit("First commit", "Tester <tester@example.com>").unwrap();
+
+    // Second commit on top of the first
+    fs::write("hello.txt", "hello again").unwrap();
+    staging::add_file_to_staging("hello.txt").unwrap();
+    let second = commit::commit("Second commit", "Tester <tester@example.com>").unwrap();
+
+    // Log walks newest to oldest
+    let history = commit::log().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].0, second);
+    assert_eq!(history[0].1.message, "Second commit");
+    assert_eq!(history[0].1.parent, Some(first.clone()));
+    assert_eq!(history[1].0, first);
+    assert_eq!(history[1].1.parent, None);
+}
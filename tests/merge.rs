@@ -0,0 +1,81 @@
+// Runs `rit merge` through the CLI: a clean merge commits, and a conflicting one shows in status and is aborted.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+fn commit_file(dir: &std::path::Path, content: &str, message: &str) {
+    fs::write(dir.join("notes.txt"), content).unwrap();
+    assert!(rit(dir, &["add", "notes.txt"]).status.success());
+    assert!(rit(dir, &["commit", "-m", message, "-c", "Tester <tester@example.com>"]).status.success());
+}
+
+// Points HEAD at a branch and restores the file from it
+fn switch_to(dir: &std::path::Path, branch: &str) {
+    fs::write(dir.join(".rit/HEAD"), format!("ref: refs/heads/{}\n", branch)).unwrap();
+    assert!(rit(dir, &["checkout", branch, "--index", "--", "notes.txt"]).status.success());
+}
+
+#[test]
+fn test_conflicting_merge_then_abort() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    commit_file(dir, "shared\nbase\n", "base");
+    assert!(rit(dir, &["branch", "topic"]).status.success());
+    commit_file(dir, "shared\nours\n", "ours");
+    switch_to(dir, "topic");
+    commit_file(dir, "shared\ntheirs\n", "theirs");
+    switch_to(dir, "master");
+
+    let output = rit(dir, &["merge", "topic"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("CONFLICT (content): Merge conflict in notes.txt"));
+    assert!(dir.join(".rit/MERGE_HEAD").exists());
+    assert!(fs::read_to_string(dir.join(".rit/MERGE_MSG")).unwrap().starts_with("Merge branch 'topic'\n"));
+    assert!(fs::read_to_string(dir.join("notes.txt")).unwrap().contains("<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\n"));
+
+    let status = String::from_utf8_lossy(&rit(dir, &["status"]).stdout).to_string();
+    assert!(status.contains("You have unmerged paths."));
+    assert!(status.contains("notes.txt"));
+
+    // Committing now would lose the merged branch, so it is refused
+    assert!(!rit(dir, &["commit", "-m", "merge", "-c", "Tester <tester@example.com>"]).status.success());
+
+    assert!(rit(dir, &["merge", "--abort"]).status.success());
+    assert!(!dir.join(".rit/MERGE_HEAD").exists());
+    assert!(!dir.join(".rit/MERGE_MSG").exists());
+    assert_eq!(fs::read_to_string(dir.join("notes.txt")).unwrap(), "shared\nours\n");
+    let status = String::from_utf8_lossy(&rit(dir, &["status"]).stdout).to_string();
+    assert!(!status.contains("unmerged"));
+    assert!(rit(dir, &["diff"]).stdout.is_empty());
+
+    // With nothing in progress there is nothing to abort
+    assert!(!rit(dir, &["merge", "--abort"]).status.success());
+}
+
+#[test]
+fn test_clean_merge_creates_merge_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    commit_file(dir, "one\ntwo\nthree\n", "base");
+    assert!(rit(dir, &["branch", "topic"]).status.success());
+    commit_file(dir, "ONE\ntwo\nthree\n", "ours");
+    switch_to(dir, "topic");
+    commit_file(dir, "one\ntwo\nTHREE\n", "theirs");
+    switch_to(dir, "master");
+
+    let output = rit(dir, &["merge", "topic", "-c", "Tester <tester@example.com>"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Merge made by commit "));
+    assert_eq!(fs::read_to_string(dir.join("notes.txt")).unwrap(), "ONE\ntwo\nTHREE\n");
+    assert!(!dir.join(".rit/MERGE_HEAD").exists());
+
+    let log = String::from_utf8_lossy(&rit(dir, &["log", "--merges"]).stdout).to_string();
+    assert!(log.contains("\nMerge:     "), "{}", log);
+    assert!(log.contains("Merge branch 'topic'"), "{}", log);
+}
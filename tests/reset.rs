@@ -0,0 +1,44 @@
+// Runs `rit reset` through the CLI to check it unstages what status says it will.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+fn summary(dir: &std::path::Path) -> String {
+    String::from_utf8_lossy(&rit(dir, &["status"]).stdout).lines().last().unwrap_or("").to_string()
+}
+
+#[test]
+fn test_reset_head_unstages_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("a.txt"), "a\n").unwrap();
+    fs::write(dir.join("b.txt"), "b\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    rit(dir, &["add", "b.txt"]);
+
+    // Before the first commit a bare reset unstages everything
+    assert!(rit(dir, &["reset"]).status.success());
+    assert_eq!(summary(dir), "0 staged changes, 0 unstaged changes, 2 untracked files");
+
+    rit(dir, &["add", "a.txt"]);
+    rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+
+    // The hint status prints works: a modified file goes back to HEAD's version, a new one is dropped
+    fs::write(dir.join("a.txt"), "a changed\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    rit(dir, &["add", "b.txt"]);
+    assert!(String::from_utf8_lossy(&rit(dir, &["status"]).stdout).contains("(use \"rit reset HEAD <file>...\" to unstage)"));
+    let output = rit(dir, &["reset", "HEAD", "a.txt", "b.txt"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Unstaged changes after reset:\nM\ta.txt\n");
+    assert_eq!(summary(dir), "0 staged changes, 1 unstaged change, 1 untracked file");
+
+    rit(dir, &["add", "a.txt"]);
+    assert!(rit(dir, &["reset"]).status.success());
+    assert_eq!(summary(dir), "0 staged changes, 1 unstaged change, 1 untracked file");
+}
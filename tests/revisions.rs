@@ -0,0 +1,203 @@
+// Runs object-reading and plumbing commands through the CLI with revision names instead of raw keys.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::{rit, rit_stdout};
+
+#[test]
+fn test_cat_file_and_ls_tree_accept_head() {
+    let temp_dir = TempDir::new().unwrap();
+    rit_stdout(temp_dir.path(), &["init"]);
+    fs::write(temp_dir.path().join("file.txt"), "content").unwrap();
+    rit_stdout(temp_dir.path(), &["add", "file.txt"]);
+    let commit_hash = rit_stdout(temp_dir.path(), &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+
+    let commit = rit_stdout(temp_dir.path(), &["cat-file", "HEAD"]);
+    assert!(commit.starts_with("tree "));
+    assert!(commit.contains("first"));
+    assert_eq!(commit, rit_stdout(temp_dir.path(), &["cat-file", commit_hash.trim()]));
+
+    assert_eq!(rit_stdout(temp_dir.path(), &["cat-file", "-t", "HEAD"]), "commit\n");
+    assert_eq!(rit_stdout(temp_dir.path(), &["cat-file", "-s", "HEAD"]), format!("{}\n", commit.len() - 1));
+
+    let pretty = rit_stdout(temp_dir.path(), &["cat-file", "-p", "HEAD"]);
+    assert!(pretty.contains("\ncommitter Tester <tester@example.com>\ndate      "));
+    assert!(pretty.ends_with("\n\nfirst\n"));
+
+    let listing = rit_stdout(temp_dir.path(), &["ls-tree", "HEAD"]);
+    assert!(listing.contains("blob"));
+    assert!(listing.ends_with("\tfile.txt\n"));
+    assert_eq!(listing, rit_stdout(temp_dir.path(), &["ls-tree", "master"]));
+}
+
+#[test]
+fn test_ls_tree_name_only_and_nul_terminated() {
+    let temp_dir = TempDir::new().unwrap();
+    rit_stdout(temp_dir.path(), &["init"]);
+    fs::create_dir(temp_dir.path().join("docs")).unwrap();
+    fs::write(temp_dir.path().join("docs/guide.md"), "guide").unwrap();
+    fs::write(temp_dir.path().join("line\nbreak.txt"), "odd name").unwrap();
+    rit_stdout(temp_dir.path(), &["add", "docs/guide.md"]);
+    rit_stdout(temp_dir.path(), &["add", "line\nbreak.txt"]);
+    rit_stdout(temp_dir.path(), &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+
+    assert_eq!(rit_stdout(temp_dir.path(), &["ls-tree", "--name-only", "HEAD"]), "docs\nline\nbreak.txt\n");
+    assert_eq!(rit_stdout(temp_dir.path(), &["ls-tree", "--name-only", "-z", "HEAD"]), "docs\0line\nbreak.txt\0");
+
+    let entries: Vec<String> = rit_stdout(temp_dir.path(), &["ls-tree", "-z", "HEAD"])
+        .split_terminator('\0')
+        .map(str::to_string)
+        .collect();
+    assert_eq!(entries.len(), 2);
+    assert!(entries[0].starts_with("040000 tree\t") && entries[0].ends_with("\tdocs"));
+    assert!(entries[1].ends_with("\tline\nbreak.txt"));
+}
+
+#[test]
+fn test_cat_file_type_and_size() {
+    let temp_dir = TempDir::new().unwrap();
+    rit_stdout(temp_dir.path(), &["init"]);
+    fs::write(temp_dir.path().join("file.txt"), "content").unwrap();
+    let blob = rit_stdout(temp_dir.path(), &["hash-object", "file.txt"]);
+    rit_stdout(temp_dir.path(), &["add", "file.txt"]);
+    rit_stdout(temp_dir.path(), &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    let listing = rit_stdout(temp_dir.path(), &["ls-tree", "HEAD"]);
+    let tree = rit_stdout(temp_dir.path(), &["cat-file", "HEAD"]).lines().next().unwrap().strip_prefix("tree ").unwrap().to_string();
+
+    assert_eq!(rit_stdout(temp_dir.path(), &["cat-file", "-t", blob.trim()]), "blob\n");
+    assert_eq!(rit_stdout(temp_dir.path(), &["cat-file", "-s", blob.trim()]), "7\n");
+    assert_eq!(rit_stdout(temp_dir.path(), &["cat-file", "-t", &tree]), "tree\n");
+    assert!(listing.contains(blob.trim()));
+}
+
+#[test]
+fn test_show_commit_and_its_changes() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit_stdout(dir, &["init"]);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    rit_stdout(dir, &["add", "a.txt"]);
+    let first = rit_stdout(dir, &["commit", "-m", "first\n\nbody", "-c", "Tester <tester@example.com>"]).trim().to_string();
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    fs::write(dir.join("b.txt"), "new\n").unwrap();
+    rit_stdout(dir, &["add", "a.txt"]);
+    rit_stdout(dir, &["add", "b.txt"]);
+    rit_stdout(dir, &["commit", "-m", "second", "-c", "Tester <tester@example.com>"]);
+
+    let stdout = rit_stdout(dir, &["show"]);
+    assert!(stdout.starts_with("commit "), "{}", stdout);
+    assert!(stdout.contains(&format!("\nparent {}\nCommitter: Tester <tester@example.com>\nDate:      ", first)), "{}", stdout);
+    assert!(stdout.contains("\n\n    second\n\n"), "{}", stdout);
+    assert!(stdout.contains("--- a/a.txt\n+++ b/a.txt\n"), "{}", stdout);
+    assert!(stdout.contains("-one\n+two\n"), "{}", stdout);
+    assert!(stdout.contains("--- /dev/null\n+++ b/b.txt\n"), "{}", stdout);
+
+    // The root commit has no parent line and shows its files as added
+    let stdout = rit_stdout(dir, &["show", "--name-status", &first]);
+    assert!(!stdout.contains("\nparent "), "{}", stdout);
+    assert!(stdout.contains("\n    first\n    \n    body\n"), "{}", stdout);
+    assert!(stdout.ends_with("\nA\ta.txt\n"), "{}", stdout);
+}
+
+#[test]
+fn test_log_shows_readable_dates() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit_stdout(dir, &["init"]);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    rit_stdout(dir, &["add", "a.txt"]);
+    rit_stdout(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+
+    // `YYYY-MM-DD HH:MM:SS +HHMM` by default, epoch seconds with `--date=raw`
+    let stdout = rit_stdout(dir, &["log"]);
+    let date = stdout.lines().find_map(|line| line.strip_prefix("Date:      ")).unwrap();
+    let shape: String = date.chars().map(|c| if c.is_ascii_digit() { '0' } else { c }).collect();
+    assert!(shape == "0000-00-00 00:00:00 +0000" || shape == "0000-00-00 00:00:00 -0000", "{}", date);
+
+    let stdout = rit_stdout(dir, &["log", "--date=raw"]);
+    let date = stdout.lines().find_map(|line| line.strip_prefix("Date:      ")).unwrap();
+    let (seconds, offset) = date.split_once(' ').unwrap();
+    assert!(seconds.parse::<u64>().is_ok(), "{}", date);
+    assert_eq!(offset.len(), 5, "{}", date);
+}
+
+#[test]
+fn test_abbreviated_keys() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit_stdout(dir, &["init"]);
+    fs::write(dir.join("file.txt"), "content").unwrap();
+    rit_stdout(dir, &["add", "file.txt"]);
+    let commit_hash = rit_stdout(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]).trim().to_string();
+
+    let short = &commit_hash[..8];
+    assert_eq!(rit_stdout(dir, &["cat-file", short]), rit_stdout(dir, &["cat-file", &commit_hash]));
+    assert_eq!(rit_stdout(dir, &["ls-tree", short]), rit_stdout(dir, &["ls-tree", "HEAD"]));
+    let blob = rit_stdout(dir, &["ls-tree", "HEAD"]).split('\t').nth(1).unwrap().to_string();
+    assert_eq!(rit_stdout(dir, &["blob", &blob[..10]]), "content\n");
+
+    let output = rit(dir, &["cat-file", "0000000"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_write_tree_and_read_tree() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit_stdout(dir, &["init"]);
+    fs::create_dir_all(dir.join("docs")).unwrap();
+    fs::write(dir.join("docs/guide.md"), "guide\n").unwrap();
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    rit_stdout(dir, &["add", "docs/guide.md"]);
+    rit_stdout(dir, &["add", "a.txt"]);
+
+    // The tree written from the index is the one a commit of it records
+    let tree = rit_stdout(dir, &["write-tree"]).trim().to_string();
+    assert_eq!(rit_stdout(dir, &["ls-tree", "--name-only", &tree]), "a.txt\ndocs\n");
+    rit_stdout(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    assert!(rit_stdout(dir, &["cat-file", "HEAD"]).starts_with(&format!("tree {}\n", tree)));
+
+    fs::write(dir.join("b.txt"), "two\n").unwrap();
+    rit_stdout(dir, &["add", "b.txt"]);
+    let staged = rit_stdout(dir, &["ls-files", "-s"]);
+    assert_ne!(rit_stdout(dir, &["write-tree"]).trim(), tree);
+
+    // Reading the tree back drops b.txt from the index but leaves the file on disk
+    rit_stdout(dir, &["read-tree", &tree]);
+    assert_eq!(rit_stdout(dir, &["ls-files"]), "a.txt\ndocs/guide.md\n");
+    assert_eq!(rit_stdout(dir, &["write-tree"]).trim(), tree);
+    assert!(dir.join("b.txt").exists());
+    rit_stdout(dir, &["add", "b.txt"]);
+    assert_eq!(rit_stdout(dir, &["ls-files", "-s"]), staged);
+    rit_stdout(dir, &["read-tree", "HEAD"]);
+    assert_eq!(rit_stdout(dir, &["ls-files"]), "a.txt\ndocs/guide.md\n");
+}
+
+#[test]
+fn test_show_and_ls_tree_accept_tags() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit_stdout(dir, &["init"]);
+    fs::write(dir.join("file.txt"), "one").unwrap();
+    rit_stdout(dir, &["add", "file.txt"]);
+    let first = rit_stdout(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]).trim().to_string();
+    fs::write(dir.join("file.txt"), "two").unwrap();
+    rit_stdout(dir, &["add", "file.txt"]);
+    rit_stdout(dir, &["commit", "-m", "second", "-c", "Tester <tester@example.com>"]);
+    rit_stdout(dir, &["tag", "light", &first]);
+    rit_stdout(dir, &["tag", "-m", "release", "--tagger", "Tagger <tagger@example.com>", "v1"]);
+
+    let show_head = rit_stdout(dir, &["show", "HEAD"]);
+    assert!(show_head.contains("    second\n"), "{}", show_head);
+    // An annotated tag shows the commit it tags, and a lightweight one the commit it names
+    assert_eq!(rit_stdout(dir, &["show", "v1"]), show_head);
+    assert!(rit_stdout(dir, &["show", "light"]).starts_with(&format!("commit {}\n", first)));
+    assert!(rit_stdout(dir, &["show", "v1~1"]).starts_with(&format!("commit {}\n", first)));
+
+    // The tag name itself still names the tag object
+    assert_eq!(rit_stdout(dir, &["cat-file", "-t", "v1"]), "tag\n");
+    assert_eq!(rit_stdout(dir, &["ls-tree", "v1"]), rit_stdout(dir, &["ls-tree", "HEAD"]));
+}
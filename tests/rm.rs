@@ -0,0 +1,35 @@
+// Runs `rit rm` through the CLI to check what it removes from the index and the working tree.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+#[test]
+fn test_rm_removes_from_index_and_working_tree() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("a.txt"), "a\n").unwrap();
+    fs::write(dir.join("b.txt"), "b\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    rit(dir, &["add", "b.txt"]);
+
+    let output = rit(dir, &["rm", "a.txt"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "rm 'a.txt'\n");
+    assert!(!dir.join("a.txt").exists());
+
+    // --cached keeps the file, which then shows as untracked
+    assert!(rit(dir, &["rm", "--cached", "b.txt"]).status.success());
+    assert!(dir.join("b.txt").exists());
+    let status = String::from_utf8_lossy(&rit(dir, &["status"]).stdout).to_string();
+    assert!(status.ends_with("\n0 staged changes, 0 unstaged changes, 1 untracked file\n"), "{}", status);
+
+    let output = rit(dir, &["rm", "b.txt"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pathspec 'b.txt' did not match any tracked files"));
+    assert!(dir.join("b.txt").exists());
+}
@@ -0,0 +1,96 @@
+// Runs `rit status` through the CLI to check the summary line, exit status and short format.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+#[test]
+fn test_status_exit_code() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+
+    let output = rit(dir, &["status", "--exit-code"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("\nnothing to commit, working tree clean\n"));
+
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    let output = rit(dir, &["status", "--exit-code"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("\n0 staged changes, 0 unstaged changes, 1 untracked file\n"));
+
+    rit(dir, &["add", "src/main.rs"]);
+    let output = rit(dir, &["status", "--exit-code"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("\n1 staged change, 0 unstaged changes, 0 untracked files\n"));
+    assert_eq!(rit(dir, &["status", "--porcelain=v2", "--exit-code"]).status.code(), Some(1));
+
+    // Without the flag a dirty tree still exits 0, for people running it by hand
+    assert_eq!(rit(dir, &["status"]).status.code(), Some(0));
+}
+
+#[test]
+fn test_status_sees_files_outside_src() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("notes.txt"), "notes\n").unwrap();
+    rit(dir, &["add", "notes.txt"]);
+    rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    assert_eq!(rit(dir, &["status", "--exit-code"]).status.code(), Some(0));
+
+    fs::write(dir.join("notes.txt"), "edited\n").unwrap();
+    let output = rit(dir, &["status", "--exit-code"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("modified:   notes.txt"));
+}
+
+#[test]
+fn test_status_without_untracked_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("notes.txt"), "notes\n").unwrap();
+    rit(dir, &["add", "notes.txt"]);
+    rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    fs::write(dir.join("scratch.txt"), "scratch\n").unwrap();
+
+    assert_eq!(rit(dir, &["status", "--exit-code"]).status.code(), Some(1));
+    for flag in ["-uno", "--untracked-files=no"] {
+        let output = rit(dir, &["status", flag, "--exit-code"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("scratch.txt"));
+        assert!(stdout.contains("Untracked files not listed"));
+    }
+    let porcelain = rit(dir, &["status", "--porcelain=v2", "-uno"]);
+    assert!(porcelain.stdout.is_empty());
+}
+
+#[test]
+fn test_status_short() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("kept.txt"), "kept\n").unwrap();
+    fs::write(dir.join("gone.txt"), "gone\n").unwrap();
+    rit(dir, &["add", "kept.txt"]);
+    rit(dir, &["add", "gone.txt"]);
+    rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+
+    fs::write(dir.join("kept.txt"), "changed\n").unwrap();
+    fs::remove_file(dir.join("gone.txt")).unwrap();
+    fs::write(dir.join("added.txt"), "added\n").unwrap();
+    rit(dir, &["add", "added.txt"]);
+    fs::write(dir.join("new.txt"), "new\n").unwrap();
+
+    let output = rit(dir, &["status", "--short"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "A  added.txt\n D gone.txt\n M kept.txt\n?? new.txt\n");
+    let output = rit(dir, &["status", "-sb"]);
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("## master\nA  added.txt\n"));
+}
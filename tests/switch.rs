@@ -0,0 +1,105 @@
+// Runs `rit switch` and `rit checkout -b` through the CLI to check HEAD and the working tree follow.
+
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+
+use common::rit;
+
+#[test]
+fn test_switch_between_branches() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+
+    let output = rit(dir, &["checkout", "-b", "topic"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Switched to a new branch 'topic'\n");
+    assert_eq!(fs::read_to_string(dir.join(".rit/HEAD")).unwrap(), "ref: refs/heads/topic\n");
+
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    rit(dir, &["commit", "-m", "second", "-c", "Tester <tester@example.com>"]);
+
+    let output = rit(dir, &["switch", "master"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Switched to branch 'master'\n");
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\n");
+
+    fs::write(dir.join("a.txt"), "dirty\n").unwrap();
+    let output = rit(dir, &["switch", "topic"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("would be overwritten by switch"));
+    assert_eq!(fs::read_to_string(dir.join(".rit/HEAD")).unwrap(), "ref: refs/heads/master\n");
+
+    assert!(!rit(dir, &["switch", "nope"]).status.success());
+}
+
+#[test]
+fn test_checkout_older_commit_detaches_head() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("a"), "one\n").unwrap();
+    rit(dir, &["add", "a"]);
+    rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+    fs::write(dir.join("a"), "two\n").unwrap();
+    fs::write(dir.join("b"), "two\n").unwrap();
+    rit(dir, &["add", "a"]);
+    rit(dir, &["add", "b"]);
+    rit(dir, &["commit", "-m", "second", "-c", "Tester <tester@example.com>"]);
+    let tip = fs::read_to_string(dir.join(".rit/refs/heads/master")).unwrap();
+
+    let output = rit(dir, &["checkout", "HEAD~1"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("HEAD is now detached at "));
+    assert!(!fs::read_to_string(dir.join(".rit/HEAD")).unwrap().starts_with("ref:"));
+
+    // The old tree is what HEAD holds now, so nothing shows as changed
+    let output = rit(dir, &["status", "-s"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+
+    // A commit on the detached HEAD leaves the branch alone
+    fs::write(dir.join("a"), "detached\n").unwrap();
+    rit(dir, &["add", "a"]);
+    assert!(rit(dir, &["commit", "-m", "detached", "-c", "Tester <tester@example.com>"]).status.success());
+    assert_eq!(fs::read_to_string(dir.join(".rit/refs/heads/master")).unwrap(), tip);
+
+    let output = rit(dir, &["checkout", "master"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Switched to branch 'master'\n");
+    assert_eq!(fs::read_to_string(dir.join(".rit/HEAD")).unwrap(), "ref: refs/heads/master\n");
+    assert_eq!(fs::read_to_string(dir.join("b")).unwrap(), "two\n");
+}
+
+#[test]
+fn test_branch_names_with_slashes() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"]);
+
+    let output = rit(dir, &["branch", "feature/x"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let output = rit(dir, &["switch", "-c", "feature/y"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(fs::read_to_string(dir.join(".rit/HEAD")).unwrap(), "ref: refs/heads/feature/y\n");
+
+    let output = rit(dir, &["branch"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "  feature/x\n* feature/y\n  master\n");
+
+    // Commits move the whole slashed branch, not a shortened name
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    assert!(rit(dir, &["commit", "-m", "second", "-c", "Tester <tester@example.com>"]).status.success());
+    assert!(!dir.join(".rit/refs/heads/feature").is_file());
+    assert_ne!(fs::read_to_string(dir.join(".rit/refs/heads/feature/y")).unwrap(), fs::read_to_string(dir.join(".rit/refs/heads/feature/x")).unwrap());
+
+    assert!(rit(dir, &["switch", "feature/x"]).status.success());
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\n");
+}
@@ -0,0 +1,41 @@
+// Runs `rit tag` through the CLI to check creating, listing and replacing tags.
+
+mod common;
+
+use std::fs;
+use std::process::Output;
+use tempfile::TempDir;
+
+use common::rit;
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_tag_create_list_and_force() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    rit(dir, &["init"]);
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    let first = stdout(&rit(dir, &["commit", "-m", "first", "-c", "Tester <tester@example.com>"])).trim().to_string();
+
+    stdout(&rit(dir, &["tag", "v1"]));
+    assert_eq!(fs::read_to_string(dir.join(".rit/refs/tags/v1")).unwrap().trim(), first);
+    stdout(&rit(dir, &["tag", "-m", "Release two", "--tagger", "Tagger <tagger@example.com>", "v2"]));
+    assert_eq!(stdout(&rit(dir, &["tag"])), "v1\nv2\n");
+    assert!(stdout(&rit(dir, &["verify-tag", "v2"])).contains(&format!("object {}\ntype commit\ntag v2", first)));
+
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    rit(dir, &["add", "a.txt"]);
+    let second = stdout(&rit(dir, &["commit", "-m", "second", "-c", "Tester <tester@example.com>"])).trim().to_string();
+
+    // An existing tag stays put unless forced
+    let output = rit(dir, &["tag", "v1"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    stdout(&rit(dir, &["tag", "-f", "v1", "HEAD"]));
+    assert_eq!(fs::read_to_string(dir.join(".rit/refs/tags/v1")).unwrap().trim(), second);
+}